@@ -0,0 +1,118 @@
+# a helper called from 50 sites, duplicated once per call site without the summaries
+	.text
+
+	.type	helper,@function
+helper:
+	movl	%edi, %eax
+	cmpl	$0, %edi
+	jle	.Lnegative
+	addl	$1, %eax
+	imull	%eax, %eax
+	retq
+.Lnegative:
+	negl	%eax
+	retq
+
+	.type	many_calls,@function
+many_calls:
+	movl	$1, %edi
+	callq	helper
+	movl	$2, %edi
+	callq	helper
+	movl	$3, %edi
+	callq	helper
+	movl	$4, %edi
+	callq	helper
+	movl	$5, %edi
+	callq	helper
+	movl	$6, %edi
+	callq	helper
+	movl	$7, %edi
+	callq	helper
+	movl	$8, %edi
+	callq	helper
+	movl	$9, %edi
+	callq	helper
+	movl	$10, %edi
+	callq	helper
+	movl	$11, %edi
+	callq	helper
+	movl	$12, %edi
+	callq	helper
+	movl	$13, %edi
+	callq	helper
+	movl	$14, %edi
+	callq	helper
+	movl	$15, %edi
+	callq	helper
+	movl	$16, %edi
+	callq	helper
+	movl	$17, %edi
+	callq	helper
+	movl	$18, %edi
+	callq	helper
+	movl	$19, %edi
+	callq	helper
+	movl	$20, %edi
+	callq	helper
+	movl	$21, %edi
+	callq	helper
+	movl	$22, %edi
+	callq	helper
+	movl	$23, %edi
+	callq	helper
+	movl	$24, %edi
+	callq	helper
+	movl	$25, %edi
+	callq	helper
+	movl	$26, %edi
+	callq	helper
+	movl	$27, %edi
+	callq	helper
+	movl	$28, %edi
+	callq	helper
+	movl	$29, %edi
+	callq	helper
+	movl	$30, %edi
+	callq	helper
+	movl	$31, %edi
+	callq	helper
+	movl	$32, %edi
+	callq	helper
+	movl	$33, %edi
+	callq	helper
+	movl	$34, %edi
+	callq	helper
+	movl	$35, %edi
+	callq	helper
+	movl	$36, %edi
+	callq	helper
+	movl	$37, %edi
+	callq	helper
+	movl	$38, %edi
+	callq	helper
+	movl	$39, %edi
+	callq	helper
+	movl	$40, %edi
+	callq	helper
+	movl	$41, %edi
+	callq	helper
+	movl	$42, %edi
+	callq	helper
+	movl	$43, %edi
+	callq	helper
+	movl	$44, %edi
+	callq	helper
+	movl	$45, %edi
+	callq	helper
+	movl	$46, %edi
+	callq	helper
+	movl	$47, %edi
+	callq	helper
+	movl	$48, %edi
+	callq	helper
+	movl	$49, %edi
+	callq	helper
+	movl	$50, %edi
+	callq	helper
+	retq
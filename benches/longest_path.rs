@@ -0,0 +1,87 @@
+//! Times the longest path on the condensed graph of a large generated DAG, relaxing its edges
+//! in topological order, against Bellman-Ford on the negated weights (`cargo bench`).
+
+use std::time::{Duration, Instant};
+
+use petgraph::algo::bellman_ford;
+use timing_analysis_tool::instruction::Instruction;
+use timing_analysis_tool::{Block, MappedGraph};
+
+const NODES: u64 = 5_000;
+const FAN_OUT: u64 = 3; // edges from each block to the following ones
+const RUNS: u32 = 20;
+
+/// Returns a DAG of `NODES` blocks, each one jumping to `FAN_OUT` pseudo-random later blocks
+/// (a linear congruential generator keeps the graph the same across runs).
+fn generated_dag() -> MappedGraph {
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = |bound: u64| {
+        seed = seed
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (seed >> 33) % bound
+    };
+    let blocks = (0..NODES)
+        .map(|index| {
+            Block::new(Instruction {
+                address: index * 4,
+                mnemonic: "nop".to_string(),
+                operands: Vec::new(),
+                size: 4,
+                latency: 1 + next(20) as u32,
+            })
+        })
+        .collect::<Vec<Block>>();
+
+    let mut graph = MappedGraph::new();
+    for (index, block) in blocks.iter().enumerate().take(NODES as usize - 1) {
+        for _ in 0..FAN_OUT {
+            let target = &blocks[index + 1 + next(NODES - index as u64 - 1) as usize];
+            graph.add_edge(block.clone(), target.clone(), target.get_latency() as f64);
+        }
+    }
+
+    graph
+}
+
+fn main() {
+    let mut graph = generated_dag();
+    let condensed_graph = graph.condense_cycles();
+    let source = condensed_graph.scc_of(0).expect("No source block").to_vec();
+
+    let mut latency = 0.0;
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        latency = condensed_graph
+            .longest_path(&source)
+            .expect("Generated graph not acyclic");
+    }
+    let topological: Duration = start.elapsed() / RUNS;
+
+    // the longest path is the shortest one on the negated weights
+    let mut negated = condensed_graph.graph.clone();
+    for weight in negated.edge_weights_mut() {
+        *weight = -*weight;
+    }
+    let start = Instant::now();
+    let paths = bellman_ford(&negated, condensed_graph.node_index_map[&0])
+        .expect("Generated graph not acyclic");
+    let bellman_ford_time = start.elapsed();
+    let bellman_ford_latency = paths
+        .distances
+        .iter()
+        .filter(|distance| distance.is_finite())
+        .fold(0.0, |max: f64, distance| max.max(-distance));
+    assert_eq!(latency, bellman_ford_latency, "The longest paths differ");
+
+    println!(
+        "{NODES} blocks, {} edges, longest path {latency}",
+        condensed_graph.graph.edge_count()
+    );
+    println!("topological order: {topological:?}");
+    println!("bellman-ford: {bellman_ford_time:?}");
+    println!(
+        "speedup: {:.1}x",
+        bellman_ford_time.as_secs_f64() / topological.as_secs_f64()
+    );
+}
@@ -0,0 +1,57 @@
+//! Times the analysis of a helper called from 50 sites, with and without the summaries of
+//! the called functions (`cargo bench`).
+
+use std::time::{Duration, Instant};
+
+use timing_analysis_tool::{analyze_bytes, Config};
+
+use crate::common::function_address;
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+// llvm-mc -triple=x86_64-linux-gnu -filetype=obj -o helper_calls.o helper_calls.s
+const FIXTURE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/benches/fixtures/helper_calls.o"
+);
+
+const RUNS: u32 = 20;
+
+/// Returns the WCET and the mean time of the analysis.
+fn run(bytes: &[u8], config: &Config) -> (u64, Duration) {
+    let mut wcet = 0;
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        wcet = analyze_bytes(bytes, config).expect("Analysis failed").wcet;
+    }
+
+    (wcet, start.elapsed() / RUNS)
+}
+
+fn main() {
+    let bytes = std::fs::read(FIXTURE).expect("Unable to read the fixture");
+    let entry = function_address(&bytes, "many_calls");
+
+    let config = Config {
+        graphs_dir: None,
+        entry: Some(entry),
+        ..Config::default()
+    };
+    let (wcet, duplicated) = run(&bytes, &config);
+    let (summarized_wcet, summarized) = run(
+        &bytes,
+        &Config {
+            summarize_calls: true,
+            ..config
+        },
+    );
+    assert_eq!(wcet, summarized_wcet, "The summaries changed the WCET");
+
+    println!("duplicated calls: {duplicated:?} per analysis");
+    println!("summarized calls: {summarized:?} per analysis");
+    println!(
+        "speedup: {:.1}x",
+        duplicated.as_secs_f64() / summarized.as_secs_f64()
+    );
+}
@@ -0,0 +1,82 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::config::Config;
+use crate::disassemble_object;
+use crate::error::WcetError;
+use crate::graph::MappedGraph;
+use crate::latency::LatencyTable;
+use crate::wcet::{build_control_flow, build_graph, ControlFlow, WcetReport};
+
+/// Analysis of an object file whose blocks and graph are built once, to compute its WCET with
+/// different latency tables without disassembling it again (e.g. while tuning a table): only
+/// the latencies of the instructions, the summaries of the calls and the weights of the edges
+/// are recomputed.
+pub struct Analysis {
+    content_hash: u64,         // hash of the content of the object file
+    config: Config,            // configuration completed from the object file
+    control_flow: ControlFlow, // blocks, without them if only the disassembly is listed
+    graph: MappedGraph,        // graph of the blocks
+}
+
+impl Analysis {
+    /// Disassembles the object file contained in `bytes` and builds its blocks and graph, with
+    /// `config` completed from the object as in `analyze_bytes` (the `core_latency_tables` are
+    /// ignored).
+    pub fn new(bytes: &[u8], config: &Config) -> Result<Self, WcetError> {
+        let ((control_flow, config), _) =
+            disassemble_object(bytes, config, |cs, arch_mode, sections, config| {
+                Ok((
+                    build_control_flow(cs, arch_mode, sections, config)?,
+                    config.clone(),
+                ))
+            })?;
+        let config = Config {
+            core_latency_tables: Vec::new(),
+            ..config
+        };
+        let graph = build_graph(&control_flow.blocks, config.cost_model())?;
+
+        Ok(Analysis {
+            content_hash: Analysis::content_hash(bytes),
+            config,
+            control_flow,
+            graph,
+        })
+    }
+
+    /// Returns the hash identifying the content of an object file, to reuse the analysis of a
+    /// file read again by the same process only if it has not changed (see `is_of`). The hash
+    /// may change with the version of Rust the tool is built with, so it is not a key to
+    /// store, e.g. in a persistent cache.
+    pub fn content_hash(bytes: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns whether `bytes` is the content of the analyzed object file.
+    pub fn is_of(&self, bytes: &[u8]) -> bool {
+        Analysis::content_hash(bytes) == self.content_hash
+    }
+
+    /// Computes the WCET report with the current latency table, the one of the configuration
+    /// or the last one given to `with_latencies`.
+    pub fn report(&self) -> Result<WcetReport, WcetError> {
+        self.control_flow
+            .analyze_with_graph(&self.graph, &self.config)
+    }
+
+    /// Replaces the latency table with `latency_table`, reusing the blocks and updating their
+    /// graph in place, and computes the WCET report.
+    pub fn with_latencies(
+        &mut self,
+        latency_table: &LatencyTable,
+    ) -> Result<WcetReport, WcetError> {
+        self.config.latency_table = latency_table.clone();
+        self.control_flow
+            .set_latencies(&mut self.graph, latency_table, &self.config)?;
+
+        self.report()
+    }
+}
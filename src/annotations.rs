@@ -0,0 +1,65 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::bounds::parse_address;
+
+/// Extra clock cycles of hardware events that are not visible in the instructions (e.g. a DMA
+/// wait or a memory stall measured at a known address), keyed by the address of the
+/// instruction they are charged to. The costs are loaded from a TOML file, e.g.:
+///
+/// ```toml
+/// 0x1054 = 40
+/// ```
+///
+/// The cost is added to the latency of the instruction, so it is counted every time its
+/// block is executed.
+#[derive(Debug, Clone, Default)]
+pub struct CostAnnotations {
+    pub costs: BTreeMap<u64, u32>, // address -> clock cycles
+}
+
+impl CostAnnotations {
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(path)?;
+        let file: BTreeMap<String, u32> = toml::from_str(&content)?;
+
+        let mut annotations = CostAnnotations::default();
+        for (address, cost) in file {
+            annotations.costs.insert(parse_address(&address)?, cost);
+        }
+
+        Ok(annotations)
+    }
+}
+
+/// Latencies of single instructions, keyed by their address, replacing the ones of the latency
+/// table (e.g. a load that always accesses a slow peripheral). They are loaded from a TOML
+/// file, e.g.:
+///
+/// ```toml
+/// 0x1060 = 120
+/// ```
+///
+/// The latency of the address takes precedence over the environment variables, the operand
+/// rules, the mnemonics, the groups and the default of the latency table, while the cost
+/// annotations are still added to it.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyOverrides {
+    pub latencies: BTreeMap<u64, u32>, // address -> clock cycles
+}
+
+impl LatencyOverrides {
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(path)?;
+        let file: BTreeMap<String, u32> = toml::from_str(&content)?;
+
+        let mut overrides = LatencyOverrides::default();
+        for (address, latency) in file {
+            overrides
+                .latencies
+                .insert(parse_address(&address)?, latency);
+        }
+
+        Ok(overrides)
+    }
+}
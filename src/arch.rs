@@ -1,4 +1,19 @@
 use capstone::{Arch, Mode};
+use object::{Object, ObjectSection, ObjectSymbol};
+
+use crate::error::WcetError;
+
+// architectures whose jumps are recognized by get_exit_jump
+pub const SUPPORTED_ARCHS: [Arch; 8] = [
+    Arch::X86,
+    Arch::ARM,
+    Arch::ARM64,
+    Arch::MIPS,
+    Arch::PPC,
+    Arch::SPARC,
+    Arch::RISCV,
+    Arch::SYSZ,
+];
 
 #[derive(Debug, Clone)]
 pub struct ArchMode {
@@ -6,9 +21,62 @@ pub struct ArchMode {
     pub mode: Mode,
 }
 
-impl From<object::Architecture> for ArchMode {
-    fn from(value: object::Architecture) -> Self {
-        match value {
+impl ArchMode {
+    /// Builds the architecture from its name and the name of its mode (e.g. `arm` and `thumb`),
+    /// for the raw binaries without an object header. Without a mode, the first mode of the
+    /// architecture is used.
+    pub fn from_names(arch: &str, mode: Option<&str>) -> Result<Self, WcetError> {
+        let invalid =
+            || WcetError::InvalidArchMode(arch.to_string(), mode.unwrap_or_default().to_string());
+
+        let (capstone_arch, modes): (Arch, &[(&str, Mode)]) = match arch.to_lowercase().as_str() {
+            "x86" => (
+                Arch::X86,
+                &[
+                    ("64", Mode::Mode64),
+                    ("32", Mode::Mode32),
+                    ("16", Mode::Mode16),
+                ],
+            ),
+            "arm" => (Arch::ARM, &[("arm", Mode::Arm), ("thumb", Mode::Thumb)]),
+            "arm64" | "aarch64" => (Arch::ARM64, &[("arm", Mode::Arm)]),
+            "mips" => (
+                Arch::MIPS,
+                &[("mips32", Mode::Mips32), ("mips64", Mode::Mips64)],
+            ),
+            "ppc" | "powerpc" => (Arch::PPC, &[("32", Mode::Mode32), ("64", Mode::Mode64)]),
+            "sparc" => (Arch::SPARC, &[("default", Mode::Default), ("v9", Mode::V9)]),
+            "riscv" => (
+                Arch::RISCV,
+                &[("riscv32", Mode::RiscV32), ("riscv64", Mode::RiscV64)],
+            ),
+            "sysz" | "s390x" => (Arch::SYSZ, &[("default", Mode::Default)]),
+            _ => return Err(invalid()),
+        };
+
+        let mode = match mode {
+            Some(mode) => {
+                modes
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(mode))
+                    .ok_or_else(invalid)?
+                    .1
+            }
+            None => modes[0].1,
+        };
+
+        Ok(ArchMode {
+            arch: capstone_arch,
+            mode,
+        })
+    }
+}
+
+impl TryFrom<object::Architecture> for ArchMode {
+    type Error = WcetError;
+
+    fn try_from(value: object::Architecture) -> Result<Self, Self::Error> {
+        let arch_mode = match value {
             object::Architecture::X86_64 => ArchMode {
                 arch: Arch::X86,
                 mode: Mode::Mode64,
@@ -53,7 +121,59 @@ impl From<object::Architecture> for ArchMode {
                 arch: Arch::SPARC,
                 mode: Mode::V9,
             },
-            _ => panic!("unsupported architecture"),
+            object::Architecture::S390x => ArchMode {
+                arch: Arch::SYSZ,
+                mode: Mode::Default,
+            },
+            _ => return Err(WcetError::UnsupportedArch(value)),
+        };
+
+        Ok(arch_mode)
+    }
+}
+
+/// Returns the code regions `[start, end)` of an ARM section with their mode, delimited by the
+/// mapping symbols (`$a` for ARM code, `$t` for Thumb code, `$d` for data, which is skipped).
+/// Without mapping symbols the whole section is disassembled in the default mode.
+pub fn arm_code_regions(
+    obj_file: &object::File,
+    section: &object::Section,
+    default_mode: Mode,
+) -> Vec<(u64, u64, Mode)> {
+    let section_start = section.address();
+    let section_end = section.address() + section.size();
+
+    let mut mapping_symbols = obj_file
+        .symbols()
+        .filter(|symbol| symbol.section_index() == Some(section.index()))
+        .filter_map(|symbol| {
+            let mode = match symbol.name().ok()?.split('.').next()? {
+                "$a" => Some(Mode::Arm),
+                "$t" => Some(Mode::Thumb),
+                "$d" => None,
+                _ => return None,
+            };
+            Some((symbol.address(), mode))
+        })
+        .collect::<Vec<(u64, Option<Mode>)>>();
+    mapping_symbols.sort_by_key(|(address, _)| *address);
+
+    if mapping_symbols.is_empty() {
+        return vec![(section_start, section_end, default_mode)];
+    }
+
+    let mut regions = Vec::new();
+    for (index, (start, mode)) in mapping_symbols.iter().enumerate() {
+        let end = match mapping_symbols.get(index + 1) {
+            Some((next_start, _)) => *next_start,
+            None => section_end,
+        };
+        if let Some(mode) = mode {
+            if start < &end {
+                regions.push((*start, end, *mode));
+            }
         }
     }
+
+    regions
 }
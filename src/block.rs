@@ -1,11 +1,34 @@
+use std::ops::Range;
+
+use crate::config::CacheConfig;
+use crate::cost::{checked_cost, CostModel};
+use crate::error::WcetError;
 use crate::instruction::Instruction;
 use crate::jump::ExitJump;
 
-#[derive(Default, Clone, Hash, PartialEq, Eq)]
+/// Basic block of the control flow graph. A block is identified by its leader: the equality
+/// and the hash only consider the leader, like the maps keyed by leader, and not the
+/// instructions nor the exit jump.
+#[derive(Default, Clone)]
 pub struct Block {
     pub leader: u64,
     pub instructions: Vec<Instruction>,
     pub exit_jump: Option<ExitJump>,
+    pub function: String, // name of the enclosing function
+}
+
+impl PartialEq for Block {
+    fn eq(&self, other: &Self) -> bool {
+        self.leader == other.leader
+    }
+}
+
+impl Eq for Block {}
+
+impl std::hash::Hash for Block {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.leader.hash(state);
+    }
 }
 
 impl Block {
@@ -14,6 +37,7 @@ impl Block {
             leader: instruction.address,
             instructions: vec![instruction],
             exit_jump: None,
+            function: String::new(),
         }
     }
 
@@ -45,8 +69,14 @@ impl Block {
                     targets.push(*target);
                 }
                 ExitJump::Indirect => {}
+                ExitJump::IndirectResolved(indirect_targets) => {
+                    targets.extend(indirect_targets);
+                }
                 ExitJump::Ret(ret_targets) => {
-                    targets.push(*ret_targets);
+                    // 0 means that the return address is unknown
+                    if *ret_targets != 0 {
+                        targets.push(*ret_targets);
+                    }
                 }
                 ExitJump::Call(target, _) => {
                     targets.push(*target);
@@ -60,6 +90,37 @@ impl Block {
         targets
     }
 
+    /// Returns the weight of the edge to `target`: the cost of the target block plus the cost
+    /// of the control transfer, e.g. the latencies of a `LatencyTable`.
+    pub fn edge_latency(
+        &self,
+        target: &Block,
+        cost_model: &dyn CostModel,
+    ) -> Result<f64, WcetError> {
+        Ok(target.cost(cost_model)? + self.transfer_latency(target, cost_model)?)
+    }
+
+    /// Returns the cost of the control transfer to `target` through the exit jump, e.g. the
+    /// `branch_penalty` of the taken side of a conditional jump, or 0 without exit jump.
+    pub fn transfer_latency(
+        &self,
+        target: &Block,
+        cost_model: &dyn CostModel,
+    ) -> Result<f64, WcetError> {
+        match &self.exit_jump {
+            Some(exit_jump) => {
+                checked_cost(cost_model.edge_cost(self, target, exit_jump), self.leader)
+            }
+            None => Ok(0.0),
+        }
+    }
+
+    /// Returns the cost of an execution of the block, its latency with a `LatencyTable`, failing
+    /// if the cost model returns a negative or non-finite cost.
+    pub fn cost(&self, cost_model: &dyn CostModel) -> Result<f64, WcetError> {
+        checked_cost(cost_model.block_cost(self), self.leader)
+    }
+
     pub fn modify_targets(&mut self, new_target: u64, target: u64) {
         if let Some(exit_jump) = &mut self.clone().exit_jump {
             match exit_jump {
@@ -96,6 +157,13 @@ impl Block {
                     self.set_exit_jump(ExitJump::UnconditionalAbsolute(new_target));
                 }
                 ExitJump::Indirect => {}
+                ExitJump::IndirectResolved(targets) => {
+                    let targets = targets
+                        .iter()
+                        .map(|t| if *t == target { new_target } else { *t })
+                        .collect();
+                    self.set_exit_jump(ExitJump::IndirectResolved(targets));
+                }
                 ExitJump::Ret(_) => {
                     self.set_exit_jump(ExitJump::Ret(new_target));
                 }
@@ -109,9 +177,65 @@ impl Block {
         }
     }
 
+    /// Returns the addresses `[start, end)` of the bytes of the instructions of the block.
+    pub fn byte_range(&self) -> Range<u64> {
+        let start = self.instructions.first().map_or(0, |first| first.address);
+        let end = self
+            .instructions
+            .last()
+            .map_or(start, |last| last.address + last.size as u64);
+        start..end
+    }
+
     pub fn get_latency(&self) -> u32 {
         self.instructions.iter().map(|i| i.latency).sum()
     }
+
+    /// Returns the addresses of the instructions using the register written by the previous
+    /// one (a read after write hazard stalling the pipeline).
+    pub fn hazards(&self) -> Vec<u64> {
+        self.instructions
+            .windows(2)
+            .filter(|pair| {
+                pair[0]
+                    .written_register()
+                    .is_some_and(|register| pair[1].uses_register(register))
+            })
+            .map(|pair| pair[1].address)
+            .collect()
+    }
+
+    /// Adds `penalty` to the latency of each instruction of `hazards`. Returns the number of
+    /// hazards found.
+    pub fn add_hazard_penalties(&mut self, penalty: u32) -> usize {
+        let hazards = self.hazards();
+        for instruction in self.instructions.iter_mut() {
+            if hazards.contains(&instruction.address) {
+                instruction.latency += penalty;
+            }
+        }
+        hazards.len()
+    }
+
+    /// Adds the miss penalty of `cache` to the first instruction touching each cache line of
+    /// the block (an instruction can span two lines), so that its latency includes the loads
+    /// of all its lines. Returns the number of lines touched.
+    pub fn add_cache_miss_penalties(&mut self, cache: &CacheConfig) -> usize {
+        let mut lines = 0;
+        let mut last_line = None;
+        for instruction in &mut self.instructions {
+            let first_line = instruction.address / cache.line_size;
+            let end_line =
+                (instruction.address + instruction.size.max(1) as u64 - 1) / cache.line_size;
+            let start_line =
+                last_line.map_or(first_line, |last_line| first_line.max(last_line + 1));
+            let new_lines = (start_line..=end_line).count();
+            instruction.latency += new_lines as u32 * cache.miss_penalty;
+            last_line = Some(end_line);
+            lines += new_lines;
+        }
+        lines
+    }
 }
 
 impl std::fmt::Display for Block {
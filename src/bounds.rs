@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::printwarning;
+
+/// Maximum number of iterations of the cycles and of the recursive functions, keyed by address.
+/// The cycles can also be keyed by the name of their function and the ordinal of the cycle in
+/// it (`function#n`, starting from 1 in address order), which survives recompilation.
+///
+/// The bounds can be loaded from a TOML file, e.g.:
+///
+/// ```toml
+/// [cycles]
+/// 0x108d = 3
+/// "memcpy#2" = 64
+///
+/// [recursive]
+/// 0x1054 = 2
+///
+/// [rep]
+/// 0x10a2 = 256
+/// ```
+///
+/// The bound of a cycle (recursive function, x86 string instruction with a `rep` prefix) is
+/// resolved with the following precedence:
+/// 1. the `CYCLE_0x<address>` (`RECURSIVE_0x<address>`, `REP_0x<address>`) environment variable
+/// 2. the value of the address in the `cycles` (`recursive`, `rep`) section of the file
+/// 3. the value of the `function#n` name of the cycle in the `cycles` section of the file
+/// 4. the default of 1 iteration, applied by the caller
+#[derive(Debug, Clone, Default)]
+pub struct LoopBounds {
+    pub cycles: HashMap<u64, u32>, // cycle_entry_address -> max_cycles
+    pub named_cycles: HashMap<String, u32>, // function#ordinal -> max_cycles
+    pub recursive: HashMap<u64, u32>, // function_address -> max_recursions
+    pub rep: HashMap<u64, u32>,    // instruction_address -> max_iterations
+}
+
+#[derive(Deserialize)]
+struct LoopBoundsFile {
+    #[serde(default)]
+    cycles: HashMap<String, u32>,
+    #[serde(default)]
+    recursive: HashMap<String, u32>,
+    #[serde(default)]
+    rep: HashMap<String, u32>,
+}
+
+pub fn parse_address(address: &str) -> Result<u64, std::num::ParseIntError> {
+    match address.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => address.parse::<u64>(),
+    }
+}
+
+impl LoopBounds {
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(path)?;
+        let file: LoopBoundsFile = toml::from_str(&content)?;
+
+        let mut bounds = LoopBounds::default();
+        for (key, max_cycles) in file.cycles {
+            if key.contains('#') {
+                bounds.named_cycles.insert(key, max_cycles);
+            } else {
+                bounds.cycles.insert(parse_address(&key)?, max_cycles);
+            }
+        }
+        for (address, max_recursions) in file.recursive {
+            bounds
+                .recursive
+                .insert(parse_address(&address)?, max_recursions);
+        }
+        for (address, max_iterations) in file.rep {
+            bounds.rep.insert(parse_address(&address)?, max_iterations);
+        }
+
+        Ok(bounds)
+    }
+
+    pub fn cycle_bound(&self, address: u64, loop_name: Option<&String>) -> Option<u32> {
+        env_number(&format!("CYCLE_0x{address:x}"))
+            .or_else(|| self.cycles.get(&address).copied())
+            .or_else(|| loop_name.and_then(|name| self.named_cycles.get(name).copied()))
+    }
+
+    /// Returns where the bound of a cycle comes from, with the precedence of `cycle_bound`:
+    /// the environment variable, the address or the name in the file, or the default.
+    pub fn cycle_bound_origin(&self, address: u64, loop_name: Option<&String>) -> &'static str {
+        if env_number(&format!("CYCLE_0x{address:x}")).is_some() {
+            "environment variable"
+        } else if self.cycles.contains_key(&address) {
+            "address in the loop bounds file"
+        } else if loop_name.is_some_and(|name| self.named_cycles.contains_key(name)) {
+            "name in the loop bounds file"
+        } else {
+            "default"
+        }
+    }
+
+    pub fn recursive_bound(&self, address: u64) -> Option<u32> {
+        env_number(&format!("RECURSIVE_0x{address:x}"))
+            .or_else(|| self.recursive.get(&address).copied())
+    }
+
+    pub fn rep_bound(&self, address: u64) -> Option<u32> {
+        env_number(&format!("REP_0x{address:x}")).or_else(|| self.rep.get(&address).copied())
+    }
+}
+
+/// Returns the number given by the environment variable `env_var_key` (a bound or a latency),
+/// if it is set to a valid one; an invalid value is ignored with a warning.
+pub fn env_number(env_var_key: &str) -> Option<u32> {
+    match std::env::var(env_var_key) {
+        Ok(number) => match number.parse::<u32>() {
+            Ok(number) => Some(number),
+            Err(_) => {
+                printwarning!(
+                    "The environment variable {env_var_key} is not a valid number, it is ignored"
+                );
+                None
+            }
+        },
+        Err(_) => None,
+    }
+}
@@ -0,0 +1,415 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::LineWriter;
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, ValueEnum};
+use object::{Object, ObjectSymbol};
+
+use timing_analysis_tool::annotations::{CostAnnotations, LatencyOverrides};
+use timing_analysis_tool::bounds::LoopBounds;
+use timing_analysis_tool::config::{CacheConfig, Config, EntryMode, IndirectPolicy, Syntax};
+use timing_analysis_tool::indirect::IndirectTargets;
+use timing_analysis_tool::latency::{CpuModel, LatencyLayer, LatencyTable};
+use timing_analysis_tool::trace::TraceOutput;
+use timing_analysis_tool::WcetError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// Estimate the worst-case execution time (WCET) of an object file
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Path of the object file to analyze
+    pub file: PathBuf,
+
+    /// Analyze the file as a raw binary without an object header (e.g. a firmware dump),
+    /// whose architecture is given by --arch and --mode
+    #[arg(long, requires = "arch")]
+    pub raw: bool,
+
+    /// Architecture of the raw binary: x86, arm, arm64, mips, ppc, sparc, riscv or sysz
+    #[arg(long, requires = "raw")]
+    pub arch: Option<String>,
+
+    /// Mode of the raw binary (e.g. 32, 64, arm, thumb, mips32, v9, riscv64); the first mode of
+    /// the architecture by default
+    #[arg(long, requires = "raw")]
+    pub mode: Option<String>,
+
+    /// Address where the raw binary is loaded
+    #[arg(long, default_value = "0", value_name = "ADDRESS", value_parser = parse_number, requires = "raw")]
+    pub base: u64,
+
+    /// The raw binary is in big endian byte order (e.g. MIPS or PowerPC)
+    #[arg(long, requires = "raw")]
+    pub big_endian: bool,
+
+    /// Processor model whose built-in latencies are used as baseline (unit, generic, skylake,
+    /// zen2, cortex-a53 or cortex-a72), or profile of the latency file (on top of the generic
+    /// model); if repeated, the WCET is computed and reported for each one (e.g. the cores of
+    /// a heterogeneous SoC)
+    #[arg(long, value_name = "CPU", default_value = "generic")]
+    pub cpu: Vec<String>,
+
+    /// TOML or JSON file with the latencies of the instructions, layered on top of the CPU ones
+    #[arg(long)]
+    pub latency_file: Option<PathBuf>,
+
+    /// TOML file with the maximum iterations of cycles and recursive functions
+    #[arg(long)]
+    pub loop_bounds_file: Option<PathBuf>,
+
+    /// Fail, listing the cycles without a bound (environment variable or loop bounds file),
+    /// instead of counting a single iteration for each of them
+    #[arg(long)]
+    pub strict_bounds: bool,
+
+    /// TOML file with the possible targets of the indirect jumps
+    #[arg(long)]
+    pub indirect_targets_file: Option<PathBuf>,
+
+    /// Handling of the indirect jumps and calls without targets in the indirect targets file:
+    /// ignore them, make them jump to every leader for a sound WCET (conservative), or make the
+    /// jumps reach the leaders of their function only (same-function)
+    #[arg(long, value_enum, default_value_t = IndirectPolicy::Ignore)]
+    pub indirect_policy: IndirectPolicy,
+
+    /// Possible target (symbol name or address) of the conservative indirect jumps, instead of
+    /// every leader; can be repeated
+    #[arg(long, value_name = "ADDRESS")]
+    pub indirect_candidate: Vec<String>,
+
+    /// TOML file with the extra clock cycles of the instructions at given addresses (e.g.
+    /// measured memory stalls)
+    #[arg(long)]
+    pub annotations_file: Option<PathBuf>,
+
+    /// TOML file with the latencies of the instructions at given addresses, replacing the ones
+    /// of the latency tables (e.g. a load from a slow peripheral)
+    #[arg(long)]
+    pub latency_overrides_file: Option<PathBuf>,
+
+    /// Directory where the dot graphs are written
+    #[arg(long, default_value = "graphs")]
+    pub graphs_dir: PathBuf,
+
+    /// Do not write the dot graphs
+    #[arg(long)]
+    pub no_graphs: bool,
+
+    /// File where each decision of the analysis (e.g. the exit chosen for a cycle) is written
+    /// as a JSON object per line
+    #[arg(long, value_name = "FILE")]
+    pub trace_json: Option<PathBuf>,
+
+    /// Entry point to analyze, as symbol name or address (e.g. main or 0x1040); if repeated,
+    /// the WCET of each entry point is reported separately
+    #[arg(long)]
+    pub entry: Vec<String>,
+
+    /// Without --entry, analyze every entry node of the graph (all) or only the entry point of
+    /// the program, falling back to main in the objects without one (program)
+    #[arg(long, value_enum, default_value_t = EntryMode::All)]
+    pub entry_mode: EntryMode,
+
+    /// Fixed latency of the calls to a function, whose body is not analyzed (e.g. memcpy=120)
+    #[arg(long, value_name = "FUNCTION=CYCLES")]
+    pub stub_cost: Vec<String>,
+
+    /// Function never returning (symbol name or address), whose calls end the path, besides the
+    /// known ones of the runtime (e.g. abort, exit, __stack_chk_fail)
+    #[arg(long, value_name = "FUNCTION")]
+    pub no_return: Vec<String>,
+
+    /// Clock frequency in MHz, to print the WCET also in nanoseconds
+    #[arg(long, value_name = "MHZ")]
+    pub clock_mhz: Option<f64>,
+
+    /// Maximum nesting of the cycles and of the calls in the duplicated functions
+    #[arg(long, default_value_t = 1000)]
+    pub max_depth: usize,
+
+    /// Minimum iterations of each cycle, for the best-case execution time
+    #[arg(long, default_value_t = 0)]
+    pub min_cycles: u32,
+
+    /// Bytes to skip at the start of the text section, e.g. a vector table
+    #[arg(long, default_value = "0", value_name = "BYTES", value_parser = parse_number)]
+    pub text_offset: u64,
+
+    /// Executable section whose first bytes are skipped by --text-offset, instead of .text
+    #[arg(long, value_name = "NAME")]
+    pub text_section: Option<String>,
+
+    /// Address added to the addresses of the object (sections, symbols and entry point), e.g.
+    /// the base where a position-independent executable is loaded; the addresses given to the
+    /// other options are the moved ones
+    #[arg(long, default_value = "0", value_name = "ADDRESS", value_parser = parse_number)]
+    pub load_bias: u64,
+
+    /// Skip the data embedded in the code (e.g. literal pools) instead of stopping the disassembly
+    #[arg(long)]
+    pub skip_data: bool,
+
+    /// Syntax of the disassembled x86 instructions, in the listings and in the operand rules of
+    /// the latency file (the operands are matched destination first in both syntaxes)
+    #[arg(long, value_enum, default_value_t = Syntax::Intel)]
+    pub syntax: Syntax,
+
+    /// Resume the disassembly stopped by an invalid instruction at the next address aligned to
+    /// the given bytes (e.g. 4 after a jump table), instead of ignoring the rest of the code
+    #[arg(long, value_name = "BYTES", value_parser = parse_number)]
+    pub resume_alignment: Option<u64>,
+
+    /// Size of the lines of the instruction cache: the miss penalty is added for each line
+    /// touched by a block, every time it is executed
+    #[arg(long, value_name = "BYTES", value_parser = parse_number, requires = "cache_miss_penalty")]
+    pub cache_line_size: Option<u64>,
+
+    /// Clock cycles to load a line of the instruction cache
+    #[arg(long, value_name = "CYCLES", requires = "cache_line_size")]
+    pub cache_miss_penalty: Option<u32>,
+
+    /// Analyze once the functions called from more than one site, using their WCET as the
+    /// latency of the other calls instead of a copy of their blocks
+    #[arg(long)]
+    pub summarize_calls: bool,
+
+    /// Merge each block falling through to a block without other predecessors with it, to
+    /// build a smaller graph with the same WCET
+    #[arg(long)]
+    pub merge_blocks: bool,
+
+    /// Print the basic blocks of the control flow graph with their latency and successors
+    #[arg(long)]
+    pub dump_blocks: bool,
+
+    /// Only print the disassembled instructions with their latency and exit jump, without
+    /// computing the WCET
+    #[arg(long)]
+    pub disasm_only: bool,
+
+    /// Only print the cycles found in the control flow graph, each one with its blocks and the
+    /// iterations that would be considered, without computing the WCET
+    #[arg(long)]
+    pub list_loops: bool,
+
+    /// Print the number of blocks, edges and cycles of the control flow graph
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Print the time spent in each phase of the analysis and the total analysis time
+    #[arg(long)]
+    pub timings: bool,
+
+    /// Log the phases of the analysis (disassembly, leaders, blocks, duplication, condensation
+    /// and WCET summation) with their counts
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    /// Format of the printed result
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+}
+
+impl Cli {
+    /// Builds the configuration of the analysis; the functions given by name are resolved in
+    /// the symbol table of `obj_file`, which is `None` for the raw binaries.
+    pub fn to_config(&self, obj_file: Option<&object::File>) -> Result<Config, WcetError> {
+        let latency_file = self
+            .latency_file
+            .as_ref()
+            .map(|latency_file| load(latency_file, LatencyLayer::from_file))
+            .transpose()?;
+        let mut core_latency_tables = self
+            .cpu
+            .iter()
+            .map(|cpu| {
+                // the profiles are layered on top of the generic model and the latency file
+                let (cpu_model, profile) = match CpuModel::from_str(cpu, true) {
+                    Ok(cpu_model) => (cpu_model, None),
+                    Err(_) => (CpuModel::Generic, Some(cpu)),
+                };
+                let mut latency_table = LatencyTable::for_cpu(cpu_model);
+                if let Some(latency_file) = &latency_file {
+                    latency_table.merge(latency_file.clone());
+                }
+                let latency_table = match profile {
+                    Some(profile) => latency_table
+                        .profile(profile)
+                        .ok_or_else(|| WcetError::UnknownCpu(profile.clone()))?,
+                    None => latency_table,
+                };
+                Ok((cpu.clone(), latency_table))
+            })
+            .collect::<Result<Vec<(String, LatencyTable)>, WcetError>>()?;
+        // a single CPU is analyzed without a report for each core
+        let latency_table = core_latency_tables[0].1.clone();
+        if core_latency_tables.len() == 1 {
+            core_latency_tables.clear();
+        }
+
+        let loop_bounds = match &self.loop_bounds_file {
+            Some(loop_bounds_file) => load(loop_bounds_file, LoopBounds::from_file)?,
+            None => LoopBounds::default(),
+        };
+
+        let indirect_targets = match &self.indirect_targets_file {
+            Some(indirect_targets_file) => load(indirect_targets_file, IndirectTargets::from_file)?,
+            None => IndirectTargets::default(),
+        };
+
+        let trace_output = match &self.trace_json {
+            Some(path) => {
+                let file = File::create(path).map_err(|e| WcetError::Io(path.clone(), e))?;
+                Some(TraceOutput::new(LineWriter::new(file)))
+            }
+            None => None,
+        };
+
+        let indirect_candidates = self
+            .indirect_candidate
+            .iter()
+            .map(|candidate| resolve_function(obj_file, candidate, self.load_bias))
+            .collect::<Result<Vec<u64>, WcetError>>()?;
+
+        let cost_annotations = match &self.annotations_file {
+            Some(annotations_file) => load(annotations_file, CostAnnotations::from_file)?,
+            None => CostAnnotations::default(),
+        };
+
+        let latency_overrides = match &self.latency_overrides_file {
+            Some(latency_overrides_file) => {
+                load(latency_overrides_file, LatencyOverrides::from_file)?
+            }
+            None => LatencyOverrides::default(),
+        };
+
+        if self.cache_line_size == Some(0) {
+            return Err(WcetError::InvalidOption("cache-line-size", "0".to_string()));
+        }
+        let cache =
+            self.cache_line_size
+                .zip(self.cache_miss_penalty)
+                .map(|(line_size, miss_penalty)| CacheConfig {
+                    line_size,
+                    miss_penalty,
+                });
+
+        let mut entries = self
+            .entry
+            .iter()
+            .map(|entry| {
+                resolve_address(obj_file, entry, self.load_bias)
+                    .ok_or_else(|| WcetError::EntrySymbolNotFound(entry.clone()))
+            })
+            .collect::<Result<Vec<u64>, WcetError>>()?;
+        // a single entry point restricts the analysis, without a report for each entry
+        let entry = (entries.len() == 1).then(|| entries.remove(0));
+
+        let stub_costs = self
+            .stub_cost
+            .iter()
+            .map(|stub_cost| {
+                let invalid = || WcetError::InvalidOption("stub-cost", stub_cost.clone());
+                let (function, cost) = stub_cost.split_once('=').ok_or_else(invalid)?;
+                let address = resolve_function(obj_file, function, self.load_bias)?;
+                let cost = cost.parse::<u32>().map_err(|_| invalid())?;
+                Ok((address, cost))
+            })
+            .collect::<Result<_, WcetError>>()?;
+
+        let no_return = self
+            .no_return
+            .iter()
+            .map(|function| resolve_function(obj_file, function, self.load_bias))
+            .collect::<Result<_, WcetError>>()?;
+
+        Ok(Config {
+            latency_table,
+            core_latency_tables,
+            cost_model: None, // only available from the library
+            loop_bounds,
+            strict_bounds: self.strict_bounds,
+            indirect_targets,
+            indirect_policy: self.indirect_policy,
+            indirect_candidates,
+            cost_annotations,
+            latency_overrides,
+            cache,
+            graphs_dir: (!self.no_graphs).then(|| self.graphs_dir.clone()),
+            trace_output,
+            functions: BTreeMap::new(), // taken from the symbol table by analyze_bytes
+            stub_costs,
+            no_return,
+            entry,
+            entries,
+            program_entry: None, // taken from the object by analyze_bytes
+            entry_mode: self.entry_mode,
+            clock_mhz: self.clock_mhz,
+            max_depth: self.max_depth,
+            min_cycles: self.min_cycles,
+            line_table: BTreeMap::new(), // taken from the debug information by analyze_bytes
+            stub_ranges: Vec::new(),     // taken from the sections by analyze_bytes
+            load_bias: self.load_bias,
+            text_offset: self.text_offset,
+            text_section: self.text_section.clone(),
+            skip_data: self.skip_data,
+            syntax: self.syntax,
+            resume_alignment: self.resume_alignment,
+            dump_blocks: self.dump_blocks,
+            disasm_only: self.disasm_only,
+            list_loops: self.list_loops,
+            summarize_calls: self.summarize_calls,
+            merge_blocks: self.merge_blocks,
+        })
+    }
+}
+
+/// Loads the configuration file at `path` with `from_file`.
+fn load<T>(
+    path: &Path,
+    from_file: impl FnOnce(&Path) -> Result<T, Box<dyn std::error::Error>>,
+) -> Result<T, WcetError> {
+    from_file(path).map_err(|e| WcetError::InvalidFile(path.to_path_buf(), e.to_string()))
+}
+
+/// Parses a decimal or hexadecimal (with the 0x prefix) number.
+fn parse_number(value: &str) -> Result<u64, String> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse::<u64>(),
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// Resolves a function given as hexadecimal address or as symbol name
+/// (also with the leading underscore used by Mach-O), whose address is moved by `load_bias`.
+fn resolve_address(obj_file: Option<&object::File>, entry: &str, load_bias: u64) -> Option<u64> {
+    if let Some(hex) = entry.strip_prefix("0x") {
+        return u64::from_str_radix(hex, 16).ok();
+    }
+
+    obj_file?
+        .symbols()
+        .find(|symbol| match symbol.name() {
+            Ok(name) => name == entry || name.strip_prefix('_') == Some(entry),
+            Err(_) => false,
+        })
+        .map(|symbol| symbol.address() + load_bias)
+}
+
+/// Resolves a function with `resolve_address`, returning an error if it is not found.
+fn resolve_function(
+    obj_file: Option<&object::File>,
+    function: &str,
+    load_bias: u64,
+) -> Result<u64, WcetError> {
+    resolve_address(obj_file, function, load_bias)
+        .ok_or_else(|| WcetError::SymbolNotFound(function.to_string()))
+}
@@ -0,0 +1,216 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use clap::ValueEnum;
+
+use crate::annotations::{CostAnnotations, LatencyOverrides};
+use crate::bounds::LoopBounds;
+use crate::cost::CostModel;
+use crate::error::WcetError;
+use crate::indirect::IndirectTargets;
+use crate::latency::LatencyTable;
+use crate::trace::TraceOutput;
+
+/// Entry nodes whose WCET is computed, if no entry point is given.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum EntryMode {
+    /// Every entry node of the graph (the roots), reporting the maximum WCET
+    #[default]
+    All,
+    /// Only the entry point of the program, or the main function if the object has none
+    Program,
+}
+
+/// Handling of the indirect jumps whose targets are not given (see `IndirectTargets`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum IndirectPolicy {
+    /// Ignore the jump, which falls through to the next instruction (may under-approximate)
+    #[default]
+    Ignore,
+    /// Jump to any leader, function entries included, or to the candidates given by the user
+    /// (sound, but may create many cycles); the indirect calls also return to the next
+    /// instruction
+    Conservative,
+    /// Jump to the leaders of the function of the jump after its first block, e.g. the cases
+    /// of a switch (may under-approximate an indirect tail call); the indirect calls are ignored
+    SameFunction,
+}
+
+/// Syntax of the x86 instructions disassembled by Capstone.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Syntax {
+    /// Destination first, e.g. `mov rax, qword ptr [rbx]`
+    #[default]
+    Intel,
+    /// Destination last, with the size of the operands in the mnemonic, e.g. `movq (%rbx), %rax`
+    Att,
+}
+
+/// Names of the functions of the C and C++ runtimes that never return, whose calls end the
+/// path; the names with a leading underscore (Mach-O) are also recognized.
+pub const NO_RETURN_FUNCTIONS: [&str; 10] = [
+    "abort",
+    "exit",
+    "_exit",
+    "_Exit",
+    "quick_exit",
+    "__stack_chk_fail",
+    "__assert_fail",
+    "__cxa_throw",
+    "longjmp",
+    "pthread_exit",
+];
+
+/// Instruction cache, modeled as always missing: each cache line touched by a block is loaded
+/// again every time the block is executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    pub line_size: u64,    // bytes
+    pub miss_penalty: u32, // clock cycles to load a line
+}
+
+/// Configuration of an analysis, populated from the command line.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub latency_table: LatencyTable,
+    pub core_latency_tables: Vec<(String, LatencyTable)>, // (core, table) analyzed separately, instead of latency_table
+    pub cost_model: Option<Arc<dyn CostModel>>, // cost of the blocks and of the edges, the latencies of latency_table if None
+    pub loop_bounds: LoopBounds,
+    pub strict_bounds: bool, // fail on the cycles without a bound, instead of counting a single iteration
+    pub indirect_targets: IndirectTargets,
+    pub indirect_policy: IndirectPolicy, // handling of the indirect jumps without targets
+    pub indirect_candidates: Vec<u64>, // targets of the unresolved indirect jumps, every leader if empty
+    pub cost_annotations: CostAnnotations,
+    pub latency_overrides: LatencyOverrides,
+    pub cache: Option<CacheConfig>, // instruction cache, no cache misses if None
+    pub graphs_dir: Option<PathBuf>, // directory of the dot graphs, no graph is written if None
+    pub trace_output: Option<TraceOutput>, // destination of the events of the analysis, none are written if None
+    pub functions: BTreeMap<u64, String>, // function_address -> name, from the symbol table if empty
+    pub stub_costs: HashMap<u64, u32>, // function_address -> latency of a call, the callee is not analyzed
+    pub no_return: HashSet<u64>, // addresses of the functions never returning, besides the NO_RETURN_FUNCTIONS
+    pub entry: Option<u64>,      // leader of the entry node to analyze, all entry nodes if None
+    pub entries: Vec<u64>, // leaders of the entry nodes analyzed separately (e.g. tasks), instead of entry
+    pub program_entry: Option<u64>, // entry point of the program, the unreachable blocks are dropped
+    pub entry_mode: EntryMode,      // entry nodes analyzed if entry is None
+    pub clock_mhz: Option<f64>,     // clock frequency, to report the WCET also in nanoseconds
+    pub max_depth: usize, // maximum nesting of the condensed cycles and of the calls in the duplicated functions
+    pub min_cycles: u32,  // iterations of each cycle on the best-case path
+    pub line_table: BTreeMap<u64, String>, // address -> file:line, from the debug information
+    pub stub_ranges: Vec<Range<u64>>, // code of the stubs jumping to the external functions (e.g. .plt), from the sections
+    pub load_bias: u64, // added to the addresses of the object, e.g. the runtime base of a position-independent executable
+    pub text_offset: u64, // bytes skipped at the start of text_section (e.g. a vector table)
+    pub text_section: Option<String>, // section whose start is skipped, .text (__text) or else the first executable section if None
+    pub skip_data: bool, // continue the disassembly after the bytes that are not instructions
+    pub syntax: Syntax,  // syntax of the x86 instructions
+    pub resume_alignment: Option<u64>, // bytes to which the disassembly is realigned after an invalid instruction, stopped if None
+    pub dump_blocks: bool,             // list the blocks of the graph in the report, for debugging
+    pub disasm_only: bool, // list the instructions in the report, without computing the WCET
+    pub list_loops: bool,  // list the cycles with their bounds, without computing the WCET
+    pub summarize_calls: bool, // analyze once the functions called from more than one site
+    pub merge_blocks: bool, // merge the straight-line chains of blocks before building the graph
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            latency_table: LatencyTable::default(),
+            core_latency_tables: Vec::new(),
+            cost_model: None,
+            loop_bounds: LoopBounds::default(),
+            strict_bounds: false,
+            indirect_targets: IndirectTargets::default(),
+            indirect_policy: IndirectPolicy::Ignore,
+            indirect_candidates: Vec::new(),
+            cost_annotations: CostAnnotations::default(),
+            latency_overrides: LatencyOverrides::default(),
+            cache: None,
+            graphs_dir: None,
+            trace_output: None,
+            functions: BTreeMap::new(),
+            stub_costs: HashMap::new(),
+            no_return: HashSet::new(),
+            entry: None,
+            entries: Vec::new(),
+            program_entry: None,
+            entry_mode: EntryMode::All,
+            clock_mhz: None,
+            max_depth: 1000,
+            min_cycles: 0,
+            line_table: BTreeMap::new(),
+            stub_ranges: Vec::new(),
+            load_bias: 0,
+            text_offset: 0,
+            text_section: None,
+            skip_data: false,
+            syntax: Syntax::Intel,
+            resume_alignment: None,
+            dump_blocks: false,
+            disasm_only: false,
+            list_loops: false,
+            summarize_calls: false,
+            merge_blocks: false,
+        }
+    }
+}
+
+impl Config {
+    /// Returns the cost model of the blocks and of the edges of the graph: the latencies of
+    /// the latency table, unless another model is given.
+    pub fn cost_model(&self) -> &dyn CostModel {
+        match &self.cost_model {
+            Some(cost_model) => cost_model.as_ref(),
+            None => &self.latency_table,
+        }
+    }
+
+    /// Creates the graphs directory, if the graphs are enabled, removing the graphs written by
+    /// a previous analysis; the other files of the directory are kept.
+    pub fn prepare_graphs_dir(&self) -> Result<(), WcetError> {
+        let Some(graphs_dir) = &self.graphs_dir else {
+            return Ok(());
+        };
+        let io_error = |e| WcetError::Io(graphs_dir.clone(), e);
+
+        if !graphs_dir.exists() {
+            return std::fs::create_dir_all(graphs_dir).map_err(io_error);
+        }
+        for entry in std::fs::read_dir(graphs_dir).map_err(io_error)? {
+            let path = entry.map_err(io_error)?.path();
+            let is_graph = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| {
+                    [
+                        "graph.",
+                        "condensed_graph.",
+                        "cycle_graph_",
+                        "condensed_cycle_graph_",
+                    ]
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix))
+                });
+            if is_graph && path.is_file() {
+                std::fs::remove_file(&path).map_err(|e| WcetError::Io(path, e))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the graph returned by `digraph` (DOT or JSON) as `file_name` in the graphs
+    /// directory, if the graphs are enabled.
+    pub fn write_graph(
+        &self,
+        file_name: &str,
+        digraph: impl FnOnce() -> String,
+    ) -> Result<(), WcetError> {
+        if let Some(graphs_dir) = &self.graphs_dir {
+            let path = graphs_dir.join(file_name);
+            std::fs::write(&path, digraph()).map_err(|e| WcetError::Io(path, e))?;
+        }
+
+        Ok(())
+    }
+}
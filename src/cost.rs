@@ -0,0 +1,57 @@
+use std::fmt::Debug;
+
+use crate::block::Block;
+use crate::error::WcetError;
+use crate::jump::ExitJump;
+use crate::latency::LatencyTable;
+
+/// Cost of the blocks and of the edges of the control flow graph, whose longest path is the
+/// worst case: clock cycles with the `LatencyTable` (the default), but also e.g. energy or
+/// memory traffic. The costs of the cycles are the costs of their iterations times their
+/// bounds, as for the latencies.
+///
+/// The costs must be finite and non-negative. They are summed as they are along the paths, and
+/// only the worst case is rounded up to an integer. The latencies of the instructions (with the annotations, the stubs, the summaries of the calls and the
+/// cache misses added) are available in the instructions of the blocks.
+pub trait CostModel: Debug + Send + Sync {
+    /// Returns the cost of an execution of `block`.
+    fn block_cost(&self, block: &Block) -> f64;
+
+    /// Returns the cost of the control transfer from `from` to `to` through the exit jump
+    /// `kind` of `from`, paid on the edge in addition to the cost of `to`.
+    fn edge_cost(&self, from: &Block, to: &Block, kind: &ExitJump) -> f64;
+}
+
+/// The latency of a block is the sum of the latencies of its instructions, while a control
+/// transfer costs the `branch_penalty` if it is the taken side of a conditional jump, the
+/// `call_latency` and `ret_latency` for the edges of calls and returns, 0 otherwise.
+impl CostModel for LatencyTable {
+    fn block_cost(&self, block: &Block) -> f64 {
+        block.get_latency() as f64
+    }
+
+    fn edge_cost(&self, _from: &Block, to: &Block, kind: &ExitJump) -> f64 {
+        let latency = match kind {
+            ExitJump::ConditionalRelative { taken, .. }
+            | ExitJump::ConditionalAbsolute { taken, .. }
+                if *taken == to.leader =>
+            {
+                self.branch_penalty
+            }
+            ExitJump::Call(call_target, _) if *call_target == to.leader => self.call_latency,
+            ExitJump::Ret(_) => self.ret_latency,
+            _ => 0,
+        };
+        latency as f64
+    }
+}
+
+/// Returns `cost` if it can weight the edges of the graph, failing if it is negative or not
+/// finite (`leader` is the block it is the cost of, or the source of the edge).
+pub(crate) fn checked_cost(cost: f64, leader: u64) -> Result<f64, WcetError> {
+    if !cost.is_finite() || cost < 0.0 {
+        return Err(WcetError::InvalidCost(leader, cost));
+    }
+
+    Ok(cost)
+}
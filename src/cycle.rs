@@ -1,33 +1,89 @@
 use petgraph::Direction::{Incoming, Outgoing};
+use serde::Serialize;
 use std::collections::{BTreeMap, HashMap};
-use std::io::Write;
 use std::sync::atomic::{AtomicU32, Ordering};
 
 use crate::block::Block;
-use crate::graph::{MappedCondensedGraph, MappedGraph};
+use crate::config::Config;
+use crate::cost::CostModel;
+use crate::error::WcetError;
+use crate::graph::{weight_to_latency, MappedCondensedGraph, MappedGraph};
 use crate::jump::ExitJump;
-use crate::printwarning;
+use crate::trace::{self, Event};
 
 static COUNTER: AtomicU32 = AtomicU32::new(0);
 
+/// Cycle of the graph as listed by `config.list_loops`, to annotate the bounds iteratively.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LoopListing {
+    pub entry: u64,           // real entry address
+    pub name: Option<String>, // function#n name, if its function is known
+    pub function: String,     // name of the enclosing function
+    pub bound: Option<u32>,   // iterations considered, None if it never terminates (counted once)
+    pub origin: &'static str, // where the bound comes from (see `cycle_bound_origin`)
+    pub blocks: Vec<u64>,     // real leaders of its blocks, sorted
+}
+
+impl std::fmt::Display for LoopListing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let iterations = match self.bound {
+            Some(bound) => format!("{bound} iterations ({})", self.origin),
+            None => "non-terminating, 1 iteration".to_string(),
+        };
+        write!(
+            f,
+            "Loop 0x{:x} ({}) in {}: {iterations}, blocks [{}]",
+            self.entry,
+            self.name.as_deref().unwrap_or("unnamed"),
+            self.function,
+            self.blocks
+                .iter()
+                .map(|leader| format!("0x{leader:x}"))
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn condensate_graph(
     mut original_graph: MappedGraph,
-    entry_node_latency_map: &mut HashMap<u64, u32>,
+    entry_node_latency_map: &mut HashMap<u64, f64>, // block_leader -> latency
     blocks: &BTreeMap<u64, Block>,
     recursive_functions: &HashMap<u64, u64>, // function_address -> ret_address
-    latency_map: &mut HashMap<u64, u32>,     // ret_address -> latency
+    latency_map: &mut HashMap<u64, f64>,     // ret_address -> latency
     fictious_map: &mut HashMap<u64, u64>,    // fictious_address -> real_address
-) -> MappedCondensedGraph {
-    let mut condensed_graph = original_graph.condense_cycles();
+    cycles: &mut Vec<(u64, u32)>,            // (cycle_entry_address, max_cycles)
+    loop_latencies: &mut BTreeMap<u64, (u64, u32, u64)>, // cycle_entry_address -> (iteration_latency, max_cycles, total_latency)
+    non_terminating_loops: &mut Vec<u64>, // cycle_entry_address of the cycles without exit
+    loops: &mut Vec<LoopListing>,         // cycles listed with config.list_loops
+    config: &Config,
+    depth: usize, // nesting level of the cycles being condensed
+) -> Result<MappedCondensedGraph, WcetError> {
+    let loop_names = loop_names(blocks, fictious_map);
+    // the entries of the analysis are roots of the dominator tree only in the whole graph, the
+    // entry of a nested cycle has no incoming edges once its back edges are removed
+    let entries = match config.entry {
+        _ if depth > 0 => Vec::new(),
+        _ if !config.entries.is_empty() => config.entries.clone(),
+        Some(entry) => vec![entry],
+        None => config.program_entry.into_iter().collect(),
+    };
+    let natural_loops = original_graph.natural_loops(&entries); // header -> leaders of the loop
 
-    let mut condensed_nodes = condensed_graph.get_condensed_nodes();
+    let mut condensed_graph = original_graph.condense_cycles();
 
-    //adding self cycles nodes
-    for node in condensed_graph.get_nodes() {
-        if node[0].get_targets().contains(&node[0].leader) {
-            condensed_nodes.push(node.clone());
-        }
-    }
+    // the cycles are the nodes with more than one block and the single blocks jumping to themselves
+    let self_loop_leaders = original_graph
+        .self_loop_nodes()
+        .iter()
+        .map(|block| block.leader)
+        .collect::<Vec<u64>>();
+    let condensed_nodes = condensed_graph
+        .get_nodes()
+        .into_iter()
+        .filter(|node| node.len() > 1 || self_loop_leaders.contains(&node[0].leader))
+        .collect::<Vec<Vec<Block>>>();
 
     for condensed_node in condensed_nodes {
         COUNTER.fetch_add(1, Ordering::Relaxed);
@@ -43,7 +99,7 @@ pub fn condensate_graph(
                     cycle_graph.add_edge(
                         block.clone(),
                         target_block.clone(),
-                        target_block.get_latency() as f32,
+                        block.edge_latency(target_block, config.cost_model())?,
                     );
                 }
             }
@@ -51,7 +107,7 @@ pub fn condensate_graph(
 
         let incomings_vec = condensed_graph.neighbors_directed(&condensed_node, Incoming);
 
-        let mut entry_blocks = HashMap::new();
+        let mut entry_blocks = BTreeMap::new(); // leader -> entry block
 
         // handling case where pre_cycle_block has more than one block --> it is a condensed node
         for pre_cycle_blocks in &incomings_vec {
@@ -64,134 +120,81 @@ pub fn condensate_graph(
             }
         }
 
-        let outer_nodes = condensed_graph
-            .neighbors_directed(&condensed_node, Outgoing)
-            .to_owned();
-
-        let mut entry_block = condensed_node[0].clone(); // to initialize the variable
-
-        let mut false_outer_blocks = HashMap::<Block, Vec<Block>>::new(); // exit_block -> outer_blocks
+        // the header of a natural loop covering the whole cycle is its only entry, since it
+        // dominates all the blocks of the cycle; the irreducible cycles fall back to the lowest
+        // entry block, or to the ret block of the "return" loop of a recursive function, which
+        // has no entry block
+        let natural_header = condensed_node.iter().find(|block| {
+            natural_loops
+                .get(&block.leader)
+                .is_some_and(|natural_loop| {
+                    natural_loop.len() == condensed_node.len()
+                        && condensed_node
+                            .iter()
+                            .all(|inner_block| natural_loop.contains(&inner_block.leader))
+                        && entry_blocks.keys().all(|leader| *leader == block.leader)
+                })
+        });
+        let entry_block = match natural_header
+            .or_else(|| entry_blocks.values().min_by_key(|block| block.leader))
+        {
+            Some(block) => block.clone(),
+            None => condensed_node
+                .iter()
+                .find(|block| matches!(block.exit_jump, Some(ExitJump::Ret(_))))
+                .unwrap_or(&condensed_node[0])
+                .clone(),
+        };
 
         // to handle case where there is no entry block
         if entry_blocks.is_empty() {
             entry_blocks.insert(entry_block.leader, entry_block.clone());
         }
 
-        let mut normal_cycle = false;
-        // handling case where there are more than one outer block
-        for outer_blocks in outer_nodes {
-            // handle case where outer block has more than one block --> it is a condensed node
-            for outer_block in outer_blocks.clone() {
-                if let Some(cycle_block) = cycle_graph
-                    .get_nodes()
-                    .iter()
-                    .find(|node| node.get_targets().contains(&outer_block.clone().leader))
-                {
-                    let mut remove_flag = false;
-                    for (leader, entry) in entry_blocks.clone() {
-                        if cycle_block.leader == leader {
-                            normal_cycle = true;
-                            remove_flag = true;
-                            entry_block = entry.clone();
-                            entry_blocks.remove(&leader);
-                            break;
-                        }
-                    }
-                    if !remove_flag {
-                        false_outer_blocks.insert(cycle_block.clone(), outer_blocks.clone());
-                    }
-                }
+        let (entry_block, exit_block) = select_cycle_boundary(
+            &mut condensed_graph,
+            &condensed_node,
+            &cycle_graph,
+            &cycle_graph.get_nodes(),
+            entry_block,
+            entry_blocks,
+            config,
+        );
+        let exit_block = cycle_exit(&entry_block, exit_block, recursive_functions, config);
+
+        let real_entry_address = fictious_map
+            .get(&entry_block.leader)
+            .unwrap_or(&entry_block.leader);
+        // a cycle without exit never terminates (e.g. the infinite loop of a task), so the WCET
+        // is the one of a single iteration whatever its bound
+        let max_cycles = match exit_block {
+            Some(_) => cycle_bound(&entry_block, fictious_map, &loop_names, config),
+            None => {
+                non_terminating_loops.push(*real_entry_address);
+                1
             }
-        }
-
-        let mut exit_block = entry_block.clone(); // to initialize the variable
-
-        // if the entry and exit nodes are the same
-        if normal_cycle {
-            // if the outer block is not the normal outer block, we need to remove it
-            for outer_blocks in false_outer_blocks.values() {
-                condensed_graph.remove_node(outer_blocks);
-                printwarning!(
-                    "We are not considering the exit block 0x{:x} as exit from the cycle 0x{:x}",
-                    outer_blocks[0].leader,
-                    entry_block.leader
-                );
-            }
-
-            for entry_leader in entry_blocks.keys() {
-                printwarning!(
-                    "We are not considering the entry block 0x{:x} as entry from the cycle 0x{:x}",
-                    entry_leader,
-                    entry_block.leader
-                );
-                // remove the incomings edges of the entry blocks not considered in the WCET calculation
-                for pre_cycle_blocks in &incomings_vec {
-                    for block in pre_cycle_blocks {
-                        if block.get_targets().contains(entry_leader) {
-                            condensed_graph.remove_edge(pre_cycle_blocks, &condensed_node);
-                        }
-                    }
-                }
-            }
-        } else if false_outer_blocks.is_empty() {
-            printwarning!(
-                "There is no outer block for the cycle 0x{:x}",
-                entry_block.leader
-            );
-        } else if false_outer_blocks.len() > 1 {
-            // find the block with the highest leader and use it as exit block
-            for possible_exit_block in false_outer_blocks.keys() {
-                let current_leader = possible_exit_block.leader;
-                if current_leader > exit_block.leader {
-                    exit_block = possible_exit_block.clone();
-                }
-            }
-
-            printwarning!(
-                "There are more than one outer block for the cycle 0x{:x} and we are considering 0x{:x}",
-                entry_block.leader, exit_block.leader
-            );
-        } else {
-            exit_block = false_outer_blocks.keys().next().unwrap().clone();
-        }
-
-        if !normal_cycle && entry_blocks.clone().len() > 1 {
-            printwarning!(
-                "There are more than one entry block for the cycle 0x{:x} and none of them is also an exit block!!",
-                entry_block.leader
-            );
-            printwarning!(
-                "We thus consider the entry block 0x{:x} and the exit block 0x{:x} for this cycle",
-                entry_block.leader,
-                exit_block.leader
+        };
+
+        if config.list_loops {
+            list_loop(
+                loops,
+                &entry_block,
+                &condensed_node,
+                exit_block.as_ref().map(|_| max_cycles),
+                cycles,
+                fictious_map,
+                &loop_names,
+                config,
             );
         }
+        cycles.push((*real_entry_address, max_cycles));
 
-        // remove the oouter blocks from the condensed graph
-
-        let mut max_cycles = 1;
-        if let Some(real_entry_address) = fictious_map.get(&entry_block.leader) {
-            let env_var_key = format!("CYCLE_0x{real_entry_address:x}");
-            if let Ok(cycle_var) = std::env::var(&env_var_key) {
-                match cycle_var.parse::<u32>() {
-                    Ok(cycle_var) => max_cycles = cycle_var,
-                    Err(_) => {
-                        panic!("The environment variable {env_var_key} is not a valid number");
-                    }
-                }
-            };
-        } else {
-            let env_var_key = format!("CYCLE_0x{:x}", entry_block.leader);
-            if let Ok(cycle_var) = std::env::var(&env_var_key) {
-                match cycle_var.parse::<u32>() {
-                    Ok(cycle_var) => max_cycles = cycle_var,
-                    Err(_) => {
-                        panic!("The environment variable {env_var_key} is not a valid number");
-                    }
-                }
-            };
-            printwarning!("Found a cycle at address 0x{:x} -> {max_cycles} cycle iterations considered for the wcet calculation. \
-            If you want to change the value, please set the env var CYCLE_0x{:x}", entry_block.leader, entry_block.leader);
+        // the back edges are removed, but the transfer latency of the jumps taking them (e.g. the
+        // branch penalty of a taken conditional jump) is still paid at every iteration
+        let mut back_edge_latency: f64 = 0.0;
+        for (source, target, _) in cycle_graph.edges_directed(&entry_block, Incoming) {
+            back_edge_latency =
+                back_edge_latency.max(source.transfer_latency(&target, config.cost_model())?);
         }
 
         // make the cycle acyclic
@@ -199,26 +202,35 @@ pub fn condensate_graph(
             cycle_graph.remove_edge(&source, &target);
         }
 
-        let graph_dir = crate::GRAPHS_DIR;
-
-        let digraph = cycle_graph.to_dot_graph();
         let graph_number = COUNTER.load(Ordering::Relaxed);
-        let mut dot_file =
-            std::fs::File::create(format!("{graph_dir}/cycle_graph_{graph_number}.dot"))
-                .expect("Unable to create file");
-        dot_file
-            .write_all(digraph.as_bytes())
-            .expect("Unable to write dot file");
+        config.write_graph(&format!("cycle_graph_{graph_number}.dot"), || {
+            cycle_graph.to_dot_graph()
+        })?;
 
-        let entry_node_latency = entry_block.get_latency();
+        let entry_node_latency = entry_block.cost(config.cost_model())?;
 
         match cycle_graph.reconstruct_longest_path(
             &entry_block,
-            &exit_block,
-            entry_node_latency as f32,
+            exit_block.as_ref(),
+            entry_node_latency,
             max_cycles,
         ) {
             Ok(cycle_node_latency) => {
+                let cycle_node_latency = cycle_node_latency + back_edge_latency * max_cycles as f64;
+                let cycle_latency = weight_to_latency(cycle_node_latency, *real_entry_address)?;
+                let iteration_latency = weight_to_latency(
+                    cycle_graph
+                        .longest_path(&entry_block)
+                        .expect("Cycle graph not acyclic")
+                        + entry_node_latency
+                        + back_edge_latency,
+                    *real_entry_address,
+                )?;
+                insert_loop_latency(
+                    loop_latencies,
+                    *real_entry_address,
+                    (iteration_latency, max_cycles, cycle_latency),
+                );
                 let node_incoming_edges = condensed_graph.edges_directed(&condensed_node, Incoming);
 
                 let mut max_cycles = 1;
@@ -228,45 +240,58 @@ pub fn condensate_graph(
                     for (recursive_address, ret_address) in recursive_functions {
                         if current_ret_address == *ret_address {
                             let env_var_key = format!("RECURSIVE_0x{recursive_address:x}");
-                            if let Ok(recursive_var) = std::env::var(&env_var_key) {
-                                match recursive_var.parse::<u32>() {
-                                    Ok(recursive_var) => max_cycles = recursive_var,
-                                    Err(_) => {
-                                        panic!(
-                                            "The environment variable {env_var_key} is not a valid number"
-                                        );
-                                    }
-                                }
-                            };
-                            printwarning!(
-                                "Found a recursive function at address 0x{recursive_address:x} -> {max_cycles} function iterations \
+                            if let Some(recursive_bound) =
+                                config.loop_bounds.recursive_bound(*recursive_address)
+                            {
+                                max_cycles = recursive_bound;
+                            }
+                            trace::emit(config, Event {
+                                event: "recursive_bound",
+                                address: Some(*recursive_address),
+                                reason: format!("Found a recursive function at address 0x{recursive_address:x} -> {max_cycles} function iterations \
                                 considered for the wcet calculation. If you want to change this value, set the environment \
-                                variable {env_var_key}"
-                                );
+                                variable {env_var_key}"),
+                                ..Event::default()
+                            });
                         }
                     }
                     latency_map.insert(
                         current_ret_address,
-                        (cycle_node_latency as u32 - entry_node_latency) * max_cycles,
+                        (cycle_node_latency - entry_node_latency) * max_cycles as f64,
                     );
                 }
 
                 if node_incoming_edges.is_empty() {
                     // if the condensed node has no incoming edges, it is the entry node
-                    entry_node_latency_map
-                        .insert(condensed_node[0].leader, cycle_node_latency as u32);
+                    entry_node_latency_map.insert(condensed_node[0].leader, cycle_node_latency);
                 // we choose [0] as reference for the condensed node for simplicity
                 } else {
                     // if the condensed node has incoming edges, we need to update the edges
                     for (source, target, _) in node_incoming_edges {
-                        condensed_graph.update_edge(&source, &target, cycle_node_latency);
+                        let transfer_latency =
+                            incoming_transfer_latency(&source, &target, config.cost_model())?;
+                        condensed_graph.update_edge(
+                            &source,
+                            &target,
+                            cycle_node_latency + transfer_latency,
+                        );
                     }
                     // we use the entry_node_latency_map to save the latency of the entry node if it is a condensed node
-                    entry_node_latency_map
-                        .insert(condensed_node[0].leader, condensed_node[0].get_latency());
+                    entry_node_latency_map.insert(
+                        condensed_node[0].leader,
+                        condensed_node[0].cost(config.cost_model())?,
+                    );
                 }
             }
             Err(_) => {
+                // a pathological graph could nest the cycles until the stack overflows
+                if depth >= config.max_depth {
+                    let real_entry_address = fictious_map
+                        .get(&entry_block.leader)
+                        .unwrap_or(&entry_block.leader);
+                    return Err(WcetError::AnalysisTooDeep(*real_entry_address));
+                }
+
                 let mut condensed_cycle_graph = condensate_graph(
                     cycle_graph.clone(),
                     entry_node_latency_map,
@@ -274,13 +299,20 @@ pub fn condensate_graph(
                     recursive_functions,
                     latency_map,
                     fictious_map,
-                );
+                    cycles,
+                    loop_latencies,
+                    non_terminating_loops,
+                    loops,
+                    config,
+                    depth + 1,
+                )?;
 
                 let condensed_cycle_graph_nodes = condensed_cycle_graph.get_nodes();
 
-                let mut condensed_cycle_entry_node = condensed_node.clone();
-
-                let mut entry_nodes = condensed_cycle_graph_nodes
+                // the entry nodes of the condensed cycle have no predecessor inside it and are
+                // reached from the outer graph; each node is represented by its first block, as
+                // the exit nodes are assumed not to be condensed
+                let entry_blocks = condensed_cycle_graph_nodes
                     .iter()
                     .filter(|node| {
                         condensed_cycle_graph
@@ -291,131 +323,117 @@ pub fn condensate_graph(
                                 .iter()
                                 .any(|n| n[0].get_targets().contains(&node[0].leader))
                     })
-                    .collect::<Vec<_>>();
-
-                if !entry_nodes.is_empty() {
-                    condensed_cycle_entry_node = entry_nodes[0].clone();
-                }
-
-                let mut max_cycles = 1;
-
-                if let Some(real_entry_address) =
-                    fictious_map.get(&condensed_cycle_entry_node[0].leader)
-                {
-                    let env_var_key = format!("CYCLE_0x{real_entry_address:x}");
-                    if let Ok(cycle_var) = std::env::var(&env_var_key) {
-                        match cycle_var.parse::<u32>() {
-                            Ok(cycle_var) => max_cycles = cycle_var,
-                            Err(_) => {
-                                panic!(
-                                    "The environment variable {env_var_key} is not a valid number"
-                                );
-                            }
+                    .map(|node| (node[0].leader, node[0].clone()))
+                    .collect::<BTreeMap<u64, Block>>();
+                let first_entry_block = match entry_blocks.values().next() {
+                    Some(block) => block.clone(),
+                    None => condensed_cycle_graph_nodes
+                        .iter()
+                        .find(|node| node.contains(&entry_block))
+                        .expect("Entry block not in the condensed cycle")[0]
+                        .clone(),
+                };
+                let cycle_blocks = condensed_cycle_graph_nodes
+                    .iter()
+                    .map(|node| node[0].clone())
+                    .collect::<Vec<Block>>();
+
+                let (cycle_entry_block, cycle_exit_block) = select_cycle_boundary(
+                    &mut condensed_graph,
+                    &condensed_node,
+                    &cycle_graph,
+                    &cycle_blocks,
+                    first_entry_block,
+                    entry_blocks,
+                    config,
+                );
+                let cycle_exit_block = cycle_exit(
+                    &cycle_entry_block,
+                    cycle_exit_block,
+                    recursive_functions,
+                    config,
+                );
+                let node_of = |block: &Block| {
+                    condensed_cycle_graph_nodes
+                        .iter()
+                        .find(|node| node[0] == *block)
+                        .expect("Block not in the condensed cycle")
+                        .clone()
+                };
+                let condensed_cycle_entry_node = node_of(&cycle_entry_block);
+                let condensed_cycle_exit_node = cycle_exit_block.as_ref().map(node_of);
+
+                // the bound of the cycle entered at the same block has already been recorded, as
+                // well as the latency of its back edges
+                let (max_cycles, back_edge_latency) = if cycle_entry_block == entry_block {
+                    (max_cycles, back_edge_latency)
+                } else {
+                    let real_entry_address = fictious_map
+                        .get(&cycle_entry_block.leader)
+                        .unwrap_or(&cycle_entry_block.leader);
+                    let max_cycles = match cycle_exit_block {
+                        Some(_) => {
+                            cycle_bound(&cycle_entry_block, fictious_map, &loop_names, config)
+                        }
+                        None => {
+                            non_terminating_loops.push(*real_entry_address);
+                            1
                         }
                     };
-                }
+                    if config.list_loops {
+                        list_loop(
+                            loops,
+                            &cycle_entry_block,
+                            &condensed_node,
+                            cycle_exit_block.as_ref().map(|_| max_cycles),
+                            cycles,
+                            fictious_map,
+                            &loop_names,
+                            config,
+                        );
+                    }
+                    cycles.push((*real_entry_address, max_cycles));
+                    (max_cycles, 0.0)
+                };
 
                 let entry_node_latency =
                     match entry_node_latency_map.get(&condensed_cycle_entry_node[0].leader) {
                         // if the entry node is condensed, its latency is already in the map
                         Some(latency) => *latency,
-                        None => condensed_cycle_entry_node[0].get_latency(),
+                        None => condensed_cycle_entry_node[0].cost(config.cost_model())?,
                     };
 
-                let outer_nodes = condensed_graph
-                    .neighbors_directed(&condensed_node, Outgoing)
-                    .to_owned();
-
-                let mut false_outer_nodes = HashMap::<Vec<Block>, Vec<Block>>::new(); // exit_node -> outer_blocks
-
-                let mut normal_cycle = false;
-                // handling case where there are more than one outer block
-                for outer_blocks in outer_nodes {
-                    // handle case where outer block has more than one block --> it is a condensed node
-                    for outer_block in outer_blocks.clone() {
-                        if let Some(cycle_node) =
-                            condensed_cycle_graph.get_nodes().iter().find(|node| {
-                                // we assume that the exit_node is not condensed
-                                node[0].get_targets().contains(&outer_block.clone().leader)
-                            })
-                        {
-                            for entry in entry_nodes.clone() {
-                                if entry[0].leader == cycle_node[0].leader {
-                                    normal_cycle = true;
-                                    condensed_cycle_entry_node = entry.clone();
-                                    entry_nodes.retain(|x| x[0].leader != entry[0].clone().leader);
-                                    break;
-                                }
-                            }
-                            if !normal_cycle {
-                                false_outer_nodes.insert(cycle_node.clone(), outer_blocks.clone());
-                            }
-                        }
-                    }
-                }
-
-                let mut condensed_cycle_exit_node = condensed_cycle_entry_node.clone();
-
-                // if the entry and exit nodes are the same
-                if normal_cycle {
-                    // if the outer block is not the normal outer block, we need to remove it
-                    for outer_blocks in false_outer_blocks.values() {
-                        condensed_cycle_graph.remove_node(outer_blocks);
-                        printwarning!(
-                            "We are not considering the exit block 0x{:x} as exit from the cycle 0x{:x}",
-                            outer_blocks[0].leader,
-                            condensed_cycle_entry_node[0].leader
-                        );
-                    }
-
-                    for entry in entry_nodes.clone() {
-                        printwarning!(
-                            "We are not considering the entry block 0x{:x} as entry from the cycle 0x{:x}",
-                            entry[0].leader,
-                            condensed_cycle_entry_node[0].leader
-                        );
-                    }
-                } else if false_outer_blocks.is_empty() {
-                    printwarning!(
-                        "There is no outer block for the cycle 0x{:x}",
-                        condensed_cycle_entry_node[0].leader
-                    );
-                } else if false_outer_blocks.len() > 1 {
-                    // find the block with the highest leader and use it as exit block
-                    for possible_exit_block in false_outer_blocks.keys() {
-                        let current_leader = possible_exit_block.leader;
-                        if current_leader > condensed_cycle_exit_node[0].leader {
-                            condensed_cycle_exit_node[0] = possible_exit_block.clone();
-                        }
-                    }
-                    printwarning!(
-                        "There are more than one outer block for the cycle 0x{:x} and we are considering 0x{:x}",
-                        condensed_cycle_entry_node[0].leader, condensed_cycle_exit_node[0].leader
-                    );
-                } else {
-                    condensed_cycle_exit_node = false_outer_nodes.keys().next().unwrap().clone();
-                }
-
-                if !normal_cycle && entry_nodes.len() > 1 {
-                    printwarning!(
-                        "There are more than one entry block for the cycle 0x{:x} and none of them is also an exit block!!",
-                        condensed_cycle_entry_node[0].leader
-                    );
-                    printwarning!(
-                        "We thus consider the entry block 0x{:x} and the exit block 0x{:x} for this cycle",
-                        condensed_cycle_entry_node[0].leader,
-                        condensed_cycle_exit_node[0].leader
-                    );
-                }
-
                 let cycle_node_latency = condensed_cycle_graph
                     .reconstruct_longest_path(
                         &condensed_cycle_entry_node,
-                        &condensed_cycle_exit_node,
-                        entry_node_latency as f32,
+                        condensed_cycle_exit_node.as_deref(),
+                        entry_node_latency,
                         max_cycles,
                     )
-                    .unwrap();
+                    .map_err(|_| {
+                        let leader = condensed_cycle_entry_node[0].leader;
+                        WcetError::NegativeCycle(
+                            *fictious_map.get(&leader).unwrap_or(&leader),
+                            true,
+                        )
+                    })?
+                    + back_edge_latency * max_cycles as f64;
+                let leader = condensed_cycle_entry_node[0].leader;
+                let real_entry_address = *fictious_map.get(&leader).unwrap_or(&leader);
+                let cycle_latency = weight_to_latency(cycle_node_latency, real_entry_address)?;
+                let iteration_latency = weight_to_latency(
+                    condensed_cycle_graph
+                        .longest_path(&condensed_cycle_entry_node)
+                        .expect("Condensed cycle graph not acyclic")
+                        + entry_node_latency
+                        + back_edge_latency,
+                    real_entry_address,
+                )?;
+                insert_loop_latency(
+                    loop_latencies,
+                    real_entry_address,
+                    (iteration_latency, max_cycles, cycle_latency),
+                );
 
                 let mut max_rec_cycles = 1;
 
@@ -424,35 +442,33 @@ pub fn condensate_graph(
                     for (recursive_address, ret_address) in recursive_functions {
                         if current_ret_address == *ret_address {
                             let env_var_key = format!("RECURSIVE_0x{recursive_address:x}");
-                            if let Ok(recursive_var) = std::env::var(&env_var_key) {
-                                match recursive_var.parse::<u32>() {
-                                    Ok(recursive_var) => max_rec_cycles = recursive_var,
-                                    Err(_) => {
-                                        panic!(
-                                            "The environment variable {env_var_key} is not a valid number"
-                                        );
-                                    }
-                                }
-                            };
-                            printwarning!(
-                                "Found a recursive function with multiple recursion at address 0x{recursive_address:x} -> {max_rec_cycles} function iterations \
+                            if let Some(recursive_bound) =
+                                config.loop_bounds.recursive_bound(*recursive_address)
+                            {
+                                max_rec_cycles = recursive_bound;
+                            }
+                            trace::emit(config, Event {
+                                event: "recursive_bound",
+                                address: Some(*recursive_address),
+                                reason: format!("Found a recursive function with multiple recursion at address 0x{recursive_address:x} -> {max_rec_cycles} function iterations \
                                 considered for the wcet calculation for every recursion (PESSIMISTIC APPROACH). If you want to change this value, set the environment \
-                                variable {env_var_key}"
-                                );
+                                variable {env_var_key}"),
+                                ..Event::default()
+                            });
                         }
                     }
 
                     if max_rec_cycles > 0 {
                         //find the return cycle pattern of a inner recursive invocation
-                        let mut ret_latency: u64 = 0;
+                        let mut ret_latency: f64 = 0.0;
                         for node in condensed_cycle_graph.get_nodes() {
                             if let Some(ExitJump::Ret(_)) = node[0].exit_jump {
                                 if node[0].leader != entry_block.leader {
                                     let next_block = condensed_cycle_graph
                                         .neighbors_directed(&node, Outgoing)[0][0]
                                         .clone();
-                                    ret_latency += node[0].get_latency() as u64;
-                                    ret_latency += next_block.get_latency() as u64;
+                                    ret_latency += node[0].cost(config.cost_model())?;
+                                    ret_latency += next_block.cost(config.cost_model())?;
                                     break;
                                 }
                             }
@@ -460,14 +476,14 @@ pub fn condensate_graph(
 
                         latency_map.insert(
                             current_ret_address,
-                            (cycle_node_latency as u32 - entry_node_latency - ret_latency as u32
-                                + ret_latency as u32 * max_rec_cycles)
-                                * max_rec_cycles,
+                            (cycle_node_latency - entry_node_latency - ret_latency
+                                + ret_latency * max_rec_cycles as f64)
+                                * max_rec_cycles as f64,
                         );
                     } else {
                         latency_map.insert(
                             current_ret_address,
-                            (cycle_node_latency as u32 - entry_node_latency) * max_rec_cycles,
+                            (cycle_node_latency - entry_node_latency) * max_rec_cycles as f64,
                         );
                     }
                 }
@@ -475,28 +491,417 @@ pub fn condensate_graph(
                 let node_incoming_edges = condensed_graph.edges_directed(&condensed_node, Incoming);
                 if node_incoming_edges.is_empty() {
                     // if the node has no incoming edges, it is an entry node
-                    entry_node_latency_map
-                        .insert(condensed_node[0].leader, cycle_node_latency as u32);
+                    entry_node_latency_map.insert(condensed_node[0].leader, cycle_node_latency);
                 // we chose [0] as reference for the condensed node for simplicity
                 } else {
                     for (source, target, _) in node_incoming_edges {
-                        condensed_graph.update_edge(&source, &target, cycle_node_latency);
+                        let transfer_latency =
+                            incoming_transfer_latency(&source, &target, config.cost_model())?;
+                        condensed_graph.update_edge(
+                            &source,
+                            &target,
+                            cycle_node_latency + transfer_latency,
+                        );
                     }
-                    entry_node_latency_map
-                        .insert(condensed_node[0].leader, condensed_node[0].get_latency());
+                    entry_node_latency_map.insert(
+                        condensed_node[0].leader,
+                        condensed_node[0].cost(config.cost_model())?,
+                    );
                 }
 
-                let digraph = condensed_cycle_graph.to_dot_graph();
-                let mut dot_file = std::fs::File::create(format!(
-                    "{graph_dir}/condensed_cycle_graph_{graph_number}.dot"
-                ))
-                .expect("Unable to create file");
-                dot_file
-                    .write_all(digraph.as_bytes())
-                    .expect("Unable to write dot file");
+                config.write_graph(&format!("condensed_cycle_graph_{graph_number}.dot"), || {
+                    condensed_cycle_graph.to_dot_graph()
+                })?;
+            }
+        }
+    }
+
+    Ok(condensed_graph)
+}
+
+/// Chooses the entry and the exit of the cycle `condensed_node` of `condensed_graph`, among the
+/// `entry_blocks` (leader -> block) reached from outside it and the `cycle_blocks` jumping out
+/// of it; it is used both for the cycles of blocks and for the cycles of condensed nodes, whose
+/// blocks are the first of each node. If an entry block is also an exit block, the cycle is a
+/// normal one entered and left at that block: the other exits and entries are removed from
+/// `condensed_graph`. Otherwise the iterations go from `entry_block` to the last exit block
+/// (see `select_exit_block`). Returns the (entry_block, exit_block) of the cycle, without exit
+/// block if no block of the cycle jumps out of it (see `cycle_exit`).
+fn select_cycle_boundary(
+    condensed_graph: &mut MappedCondensedGraph,
+    condensed_node: &[Block],
+    cycle_graph: &MappedGraph,
+    cycle_blocks: &[Block],
+    mut entry_block: Block,
+    mut entry_blocks: BTreeMap<u64, Block>,
+    config: &Config,
+) -> (Block, Option<Block>) {
+    let incomings_vec = condensed_graph.neighbors_directed(condensed_node, Incoming);
+    let outer_nodes = condensed_graph.neighbors_directed(condensed_node, Outgoing);
+
+    let mut false_outer_blocks = HashMap::<Block, Vec<Block>>::new(); // exit_block -> outer_blocks
+
+    let mut normal_cycle = false;
+    // handling case where there are more than one outer block
+    for outer_blocks in outer_nodes {
+        // handle case where outer block has more than one block --> it is a condensed node
+        for outer_block in &outer_blocks {
+            if let Some(cycle_block) = cycle_blocks
+                .iter()
+                .find(|block| block.get_targets().contains(&outer_block.leader))
+            {
+                match entry_blocks.remove(&cycle_block.leader) {
+                    Some(entry) => {
+                        normal_cycle = true;
+                        entry_block = entry;
+                    }
+                    None => {
+                        false_outer_blocks.insert(cycle_block.clone(), outer_blocks.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    // if the entry and exit nodes are the same
+    let exit_block = if normal_cycle {
+        // if the outer block is not the normal outer block, we need to remove it
+        // sorted to report the warnings in a stable order
+        let mut removed_outer_blocks = false_outer_blocks.values().collect::<Vec<&Vec<Block>>>();
+        removed_outer_blocks.sort_by_key(|outer_blocks| outer_blocks[0].leader);
+        for outer_blocks in removed_outer_blocks {
+            condensed_graph.remove_node(outer_blocks);
+            trace::emit(
+                config,
+                Event {
+                    event: "ignored_exit",
+                    cycle: Some(entry_block.leader),
+                    exit: Some(outer_blocks[0].leader),
+                    reason: format!(
+                    "We are not considering the exit block 0x{:x} as exit from the cycle 0x{:x}",
+                    outer_blocks[0].leader, entry_block.leader
+                ),
+                    ..Event::default()
+                },
+            );
+        }
+
+        for entry_leader in entry_blocks.keys() {
+            trace::emit(
+                config,
+                Event {
+                    event: "ignored_entry",
+                    cycle: Some(entry_block.leader),
+                    entry: Some(*entry_leader),
+                    reason: format!(
+                    "We are not considering the entry block 0x{:x} as entry from the cycle 0x{:x}",
+                    entry_leader, entry_block.leader
+                ),
+                    ..Event::default()
+                },
+            );
+            // remove the incomings edges of the entry blocks not considered in the WCET calculation
+            for pre_cycle_blocks in &incomings_vec {
+                for block in pre_cycle_blocks {
+                    if block.get_targets().contains(entry_leader) {
+                        condensed_graph.remove_edge(pre_cycle_blocks, condensed_node);
+                    }
+                }
+            }
+        }
+        Some(entry_block.clone())
+    } else if false_outer_blocks.is_empty() {
+        None
+    } else if false_outer_blocks.len() > 1 {
+        let exit_block = select_exit_block(
+            cycle_graph,
+            &entry_block,
+            &false_outer_blocks.keys().cloned().collect::<Vec<Block>>(),
+            config,
+        );
+
+        trace::emit(config, Event {
+            event: "multiple_exits",
+            cycle: Some(entry_block.leader),
+            exit: Some(exit_block.leader),
+            reason: format!(
+                "There are more than one outer block for the cycle 0x{:x} and we are considering 0x{:x}",
+                entry_block.leader, exit_block.leader
+            ),
+            ..Event::default()
+        });
+        Some(exit_block)
+    } else {
+        Some(false_outer_blocks.keys().next().unwrap().clone())
+    };
+
+    if !normal_cycle && entry_blocks.len() > 1 {
+        let exit = match &exit_block {
+            Some(exit_block) => format!("the exit block 0x{:x}", exit_block.leader),
+            None => "no exit block".to_string(),
+        };
+        trace::emit(config, Event {
+            event: "multiple_entries",
+            cycle: Some(entry_block.leader),
+            entry: Some(entry_block.leader),
+            exit: exit_block.as_ref().map(|exit_block| exit_block.leader),
+            reason: format!(
+                "There are more than one entry block for the cycle 0x{:x} and none of them is also an exit block!! \
+                We thus consider the entry block 0x{:x} and {exit} for this cycle",
+                entry_block.leader, entry_block.leader
+            ),
+            ..Event::default()
+        });
+    }
+
+    (entry_block, exit_block)
+}
+
+/// Returns the exit block chosen by `select_cycle_boundary` for the cycle entered at
+/// `entry_block`. The "return" loop of a recursive function has no exit either, but it is
+/// bounded by the recursions and left from its ret block, which is returned; any other cycle
+/// without exit never terminates (e.g. the infinite loop of a task) and None is returned.
+fn cycle_exit(
+    entry_block: &Block,
+    exit_block: Option<Block>,
+    recursive_functions: &HashMap<u64, u64>, // function_address -> ret_address
+    config: &Config,
+) -> Option<Block> {
+    if exit_block.is_some() {
+        return exit_block;
+    }
+
+    if let Some(ExitJump::Ret(ret_address)) = entry_block.exit_jump {
+        if recursive_functions
+            .values()
+            .any(|address| *address == ret_address)
+        {
+            return Some(entry_block.clone());
+        }
+    }
+
+    trace::emit(config, Event {
+        event: "non_terminating",
+        cycle: Some(entry_block.leader),
+        reason: format!(
+            "There is no outer block for the cycle 0x{:x}: it never terminates (e.g. the infinite loop of a task), \
+            so a single iteration is considered for the wcet calculation",
+            entry_block.leader
+        ),
+        ..Event::default()
+    });
+    None
+}
+
+/// Returns the maximum iterations of the cycle entered at `entry_block`, from the loop bounds
+/// or 1 if it is not bounded, warning about the cycles of the original (not duplicated) blocks.
+fn cycle_bound(
+    entry_block: &Block,
+    fictious_map: &HashMap<u64, u64>, // fictious_address -> real_address
+    loop_names: &HashMap<u64, String>,
+    config: &Config,
+) -> u32 {
+    let mut max_cycles = 1;
+    if let Some(real_entry_address) = fictious_map.get(&entry_block.leader) {
+        if let Some(cycle_bound) = config
+            .loop_bounds
+            .cycle_bound(*real_entry_address, loop_names.get(real_entry_address))
+        {
+            max_cycles = cycle_bound;
+        }
+    } else {
+        if let Some(cycle_bound) = config
+            .loop_bounds
+            .cycle_bound(entry_block.leader, loop_names.get(&entry_block.leader))
+        {
+            max_cycles = cycle_bound;
+        }
+        let loop_name_hint = match loop_names.get(&entry_block.leader) {
+            Some(loop_name) => {
+                format!(" or the bound of \"{loop_name}\" in the loop bounds file")
+            }
+            None => String::new(),
+        };
+        trace::emit(config, Event {
+            event: "cycle_bound",
+            cycle: Some(entry_block.leader),
+            reason: format!("Found a cycle at address 0x{:x} -> {max_cycles} cycle iterations considered for the wcet calculation. \
+            If you want to change the value, please set the env var CYCLE_0x{:x}{loop_name_hint}", entry_block.leader, entry_block.leader),
+            ..Event::default()
+        });
+    }
+    max_cycles
+}
+
+/// Lists in `loops` the cycle entered at `entry_block`, with its function, its name, its
+/// iterations and the origin of the bound (`max_cycles` is None for a cycle that never
+/// terminates, counted once whatever its bound), and the real leaders of its blocks. The copies
+/// of a cycle in the duplicated blocks of a function are listed once, when its real entry
+/// address is not yet in `cycles`.
+#[allow(clippy::too_many_arguments)]
+fn list_loop(
+    loops: &mut Vec<LoopListing>,
+    entry_block: &Block,
+    cycle_blocks: &[Block],
+    max_cycles: Option<u32>,
+    cycles: &[(u64, u32)],            // (cycle_entry_address, max_cycles)
+    fictious_map: &HashMap<u64, u64>, // fictious_address -> real_address
+    loop_names: &HashMap<u64, String>,
+    config: &Config,
+) {
+    let real_address = |leader: u64| *fictious_map.get(&leader).unwrap_or(&leader);
+    let real_entry_address = real_address(entry_block.leader);
+    if cycles
+        .iter()
+        .any(|(cycle_entry_address, _)| *cycle_entry_address == real_entry_address)
+    {
+        return;
+    }
+
+    let loop_name = loop_names.get(&real_entry_address);
+    let mut leaders = cycle_blocks
+        .iter()
+        .map(|block| real_address(block.leader))
+        .collect::<Vec<u64>>();
+    leaders.sort();
+    loops.push(LoopListing {
+        entry: real_entry_address,
+        name: loop_name.cloned(),
+        function: entry_block.function.clone(),
+        bound: max_cycles,
+        origin: config
+            .loop_bounds
+            .cycle_bound_origin(real_entry_address, loop_name),
+        blocks: leaders,
+    });
+}
+
+/// Records the latency of a cycle; the copies of a cycle in the duplicated blocks of a function
+/// share its real entry address, so the most expensive one is kept.
+fn insert_loop_latency(
+    loop_latencies: &mut BTreeMap<u64, (u64, u32, u64)>,
+    real_entry_address: u64,
+    latency: (u64, u32, u64), // (iteration_latency, max_cycles, total_latency)
+) {
+    let entry = loop_latencies.entry(real_entry_address).or_insert(latency);
+    if latency.2 > entry.2 {
+        *entry = latency;
+    }
+}
+
+/// Names the loops as `function#n`, where n is the ordinal (from 1) of the loop among the loops
+/// of its function in address order. A loop starts at the target of a backward jump of the same
+/// function and ends at the last block jumping back to it; each block is mapped to the name of
+/// the innermost loop containing it, so that any entry chosen for the cycle finds its name.
+/// The duplicated blocks are not considered.
+pub fn loop_names(
+    blocks: &BTreeMap<u64, Block>,
+    fictious_map: &HashMap<u64, u64>, // fictious_address -> real_address
+) -> HashMap<u64, String> {
+    let real_blocks = blocks
+        .values()
+        .filter(|block| !fictious_map.contains_key(&block.leader))
+        .collect::<Vec<&Block>>();
+
+    let mut loops = BTreeMap::<u64, (&str, u64)>::new(); // header_address -> (function, end_address)
+    for block in real_blocks.iter() {
+        for target in block.get_targets() {
+            if let Some(target_block) = blocks.get(&target) {
+                if target <= block.leader && target_block.function == block.function {
+                    let end = loops
+                        .entry(target)
+                        .or_insert((&target_block.function, block.leader));
+                    end.1 = end.1.max(block.leader);
+                }
+            }
+        }
+    }
+
+    let mut ordinals = HashMap::<&str, usize>::new(); // function -> last ordinal
+    let mut names = HashMap::<u64, String>::new(); // header_address -> name
+    for (header, (function, _)) in loops.iter() {
+        let ordinal = ordinals.entry(function).or_insert(0);
+        *ordinal += 1;
+        names.insert(*header, format!("{function}#{ordinal}"));
+    }
+
+    let mut loop_names = HashMap::new();
+    for block in real_blocks {
+        let innermost_loop = loops
+            .range(..=block.leader)
+            .rev()
+            .find(|(_, (function, end))| *function == block.function && block.leader <= *end);
+        if let Some((header, _)) = innermost_loop {
+            loop_names.insert(block.leader, names[header].clone());
+        }
+    }
+
+    loop_names
+}
+
+/// Chooses the exit block of a cycle among the blocks with a successor outside it: the one
+/// reached from all the others without passing through `entry_block`, which is the last exit
+/// of every iteration (it post-dominates the other exits in the body of the cycle). If the
+/// exits are on parallel paths, the one with the highest leader is chosen with a warning.
+fn select_exit_block(
+    cycle_graph: &MappedGraph,
+    entry_block: &Block,
+    exit_blocks: &[Block],
+    config: &Config,
+) -> Block {
+    // the iterations restart from the entry block, so its incoming edges are not followed
+    let mut body_graph = cycle_graph.clone();
+    for (source, target, _) in body_graph.edges_directed(entry_block, Incoming) {
+        body_graph.remove_edge(&source, &target);
+    }
+
+    let last_exit = exit_blocks.iter().find(|exit_block| {
+        exit_blocks
+            .iter()
+            .all(|other| other == *exit_block || body_graph.has_path(other, exit_block))
+    });
+
+    match last_exit {
+        Some(exit_block) => exit_block.clone(),
+        None => {
+            let exit_block = exit_blocks
+                .iter()
+                .max_by_key(|block| block.leader)
+                .expect("No exit block")
+                .clone();
+            trace::emit(config, Event {
+                event: "parallel_exits",
+                cycle: Some(entry_block.leader),
+                exit: Some(exit_block.leader),
+                reason: format!(
+                    "The exit blocks of the cycle 0x{:x} are on parallel paths, we are considering the one with the highest address 0x{:x}",
+                    entry_block.leader, exit_block.leader
+                ),
+                ..Event::default()
+            });
+            exit_block
+        }
+    }
+}
+
+/// Returns the highest latency of the control transfers (taken branch, call or return) from the
+/// blocks of `source` to the blocks of `target`, which is kept when the weight of the edge is
+/// replaced by the latency of the cycle.
+fn incoming_transfer_latency(
+    source: &[Block],
+    target: &[Block],
+    cost_model: &dyn CostModel,
+) -> Result<f64, WcetError> {
+    let mut transfer_latency: f64 = 0.0;
+    for block in source {
+        let targets = block.get_targets();
+        for target_block in target {
+            if targets.contains(&target_block.leader) {
+                transfer_latency =
+                    transfer_latency.max(block.transfer_latency(target_block, cost_model)?);
             }
         }
     }
 
-    condensed_graph
+    Ok(transfer_latency)
 }
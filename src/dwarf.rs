@@ -0,0 +1,66 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use gimli::{EndianSlice, RunTimeEndian, SectionId};
+use object::{Object, ObjectSection};
+
+use crate::printwarning;
+
+/// Returns the source location (`file:line`) of each address listed in the DWARF line programs
+/// of the object file. The map is empty if the object has no debug information.
+pub fn line_table(obj_file: &object::File) -> BTreeMap<u64, String> {
+    let mut line_table = BTreeMap::new();
+
+    if let Err(e) = read_line_programs(obj_file, &mut line_table) {
+        printwarning!("Unable to read the DWARF line information: {e}");
+    }
+
+    line_table
+}
+
+fn read_line_programs(
+    obj_file: &object::File,
+    line_table: &mut BTreeMap<u64, String>, // address -> file:line
+) -> Result<(), gimli::Error> {
+    let endian = if obj_file.is_little_endian() {
+        RunTimeEndian::Little
+    } else {
+        RunTimeEndian::Big
+    };
+
+    // the sections missing from the object are loaded as empty
+    let load_section = |id: SectionId| -> Result<Cow<[u8]>, gimli::Error> {
+        Ok(match obj_file.section_by_name(id.name()) {
+            Some(section) => section.uncompressed_data().unwrap_or(Cow::Borrowed(&[])),
+            None => Cow::Borrowed(&[]),
+        })
+    };
+    let dwarf_cow = gimli::Dwarf::load(load_section)?;
+    let dwarf = dwarf_cow.borrow(|section| EndianSlice::new(section, endian));
+
+    let mut units = dwarf.units();
+    while let Some(header) = units.next()? {
+        let unit = dwarf.unit(header)?;
+        let Some(program) = unit.line_program.clone() else {
+            continue;
+        };
+
+        let mut rows = program.rows();
+        while let Some((header, row)) = rows.next_row()? {
+            if row.end_sequence() {
+                continue;
+            }
+            let (Some(line), Some(file)) = (row.line(), row.file(header)) else {
+                continue;
+            };
+
+            let file_name = dwarf.attr_string(&unit, file.path_name())?;
+            line_table.insert(
+                row.address(),
+                format!("{}:{line}", file_name.to_string_lossy()),
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use std::path::PathBuf;
+
+use crate::graph::MAX_EXACT_WEIGHT;
+
+#[derive(Debug)]
+pub enum WcetError {
+    InvalidObject(object::Error), // the bytes are not a supported object file
+    EmptyText,                    // no instruction to analyze
+    DisassembleFailed(capstone::Error), // capstone failed to disassemble the code
+    UnsupportedArch(object::Architecture), // architecture not supported by the tool
+    UnsupportedJumps(capstone::Arch), // capstone architecture whose jumps are not recognized
+    EntryNotFound(u64),           // entry point that is not the leader of a block
+    EntrySymbolNotFound(String),  // entry point given by name that is not in the symbols
+    NoProgramEntry,               // the program has neither an entry point nor a main function
+    NegativeCycle(u64, bool), // leader of the block from which the cycle is reached, whether in a nested cycle
+    OverlappingCode(u64, u64), // start addresses of two code regions sharing addresses
+    AnalysisTooDeep(u64), // address of the cycle or function exceeding the maximum recursion depth
+    UnknownReturnAddress(u64), // called function whose return address has not been recorded
+    RecursionNotCondensed(u64), // recursive function whose cycle of returns has not been condensed
+    InvalidJump(JumpError), // the jump of an instruction can't be recognized
+    InvalidArchMode(String, String), // names of an architecture and mode not supported by the tool
+    LatencyOverflow(u64), // address of the block whose latency is too large to be computed exactly
+    InvalidCost(u64, f64), // leader of the block whose cost (or the cost of an edge from it) is negative or not finite
+    AnnotationOutsideCode(u64), // address of a cost annotation that is not an instruction of a block
+    LatencyOverrideOutsideCode(u64), // address of a latency override that is not an instruction
+    UnboundedCycles(Vec<u64>), // entry addresses of the cycles without a bound, with --strict-bounds
+    Io(PathBuf, std::io::Error), // file or directory that can't be read or written (e.g. a graph)
+    InvalidFile(PathBuf, String), // configuration file that can't be loaded, with the reason
+    UnknownCpu(String),        // name neither of a CPU model nor of a latency profile
+    SymbolNotFound(String), // function given by name (or address) to an option, not found in the symbols
+    InvalidOption(&'static str, String), // name and value of a command line option that can't be used
+}
+
+impl std::fmt::Display for WcetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WcetError::InvalidObject(e) => write!(f, "Unable to parse the object file: {e}"),
+            WcetError::EmptyText => write!(f, "No instruction found in the text sections"),
+            WcetError::DisassembleFailed(e) => write!(f, "Failed to disassemble given code: {e}"),
+            WcetError::UnsupportedArch(arch) => write!(f, "Unsupported architecture: {arch:?}"),
+            WcetError::UnsupportedJumps(arch) => {
+                write!(
+                    f,
+                    "The jumps of the architecture {arch:?} are not supported"
+                )
+            }
+            WcetError::EntryNotFound(entry) => {
+                write!(
+                    f,
+                    "The entry point 0x{entry:x} is not the leader of a block"
+                )
+            }
+            WcetError::EntrySymbolNotFound(entry) => {
+                write!(f, "The entry point {entry} is not a symbol of the object")
+            }
+            WcetError::NoProgramEntry => write!(
+                f,
+                "The program has neither an entry point nor a main function, \
+                select the entry point to analyze with --entry"
+            ),
+            WcetError::NegativeCycle(leader, nested) => {
+                write!(
+                    f,
+                    "Unable to compute the longest path from the block 0x{leader:x}: \
+                    the graph contains a cycle that has not been condensed"
+                )?;
+                if *nested {
+                    write!(f, " (while condensing the cycles nested in another cycle)")?;
+                }
+                Ok(())
+            }
+            WcetError::OverlappingCode(first, second) => write!(
+                f,
+                "The code regions starting at 0x{first:x} and 0x{second:x} overlap"
+            ),
+            WcetError::AnalysisTooDeep(address) => write!(
+                f,
+                "The analysis of 0x{address:x} exceeded the maximum recursion depth"
+            ),
+            WcetError::UnknownReturnAddress(function) => write!(
+                f,
+                "The return address of the calls to 0x{function:x} has not been recorded"
+            ),
+            WcetError::RecursionNotCondensed(function) => write!(
+                f,
+                "The returns of the recursive function 0x{function:x} have not been condensed \
+                into a cycle"
+            ),
+            WcetError::InvalidJump(e) => write!(f, "Unable to recognize the jump: {e}"),
+            WcetError::LatencyOverflow(address) => write!(
+                f,
+                "The latency computed from 0x{address:x} exceeds {MAX_EXACT_WEIGHT} clock cycles, \
+                the largest integer represented exactly by the weights of the graph"
+            ),
+            WcetError::InvalidCost(leader, cost) => write!(
+                f,
+                "The cost model returned {cost} for the block 0x{leader:x}, \
+                the costs must be finite and non-negative"
+            ),
+            WcetError::AnnotationOutsideCode(address) => write!(
+                f,
+                "The cost annotation at 0x{address:x} is not the address of an instruction of a block"
+            ),
+            WcetError::LatencyOverrideOutsideCode(address) => write!(
+                f,
+                "The latency override at 0x{address:x} is not the address of an instruction"
+            ),
+            WcetError::UnboundedCycles(addresses) => write!(
+                f,
+                "The cycles at {} have no bound, set them in the loop bounds file \
+                or with the CYCLE_0x<address> (RECURSIVE_0x<address>) environment variables",
+                addresses
+                    .iter()
+                    .map(|address| format!("0x{address:x}"))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            WcetError::InvalidArchMode(arch, mode) => {
+                write!(f, "Unsupported architecture {arch:?} with mode {mode:?}")
+            }
+            WcetError::Io(path, e) => write!(f, "Unable to access {}: {e}", path.display()),
+            WcetError::InvalidFile(path, e) => write!(f, "Unable to load {}: {e}", path.display()),
+            WcetError::UnknownCpu(name) => write!(f, "Unknown CPU model or latency profile {name}"),
+            WcetError::SymbolNotFound(name) => write!(f, "Function {name} not found"),
+            WcetError::InvalidOption(option, value) => {
+                write!(f, "Invalid value {value} of --{option}, see --help")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WcetError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JumpError {
+    UnsupportedArch(capstone::Arch), // capstone architecture whose jumps are not recognized
+    MissingMnemonic(u64),            // address of an instruction without mnemonic
+}
+
+impl std::fmt::Display for JumpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JumpError::UnsupportedArch(arch) => {
+                write!(
+                    f,
+                    "the jumps of the architecture {arch:?} are not supported"
+                )
+            }
+            JumpError::MissingMnemonic(address) => {
+                write!(f, "the instruction at 0x{address:x} has no mnemonic")
+            }
+        }
+    }
+}
+
+impl std::error::Error for JumpError {}
@@ -1,22 +1,184 @@
 #![allow(dead_code)]
-use std::collections::{hash_map, HashMap};
+use std::collections::{hash_map, BTreeMap, BTreeSet, HashMap};
 
-use petgraph::algo::{bellman_ford, condensation};
+use petgraph::algo::dominators::simple_fast;
+use petgraph::algo::{bellman_ford, condensation, has_path_connecting, tarjan_scc, toposort};
 use petgraph::dot::Dot;
 use petgraph::stable_graph::EdgeIndex;
 use petgraph::stable_graph::{NodeIndex, StableGraph};
-use petgraph::visit::EdgeRef;
+use petgraph::visit::{EdgeRef, IntoEdgeReferences};
 use petgraph::Direction;
+use serde::Serialize;
 
 use crate::block::Block;
+use crate::bounds::parse_address;
+use crate::error::WcetError;
+use crate::instruction::Instruction;
+use crate::jump::ExitJump;
+
+// 2^53: the integers above it can't be represented exactly by the f64 weights of the graphs
+pub const MAX_EXACT_WEIGHT: f64 = 9_007_199_254_740_992.0;
+
+// node -> distance from the source, node -> predecessor along the longest path
+type LongestDistances = (
+    HashMap<NodeIndex<u32>, f64>,
+    HashMap<NodeIndex<u32>, NodeIndex<u32>>,
+);
+
+// serialized form of a block in the JSON export of the graphs
+#[derive(Serialize)]
+struct JsonBlock<'a> {
+    leader: u64,
+    function: &'a str,
+    latency: u32, // clock cycles
+    instructions: &'a [Instruction],
+    exit_jump: &'a Option<ExitJump>,
+}
+
+impl<'a> From<&'a Block> for JsonBlock<'a> {
+    fn from(block: &'a Block) -> Self {
+        JsonBlock {
+            leader: block.leader,
+            function: &block.function,
+            latency: block.get_latency(),
+            instructions: &block.instructions,
+            exit_jump: &block.exit_jump,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonEdge {
+    source: u64, // leader of the source node
+    target: u64, // leader of the target node
+    weight: f64,
+}
+
+#[derive(Serialize)]
+struct JsonGraph<N> {
+    nodes: Vec<N>,
+    edges: Vec<JsonEdge>,
+}
+
+#[derive(Serialize)]
+struct JsonCondensedNode<'a> {
+    leader: u64, // leader of the first block of the node
+    blocks: Vec<JsonBlock<'a>>,
+}
+
+// node of a GraphML document, a block or a condensed node
+struct GraphmlNode<'a> {
+    leader: u64, // leader of the (first) block
+    function: &'a str,
+    latency: u64,       // clock cycles of all the blocks
+    exit_jump: &'a str, // kind of the exit jump, "Cycle" for the condensed cycles
+    blocks: Vec<u64>,   // leaders of the blocks
+}
+
+impl<'a> From<&'a Block> for GraphmlNode<'a> {
+    fn from(block: &'a Block) -> Self {
+        GraphmlNode {
+            leader: block.leader,
+            function: &block.function,
+            latency: block.get_latency() as u64,
+            exit_jump: block.exit_jump.as_ref().map_or("None", ExitJump::kind),
+            blocks: vec![block.leader],
+        }
+    }
+}
+
+/// Writes the nodes and the edges (source, target, weight) as a GraphML document, in which
+/// the nodes are identified by their leaders.
+fn graphml(nodes: &[GraphmlNode], edges: &[(u64, u64, f64)]) -> String {
+    let mut graphml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="leader" for="node" attr.name="leader" attr.type="string"/>
+  <key id="function" for="node" attr.name="function" attr.type="string"/>
+  <key id="latency" for="node" attr.name="latency" attr.type="long"/>
+  <key id="exit_jump" for="node" attr.name="exit_jump" attr.type="string"/>
+  <key id="blocks" for="node" attr.name="blocks" attr.type="string"/>
+  <key id="weight" for="edge" attr.name="weight" attr.type="double"/>
+  <graph id="G" edgedefault="directed">
+"#,
+    );
+
+    for node in nodes {
+        let blocks = node
+            .blocks
+            .iter()
+            .map(|leader| format!("0x{leader:x}"))
+            .collect::<Vec<String>>();
+        graphml += &format!(
+            r#"    <node id="0x{leader:x}">
+      <data key="leader">0x{leader:x}</data>
+      <data key="function">{}</data>
+      <data key="latency">{}</data>
+      <data key="exit_jump">{}</data>
+      <data key="blocks">{}</data>
+    </node>
+"#,
+            escape_xml(node.function),
+            node.latency,
+            node.exit_jump,
+            blocks.join(" "),
+            leader = node.leader,
+        );
+    }
+    for (source, target, weight) in edges {
+        graphml += &format!(
+            r#"    <edge source="0x{source:x}" target="0x{target:x}">
+      <data key="weight">{weight}</data>
+    </edge>
+"#
+        );
+    }
+
+    graphml += "  </graph>\n</graphml>\n";
+    graphml
+}
+
+// escapes the characters of a name (e.g. a C++ template) that are special in XML
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Size metrics of a control flow graph.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct GraphStats {
+    pub nodes: usize,
+    pub edges: usize,
+    pub sccs: usize,         // strongly connected components with more than one block
+    pub self_loops: usize,   // blocks with an edge to themselves
+    pub max_scc_size: usize, // blocks of the largest strongly connected component
+}
+
+impl std::fmt::Display for GraphStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Blocks: {}, edges: {}, cycles: {} (largest with {} blocks), self-loops: {}",
+            self.nodes, self.edges, self.sccs, self.max_scc_size, self.self_loops
+        )
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct MappedGraph {
-    pub graph: StableGraph<Block, f32>,
+    pub graph: StableGraph<Block, f64>,
     pub node_index_map: HashMap<u64, NodeIndex<u32>>,
     pub edge_index_map: HashMap<(u64, u64), EdgeIndex<u32>>,
 }
 
+impl Default for MappedGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MappedGraph {
     pub fn new() -> Self {
         MappedGraph {
@@ -26,6 +188,88 @@ impl MappedGraph {
         }
     }
 
+    /// Builds a graph from a textual spec, e.g. to test the graph algorithms without a binary.
+    /// Each line is either a block, `<leader> [latency]`, or an edge, `<source> -> <target>
+    /// [weight]`, and `#` starts a comment. The latency of a block is 1 if omitted (also for
+    /// the blocks only named by the edges) and the weight of an edge is the latency of its
+    /// target. Each block has a single instruction and jumps to the targets of its edges: the
+    /// first one is the taken target of a conditional jump, more than two make it indirect.
+    ///
+    /// ```text
+    /// 0x0 2
+    /// 0x10 5
+    /// 0x0 -> 0x10
+    /// 0x10 -> 0x0 3
+    /// 0x10 -> 0x20
+    /// ```
+    pub fn from_spec(spec: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut latencies = BTreeMap::<u64, u32>::new(); // leader -> latency
+        let mut edges = Vec::<(u64, u64, Option<f64>)>::new(); // (source, target, weight)
+
+        for line in spec.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some((source, target)) = line.split_once("->") {
+                let mut fields = target.split_whitespace();
+                let source = parse_address(source.trim())?;
+                let target = parse_address(fields.next().ok_or("Missing edge target")?)?;
+                let weight = fields.next().map(str::parse::<f64>).transpose()?;
+
+                latencies.entry(source).or_insert(1);
+                latencies.entry(target).or_insert(1);
+                edges.push((source, target, weight));
+            } else {
+                let mut fields = line.split_whitespace();
+                let leader = parse_address(fields.next().unwrap_or_default())?;
+                let latency = fields.next().map(str::parse::<u32>).transpose()?;
+                latencies.insert(leader, latency.unwrap_or(1));
+            }
+        }
+
+        let mut blocks = BTreeMap::<u64, Block>::new();
+        for (leader, latency) in latencies {
+            let mut block = Block::new(Instruction {
+                address: leader,
+                mnemonic: "nop".to_string(),
+                operands: Vec::new(),
+                size: 1,
+                latency,
+            });
+
+            let targets = edges
+                .iter()
+                .filter(|(source, _, _)| *source == leader)
+                .map(|(_, target, _)| *target)
+                .collect::<Vec<u64>>();
+            match targets.as_slice() {
+                [] => {}
+                [target] => block.set_exit_jump(ExitJump::UnconditionalAbsolute(*target)),
+                [taken, not_taken] => block.set_exit_jump(ExitJump::ConditionalAbsolute {
+                    taken: *taken,
+                    not_taken: *not_taken,
+                }),
+                _ => block.set_exit_jump(ExitJump::IndirectResolved(targets)),
+            }
+
+            blocks.insert(leader, block);
+        }
+
+        let mut graph = MappedGraph::new();
+        for block in blocks.values() {
+            graph.add_node(block.clone());
+        }
+        for (source, target, weight) in edges {
+            let target_block = blocks[&target].clone();
+            let weight = weight.unwrap_or(target_block.get_latency() as f64);
+            graph.add_edge(blocks[&source].clone(), target_block, weight);
+        }
+
+        Ok(graph)
+    }
+
     pub fn add_node(&mut self, block: Block) {
         if let hash_map::Entry::Vacant(e) = self.node_index_map.entry(block.leader) {
             let node_index = self.graph.add_node(block);
@@ -44,17 +288,23 @@ impl MappedGraph {
         self.graph.node_weights().cloned().collect::<Vec<Block>>()
     }
 
-    pub fn add_edge(&mut self, source: Block, target: Block, weight: f32) {
+    /// Adds an edge from `source` to `target`, adding also the blocks if needed. If the edge
+    /// already exists, it keeps the highest weight, i.e. the worst case.
+    pub fn add_edge(&mut self, source: Block, target: Block, weight: f64) {
         self.add_node(source.clone());
         self.add_node(target.clone());
 
-        if let hash_map::Entry::Vacant(e) =
-            self.edge_index_map.entry((source.leader, target.leader))
-        {
-            let source_index = self.node_index_map[&source.leader];
-            let target_index = self.node_index_map[&target.leader];
-            let edge_index = self.graph.add_edge(source_index, target_index, weight);
-            e.insert(edge_index);
+        match self.edge_index_map.entry((source.leader, target.leader)) {
+            hash_map::Entry::Vacant(e) => {
+                let source_index = self.node_index_map[&source.leader];
+                let target_index = self.node_index_map[&target.leader];
+                let edge_index = self.graph.add_edge(source_index, target_index, weight);
+                e.insert(edge_index);
+            }
+            hash_map::Entry::Occupied(e) => {
+                let edge_weight = &mut self.graph[*e.get()];
+                *edge_weight = edge_weight.max(weight);
+            }
         }
     }
 
@@ -65,13 +315,13 @@ impl MappedGraph {
         }
     }
 
-    pub fn update_edge(&mut self, a: &Block, b: &Block, weight: f32) {
+    pub fn update_edge(&mut self, a: &Block, b: &Block, weight: f64) {
         let a_index = self.node_index_map[&a.leader];
         let b_index = self.node_index_map[&b.leader];
         self.graph.update_edge(a_index, b_index, weight);
     }
 
-    pub fn get_edges(&self) -> Vec<(Block, Block, f32)> {
+    pub fn get_edges(&self) -> Vec<(Block, Block, f64)> {
         self.graph
             .edge_indices()
             .map(|edge_index| {
@@ -83,10 +333,10 @@ impl MappedGraph {
 
                 (source.clone(), target.clone(), *edge)
             })
-            .collect::<Vec<(Block, Block, f32)>>()
+            .collect::<Vec<(Block, Block, f64)>>()
     }
 
-    pub fn edges_directed(&self, node: &Block, direction: Direction) -> Vec<(Block, Block, f32)> {
+    pub fn edges_directed(&self, node: &Block, direction: Direction) -> Vec<(Block, Block, f64)> {
         let node_index = self.node_index_map[&node.leader];
         let edges = self.graph.edges_directed(node_index, direction);
 
@@ -97,7 +347,7 @@ impl MappedGraph {
 
                 (source.clone(), target.clone(), *edge.weight())
             })
-            .collect::<Vec<(Block, Block, f32)>>()
+            .collect::<Vec<(Block, Block, f64)>>()
     }
 
     pub fn neighbors_directed(&self, node: &Block, direction: Direction) -> Vec<Block> {
@@ -114,7 +364,50 @@ impl MappedGraph {
         blocks
     }
 
-    pub fn shortest_path(&self, source: &Block) -> f32 {
+    /// Returns whether `block` is an exit block of the graph, where its paths end: a return, a
+    /// block without successors, or an absolute jump leaving the analyzed region (e.g. a tail
+    /// call to a block that is not in the graph).
+    pub fn is_exit_block(&self, block: &Block) -> bool {
+        match &block.exit_jump {
+            Some(ExitJump::Ret(_)) => true,
+            Some(ExitJump::UnconditionalAbsolute(target))
+                if !self.node_index_map.contains_key(target) =>
+            {
+                true
+            }
+            _ => self
+                .graph
+                .neighbors_directed(self.node_index_map[&block.leader], Direction::Outgoing)
+                .next()
+                .is_none(),
+        }
+    }
+
+    /// Returns the exit blocks of the graph (see `is_exit_block`).
+    pub fn exit_blocks(&self) -> Vec<Block> {
+        self.graph
+            .node_weights()
+            .filter(|block| self.is_exit_block(block))
+            .cloned()
+            .collect()
+    }
+
+    // the reachable exit block with the minimum distance, i.e. the end of the longest path
+    // when the distances are negated; since the latencies are not negative, the farthest block
+    // can always be extended to an exit block without shortening the path
+    fn farthest_exit(&self, distances: &[f64]) -> Option<NodeIndex<u32>> {
+        self.graph
+            .node_indices()
+            .filter(|node_index| distances[node_index.index()].is_finite())
+            .filter(|node_index| self.is_exit_block(&self.graph[*node_index]))
+            .min_by(|a, b| {
+                distances[a.index()]
+                    .partial_cmp(&distances[b.index()])
+                    .unwrap()
+            })
+    }
+
+    pub fn shortest_path(&self, source: &Block) -> f64 {
         let paths = bellman_ford(&self.graph, self.node_index_map[&source.leader]).unwrap();
 
         let max_path_latency = paths
@@ -128,7 +421,54 @@ impl MappedGraph {
         max_path_latency
     }
 
-    pub fn longest_path(&self, source: &Block) -> Result<f32, petgraph::algo::NegativeCycle> {
+    /// Returns the latency of the shortest path from `source` to one of the blocks without
+    /// successors (unlike `is_exit_block`, the returns of the inlined functions don't end the
+    /// path) and the blocks along it, or None if no such block is reachable.
+    /// The cycles are never followed, since the weights of the edges are not negative.
+    pub fn shortest_path_to_exits(&self, source: &Block) -> Option<(f64, Vec<Block>)> {
+        let paths = bellman_ford(&self.graph, self.node_index_map[&source.leader]).ok()?;
+
+        let exit = self
+            .graph
+            .node_indices()
+            .filter(|node_index| paths.distances[node_index.index()].is_finite())
+            .filter(|node_index| {
+                self.graph
+                    .neighbors_directed(*node_index, Direction::Outgoing)
+                    .next()
+                    .is_none()
+            })
+            .min_by(|a, b| {
+                paths.distances[a.index()]
+                    .partial_cmp(&paths.distances[b.index()])
+                    .unwrap()
+            })?;
+
+        let mut path = Vec::new();
+        let mut current = Some(exit);
+        while let Some(node_index) = current {
+            path.push(self.graph[node_index].clone());
+            current = paths.predecessors[node_index.index()];
+        }
+        path.reverse();
+
+        Some((paths.distances[exit.index()], path))
+    }
+
+    /// Returns the latency of the shortest cycle passing through `block`, i.e. the cost of one
+    /// more iteration of the loop whose header is `block`, or None if `block` is not in a cycle.
+    pub fn shortest_cycle(&self, block: &Block) -> Option<f64> {
+        let node_index = self.node_index_map[&block.leader];
+        let paths = bellman_ford(&self.graph, node_index).ok()?;
+
+        self.graph
+            .edges_directed(node_index, Direction::Incoming)
+            .filter(|edge| paths.distances[edge.source().index()].is_finite())
+            .map(|edge| paths.distances[edge.source().index()] + *edge.weight())
+            .min_by(|a, b| a.partial_cmp(b).unwrap())
+    }
+
+    pub fn longest_path(&self, source: &Block) -> Result<f64, petgraph::algo::NegativeCycle> {
         // change the weights of the edges to negative values to find the longest path
         let mut graph = self.graph.clone();
         for edge in graph.edge_weights_mut() {
@@ -137,29 +477,119 @@ impl MappedGraph {
 
         let paths = bellman_ford(&graph, self.node_index_map[&source.leader])?;
 
-        let min_path_latency = paths
-            .distances
-            .iter()
-            .filter(|x| x.is_finite())
-            .min_by(|a, b| a.partial_cmp(b).unwrap())
-            .unwrap()
-            .to_owned();
+        // without a reachable exit block (e.g. in the body of a loop that never terminates),
+        // the path ends at the farthest reachable block
+        let distance = match self.farthest_exit(&paths.distances) {
+            Some(exit) => paths.distances[exit.index()],
+            None => paths
+                .distances
+                .iter()
+                .filter(|distance| distance.is_finite())
+                .fold(0.0, |farthest, distance| distance.min(farthest)),
+        };
+
+        Ok(-distance)
+    }
+
+    /// Returns the latency of the longest path from `source` to `sink`, or None if `sink` is
+    /// not reachable. Unlike `longest_path`, the path must end in the given block.
+    pub fn longest_path_to(
+        &self,
+        source: &Block,
+        sink: &Block,
+    ) -> Result<Option<f64>, petgraph::algo::NegativeCycle> {
+        Ok(self
+            .longest_path_between(source, sink)?
+            .map(|(latency, _)| latency))
+    }
+
+    /// Returns the latency of the longest path from `source` to `sink` (without the latency of
+    /// `source`, as for `longest_path`) with the blocks along it, or None if `sink` is not
+    /// reachable, e.g. to analyze a critical section between two blocks. The graph must be
+    /// acyclic between them, otherwise the path is unbounded.
+    pub fn longest_path_between(
+        &self,
+        source: &Block,
+        sink: &Block,
+    ) -> Result<Option<(f64, Vec<Block>)>, petgraph::algo::NegativeCycle> {
+        // change the weights of the edges to negative values to find the longest path
+        let mut graph = self.graph.clone();
+        for edge in graph.edge_weights_mut() {
+            *edge = -*edge;
+        }
+
+        let paths = bellman_ford(&graph, self.node_index_map[&source.leader])?;
+        let sink_index = self.node_index_map[&sink.leader];
+        let distance = paths.distances[sink_index.index()];
+        if !distance.is_finite() {
+            return Ok(None);
+        }
 
-        Ok(min_path_latency * -1.0)
+        let mut trace = Vec::new();
+        let mut current = Some(sink_index);
+        while let Some(node_index) = current {
+            trace.push(self.graph[node_index].clone());
+            current = paths.predecessors[node_index.index()];
+        }
+        trace.reverse();
+
+        Ok(Some((-distance, trace)))
+    }
+
+    /// Returns whether `target` is reachable from `source`.
+    pub fn has_path(&self, source: &Block, target: &Block) -> bool {
+        has_path_connecting(
+            &self.graph,
+            self.node_index_map[&source.leader],
+            self.node_index_map[&target.leader],
+            None,
+        )
     }
 
+    pub fn longest_path_trace(
+        &self,
+        source: &Block,
+    ) -> Result<Vec<Block>, petgraph::algo::NegativeCycle> {
+        // change the weights of the edges to negative values to find the longest path
+        let mut graph = self.graph.clone();
+        for edge in graph.edge_weights_mut() {
+            *edge = -*edge;
+        }
+
+        let paths = bellman_ford(&graph, self.node_index_map[&source.leader])?;
+
+        // the path ends in the farthest exit block
+        let mut current = self.farthest_exit(&paths.distances);
+
+        let mut trace = Vec::new();
+        while let Some(node_index) = current {
+            trace.push(self.graph.node_weight(node_index).unwrap().clone());
+            current = paths.predecessors[node_index.index()];
+        }
+        trace.reverse();
+
+        Ok(trace)
+    }
+
+    /// Returns the latency of `max_cycles` iterations of the acyclic body of a cycle, from
+    /// `source` (its entry block, of latency `entry_node_latency`) along the longest path, plus
+    /// the final pass from `source` to `exit`, the block the cycle is left from: the longest
+    /// path without its tail from `exit` to an exit block of the body.
     pub fn reconstruct_longest_path(
         &self,
         source: &Block,
-        exit: &Block,
-        entry_node_latency: f32,
+        exit: Option<&Block>, // None if the cycle never exits
+        entry_node_latency: f64,
         max_cycles: u32,
-    ) -> Result<f32, petgraph::algo::NegativeCycle> {
+    ) -> Result<f64, petgraph::algo::NegativeCycle> {
         match self.longest_path(source) {
             Ok(path) => {
                 let cycle_path = path + entry_node_latency;
-                let directed_path = cycle_path - self.longest_path(exit).unwrap();
-                let total_cyle_path = cycle_path * max_cycles as f32 + directed_path;
+                let directed_path = match exit {
+                    Some(exit) => cycle_path - self.longest_path(exit)?,
+                    None => 0.0,
+                };
+                let total_cyle_path = cycle_path * max_cycles as f64 + directed_path;
 
                 Ok(total_cyle_path)
             }
@@ -172,9 +602,127 @@ impl MappedGraph {
         digraph.to_string()
     }
 
+    /// Returns the blocks with an edge to themselves.
+    pub fn self_loop_nodes(&self) -> Vec<Block> {
+        self.graph
+            .edge_references()
+            .filter(|edge| edge.source() == edge.target())
+            .map(|edge| self.graph[edge.source()].clone())
+            .collect()
+    }
+
+    pub fn stats(&self) -> GraphStats {
+        let sccs = tarjan_scc(&self.graph);
+
+        GraphStats {
+            nodes: self.graph.node_count(),
+            edges: self.graph.edge_count(),
+            sccs: sccs.iter().filter(|scc| scc.len() > 1).count(),
+            self_loops: self.self_loop_nodes().len(),
+            max_scc_size: sccs.iter().map(|scc| scc.len()).max().unwrap_or(0),
+        }
+    }
+
+    /// Returns the natural loops of the graph, as header -> leaders of the blocks of the loop.
+    /// The header dominates the source of a back edge jumping to it, and the loop is made of
+    /// the blocks reaching that source without passing through the header. The dominators are
+    /// computed from each block without incoming edges and from the blocks led by `entries`
+    /// (e.g. the entry of the analysis, which can be the header of a loop), so an irreducible
+    /// cycle, which can be entered from more than one block, is not covered by a natural loop.
+    pub fn natural_loops(&self, entries: &[u64]) -> BTreeMap<u64, BTreeSet<u64>> {
+        let mut natural_loops = BTreeMap::<u64, BTreeSet<u64>>::new();
+
+        let roots = self.graph.node_indices().filter(|node| {
+            entries.contains(&self.graph[*node].leader)
+                || self
+                    .graph
+                    .neighbors_directed(*node, Direction::Incoming)
+                    .next()
+                    .is_none()
+        });
+        for root in roots {
+            let dominators = simple_fast(&self.graph, root);
+
+            for edge in self.graph.edge_references() {
+                let (source, header) = (edge.source(), edge.target());
+                let is_back_edge =
+                    dominators
+                        .dominators(source)
+                        .is_some_and(|mut source_dominators| {
+                            source_dominators.any(|dominator| dominator == header)
+                        });
+                if !is_back_edge {
+                    continue;
+                }
+
+                let body = natural_loops.entry(self.graph[header].leader).or_default();
+                body.insert(self.graph[header].leader);
+                let mut stack = vec![source];
+                while let Some(node) = stack.pop() {
+                    if body.insert(self.graph[node].leader) {
+                        stack.extend(self.graph.neighbors_directed(node, Direction::Incoming));
+                    }
+                }
+            }
+        }
+
+        natural_loops
+    }
+
+    /// Serializes the graph as JSON, with the blocks (including their instructions) as nodes
+    /// and the edges identified by the leaders of their endpoints.
+    pub fn to_json(&self) -> String {
+        let nodes = self
+            .graph
+            .node_weights()
+            .map(JsonBlock::from)
+            .collect::<Vec<JsonBlock>>();
+        let edges = self
+            .graph
+            .edge_references()
+            .map(|edge| JsonEdge {
+                source: self.graph[edge.source()].leader,
+                target: self.graph[edge.target()].leader,
+                weight: *edge.weight(),
+            })
+            .collect::<Vec<JsonEdge>>();
+
+        serde_json::to_string_pretty(&JsonGraph { nodes, edges })
+            .expect("Unable to serialize the graph")
+    }
+
+    /// Serializes the graph as GraphML (e.g. for yEd or Gephi), with the leader, function,
+    /// latency and exit jump kind of the blocks and the weights of the edges.
+    pub fn to_graphml(&self) -> String {
+        let nodes = self
+            .graph
+            .node_weights()
+            .map(GraphmlNode::from)
+            .collect::<Vec<GraphmlNode>>();
+        let edges = self
+            .graph
+            .edge_references()
+            .map(|edge| {
+                (
+                    self.graph[edge.source()].leader,
+                    self.graph[edge.target()].leader,
+                    *edge.weight(),
+                )
+            })
+            .collect::<Vec<(u64, u64, f64)>>();
+
+        graphml(&nodes, &edges)
+    }
+
     pub fn condense_cycles(&mut self) -> MappedCondensedGraph {
         let condensed_graph = condensation(self.graph.clone().into(), true);
-        let stable_condensed_graph: StableGraph<Vec<Block>, f32> = condensed_graph.into();
+        let mut stable_condensed_graph: StableGraph<Vec<Block>, f64> = condensed_graph.into();
+
+        // the first block of a node is its lowest address block, independently of the order
+        // chosen by the condensation, to keep the entry and exit blocks of the cycles stable
+        for blocks in stable_condensed_graph.node_weights_mut() {
+            blocks.sort_by_key(|block| block.leader);
+        }
 
         let mut node_index_map = HashMap::new();
         let mut edge_index_map = HashMap::new();
@@ -206,7 +754,7 @@ impl MappedGraph {
 
 #[derive(Debug, Clone)]
 pub struct MappedCondensedGraph {
-    pub graph: StableGraph<Vec<Block>, f32>,
+    pub graph: StableGraph<Vec<Block>, f64>,
     pub node_index_map: HashMap<u64, NodeIndex<u32>>,
     pub edge_index_map: HashMap<(u64, u64), EdgeIndex<u32>>,
 }
@@ -224,6 +772,32 @@ impl MappedCondensedGraph {
         condensed_nodes
     }
 
+    /// Returns the blocks of the node containing the block of `leader`, i.e. the strongly
+    /// connected component it has been condensed into, or None if it is not in the graph.
+    pub fn scc_of(&self, leader: u64) -> Option<&[Block]> {
+        if let Some(node_index) = self.node_index_map.get(&leader) {
+            return Some(&self.graph[*node_index]);
+        }
+
+        self.graph
+            .node_indices()
+            .map(|node_index| &self.graph[node_index])
+            .find(|blocks| blocks.iter().any(|block| block.leader == leader))
+            .map(Vec::as_slice)
+    }
+
+    /// Maps the leader of each block to the leader of the node containing it, i.e. the first
+    /// (lowest address) block of its strongly connected component.
+    pub fn scc_membership(&self) -> BTreeMap<u64, u64> {
+        self.graph
+            .node_indices()
+            .flat_map(|node_index| {
+                let blocks = &self.graph[node_index];
+                blocks.iter().map(|block| (block.leader, blocks[0].leader))
+            })
+            .collect()
+    }
+
     pub fn add_node(&mut self, blocks: Vec<Block>) {
         if let hash_map::Entry::Vacant(e) = self.node_index_map.entry(blocks[0].leader) {
             let node_index = self.graph.add_node(blocks.clone());
@@ -249,19 +823,27 @@ impl MappedCondensedGraph {
         nodes
     }
 
-    pub fn add_edge(&mut self, source: Vec<Block>, target: Vec<Block>, weight: f32) {
+    /// Adds an edge from `source` to `target`, adding also the nodes if needed. If the edge
+    /// already exists, it keeps the highest weight, i.e. the worst case.
+    pub fn add_edge(&mut self, source: Vec<Block>, target: Vec<Block>, weight: f64) {
         self.add_node(source.clone());
         self.add_node(target.clone());
 
-        if let hash_map::Entry::Vacant(e) = self
+        match self
             .edge_index_map
             .entry((source[0].leader, target[0].leader))
         {
-            let source_index = self.node_index_map[&source[0].leader];
-            let target_index = self.node_index_map[&target[0].leader];
+            hash_map::Entry::Vacant(e) => {
+                let source_index = self.node_index_map[&source[0].leader];
+                let target_index = self.node_index_map[&target[0].leader];
 
-            let edge_index = self.graph.add_edge(source_index, target_index, weight);
-            e.insert(edge_index);
+                let edge_index = self.graph.add_edge(source_index, target_index, weight);
+                e.insert(edge_index);
+            }
+            hash_map::Entry::Occupied(e) => {
+                let edge_weight = &mut self.graph[*e.get()];
+                *edge_weight = edge_weight.max(weight);
+            }
         }
     }
 
@@ -276,13 +858,13 @@ impl MappedCondensedGraph {
         }
     }
 
-    pub fn update_edge(&mut self, a: &[Block], b: &[Block], weight: f32) {
+    pub fn update_edge(&mut self, a: &[Block], b: &[Block], weight: f64) {
         let source_index = self.node_index_map[&a[0].leader];
         let target_index = self.node_index_map[&b[0].leader];
         self.graph.update_edge(source_index, target_index, weight);
     }
 
-    pub fn get_edges(&self) -> Vec<(Vec<Block>, Vec<Block>, f32)> {
+    pub fn get_edges(&self) -> Vec<(Vec<Block>, Vec<Block>, f64)> {
         let mut edges = Vec::new();
 
         for edge_index in self.graph.edge_indices() {
@@ -303,7 +885,7 @@ impl MappedCondensedGraph {
         &self,
         node: &[Block],
         direction: Direction,
-    ) -> Vec<(Vec<Block>, Vec<Block>, f32)> {
+    ) -> Vec<(Vec<Block>, Vec<Block>, f64)> {
         let node_index = self.node_index_map[&node[0].leader];
         let edges = self.graph.edges_directed(node_index, direction);
 
@@ -333,7 +915,7 @@ impl MappedCondensedGraph {
         blocks
     }
 
-    pub fn shortest_path(&self, source: &[Block]) -> f32 {
+    pub fn shortest_path(&self, source: &[Block]) -> f64 {
         let paths = bellman_ford(&self.graph, self.node_index_map[&source[0].leader]).unwrap();
 
         let max_path_latency = paths
@@ -347,38 +929,120 @@ impl MappedCondensedGraph {
         max_path_latency
     }
 
-    pub fn longest_path(&self, source: &[Block]) -> Result<f32, petgraph::algo::NegativeCycle> {
-        // change the weights of the edges to negative values to find the longest path
-        let mut graph = self.graph.clone();
-        for edge in graph.edge_weights_mut() {
-            *edge = -*edge;
-        }
+    pub fn longest_path(&self, source: &[Block]) -> Result<f64, petgraph::algo::NegativeCycle> {
+        let (distances, _) = self.longest_distances(source)?;
 
-        let paths = bellman_ford(&graph, self.node_index_map[&source[0].leader])?;
+        let max_path_latency = distances
+            .values()
+            .copied()
+            .fold(0.0, |max: f64, distance| max.max(distance));
 
-        let min_path_latency = paths
-            .distances
+        Ok(max_path_latency)
+    }
+
+    /// Returns the latency of the longest path from `source` to `sink`, or None if `sink` is
+    /// not reachable. Unlike `longest_path`, which measures the path to the farthest reachable
+    /// node, the path must end in the given node (e.g. an exit block of the program).
+    pub fn longest_path_to(
+        &self,
+        source: &[Block],
+        sink: &[Block],
+    ) -> Result<Option<f64>, petgraph::algo::NegativeCycle> {
+        let (distances, _) = self.longest_distances(source)?;
+
+        Ok(distances
+            .get(&self.node_index_map[&sink[0].leader])
+            .copied())
+    }
+
+    /// Returns the latency of the longest path from `source` to one of the exit nodes (the nodes
+    /// without successors), 0 if none is reachable. The distances from `source` are computed
+    /// once for all the exit nodes.
+    pub fn longest_path_to_exits(
+        &self,
+        source: &[Block],
+    ) -> Result<f64, petgraph::algo::NegativeCycle> {
+        let (distances, _) = self.longest_distances(source)?;
+
+        Ok(distances
             .iter()
-            .filter(|x| x.is_finite())
-            .min_by(|a, b| a.partial_cmp(b).unwrap())
-            .unwrap()
-            .to_owned();
+            .filter(|(node_index, _)| {
+                self.graph
+                    .edges_directed(**node_index, Direction::Outgoing)
+                    .next()
+                    .is_none()
+            })
+            .fold(0.0, |max: f64, (_, distance)| max.max(*distance)))
+    }
+
+    /// Returns the nodes along the longest path starting from `source`, represented by their
+    /// first block.
+    pub fn longest_path_trace(
+        &self,
+        source: &[Block],
+    ) -> Result<Vec<Block>, petgraph::algo::NegativeCycle> {
+        let (distances, predecessors) = self.longest_distances(source)?;
+
+        let mut trace = Vec::new();
+        let mut current = distances
+            .iter()
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+            .map(|(node_index, _)| *node_index);
 
-        Ok(min_path_latency * -1.0)
+        while let Some(node_index) = current {
+            trace.push(self.graph.node_weight(node_index).unwrap()[0].clone());
+            current = predecessors.get(&node_index).copied();
+        }
+        trace.reverse();
+
+        Ok(trace)
+    }
+
+    // distances and predecessors of the longest paths from the source node
+    fn longest_distances(
+        &self,
+        source: &[Block],
+    ) -> Result<LongestDistances, petgraph::algo::NegativeCycle> {
+        // the condensed graph is a DAG, so the edges can be relaxed in topological order in
+        // O(V + E), instead of running bellman_ford on the negated weights
+        let topological_order =
+            toposort(&self.graph, None).map_err(|_| petgraph::algo::NegativeCycle(()))?;
+
+        let mut distances = HashMap::<NodeIndex<u32>, f64>::new();
+        let mut predecessors = HashMap::<NodeIndex<u32>, NodeIndex<u32>>::new();
+        distances.insert(self.node_index_map[&source[0].leader], 0.0);
+
+        for node_index in topological_order {
+            if let Some(&distance) = distances.get(&node_index) {
+                for edge in self.graph.edges_directed(node_index, Direction::Outgoing) {
+                    let target_distance =
+                        distances.entry(edge.target()).or_insert(f64::NEG_INFINITY);
+                    if distance + *edge.weight() > *target_distance {
+                        *target_distance = distance + *edge.weight();
+                        predecessors.insert(edge.target(), node_index);
+                    }
+                }
+            }
+        }
+
+        Ok((distances, predecessors))
     }
 
     pub fn reconstruct_longest_path(
         &mut self,
         source: &[Block],
-        exit: &[Block],
-        entry_node_latency: f32,
+        exit: Option<&[Block]>, // None if the cycle never exits
+        entry_node_latency: f64,
         max_cycles: u32,
-    ) -> Result<f32, petgraph::algo::NegativeCycle> {
+    ) -> Result<f64, petgraph::algo::NegativeCycle> {
         match self.longest_path(source) {
             Ok(path) => {
                 let cycle_path = path + entry_node_latency;
-                let directed_path = cycle_path - self.longest_path(exit)?;
-                let total_cyle_path = cycle_path * max_cycles as f32 + directed_path;
+                let directed_path = match exit {
+                    Some(exit) => cycle_path - self.longest_path(exit)?,
+                    None => 0.0,
+                };
+                let total_cyle_path = cycle_path * max_cycles as f64 + directed_path;
                 Ok(total_cyle_path)
             }
             Err(e) => Err(e),
@@ -389,4 +1053,73 @@ impl MappedCondensedGraph {
         let digraph = Dot::with_config(&self.graph, &[]);
         format!("{digraph:?}")
     }
+
+    /// Serializes the condensed graph as JSON: each node lists the blocks condensed into it
+    /// and the edges are identified by the leaders of the first blocks of their endpoints.
+    pub fn to_json(&self) -> String {
+        let nodes = self
+            .graph
+            .node_weights()
+            .map(|blocks| JsonCondensedNode {
+                leader: blocks[0].leader,
+                blocks: blocks.iter().map(JsonBlock::from).collect(),
+            })
+            .collect::<Vec<JsonCondensedNode>>();
+        let edges = self
+            .graph
+            .edge_references()
+            .map(|edge| JsonEdge {
+                source: self.graph[edge.source()][0].leader,
+                target: self.graph[edge.target()][0].leader,
+                weight: *edge.weight(),
+            })
+            .collect::<Vec<JsonEdge>>();
+
+        serde_json::to_string_pretty(&JsonGraph { nodes, edges })
+            .expect("Unable to serialize the graph")
+    }
+
+    /// Serializes the condensed graph as GraphML, like `MappedGraph::to_graphml`: each node is
+    /// identified by its first block and has the latency of all its blocks, whose leaders are
+    /// listed in the `blocks` attribute.
+    pub fn to_graphml(&self) -> String {
+        let nodes = self
+            .graph
+            .node_weights()
+            .map(|blocks| match blocks.as_slice() {
+                [block] => GraphmlNode::from(block),
+                _ => GraphmlNode {
+                    leader: blocks[0].leader,
+                    function: &blocks[0].function,
+                    latency: blocks.iter().map(|block| block.get_latency() as u64).sum(),
+                    exit_jump: "Cycle",
+                    blocks: blocks.iter().map(|block| block.leader).collect(),
+                },
+            })
+            .collect::<Vec<GraphmlNode>>();
+        let edges = self
+            .graph
+            .edge_references()
+            .map(|edge| {
+                (
+                    self.graph[edge.source()][0].leader,
+                    self.graph[edge.target()][0].leader,
+                    *edge.weight(),
+                )
+            })
+            .collect::<Vec<(u64, u64, f64)>>();
+
+        graphml(&nodes, &edges)
+    }
+}
+
+/// Converts a latency computed on the weights of a graph to clock cycles, rounded up for the
+/// fractional costs, failing if it is too large to be exact, since a rounded latency could
+/// underestimate the WCET (`leader` is the block the latency is computed from).
+pub fn weight_to_latency(weight: f64, leader: u64) -> Result<u64, WcetError> {
+    if weight >= MAX_EXACT_WEIGHT {
+        return Err(WcetError::LatencyOverflow(leader));
+    }
+
+    Ok(weight.ceil() as u64)
 }
@@ -0,0 +1,37 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::bounds::parse_address;
+
+/// Possible targets of the indirect jumps (e.g. switch jump tables), keyed by the address of
+/// the jump instruction. The targets are loaded from a TOML file, e.g.:
+///
+/// ```toml
+/// 0x1bb = [0x1c2, 0x1d4, 0x1e0]
+/// ```
+///
+/// A resolved indirect jump is treated as a multi-way conditional jump to all its targets.
+#[derive(Debug, Clone, Default)]
+pub struct IndirectTargets {
+    pub targets: HashMap<u64, Vec<u64>>, // jump_address -> targets
+}
+
+impl IndirectTargets {
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(path)?;
+        let file: HashMap<String, Vec<u64>> = toml::from_str(&content)?;
+
+        let mut indirect_targets = IndirectTargets::default();
+        for (address, targets) in file {
+            indirect_targets
+                .targets
+                .insert(parse_address(&address)?, targets);
+        }
+
+        Ok(indirect_targets)
+    }
+
+    pub fn get(&self, address: u64) -> Option<&Vec<u64>> {
+        self.targets.get(&address)
+    }
+}
@@ -1,73 +1,137 @@
-use capstone::Insn;
+use capstone::{Arch, Insn};
+use serde::Serialize;
 
-use crate::CURRENT_ARCH;
+use crate::arch::ArchMode;
+use crate::config::Syntax;
+use crate::latency::LatencyTable;
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize)]
 pub struct Instruction {
     pub address: u64,
     pub mnemonic: String,
-    pub operands: (Option<String>, Option<String>),
+    pub operands: Vec<String>,
+    pub size: u16,    // bytes
     pub latency: u32, // clock cycles
 }
 
-impl<'a> From<&'a Insn<'a>> for Instruction {
-    fn from(insn: &'a Insn<'a>) -> Self {
+impl Instruction {
+    /// Builds the instruction from `insn`, whose latency is resolved from its mnemonic, its
+    /// operands and the names of its capstone `groups`. The operands of the AT&T `syntax`
+    /// are reversed, so that they are always listed destination first as in the Intel one.
+    pub fn from_insn(
+        insn: &Insn,
+        arch_mode: &ArchMode,
+        syntax: Syntax,
+        groups: &[String],
+        latency_table: &LatencyTable,
+    ) -> Self {
         let mnemonic = insn.mnemonic().unwrap().to_string();
 
-        let operands = match insn.op_str() {
-            Some(operands) => {
-                let operands = operands.split(',').map(|o| o.trim()).collect::<Vec<&str>>();
-                if operands.len() == 1 {
-                    (Some(operands[0]), None)
-                } else {
-                    (Some(operands[0]), Some(operands[1]))
-                }
-            }
-            None => (None, None),
-        };
-
-        let arch_mode = CURRENT_ARCH.with(|arch| arch.borrow().clone());
-
-        let arch_mnemonic_str = if let Some(arch_mode) = arch_mode {
-            format!(
-                "{}_{}",
-                arch_mode.arch.to_string().to_uppercase(),
-                mnemonic.to_uppercase()
-            )
-        } else {
-            panic!("No architecture set")
-        };
-
-        let latency = match std::env::var(arch_mnemonic_str) {
-            Ok(latency) => latency.parse::<u32>().unwrap(),
-            _ => 1,
-        };
+        let mut operands = split_operands(insn.op_str().unwrap_or_default());
+        if arch_mode.arch == Arch::X86 && syntax == Syntax::Att {
+            operands.reverse();
+        }
+        // a repeated string instruction has the latency of a single execution
+        let latency = latency_table.get_latency(
+            arch_mode.arch,
+            syntax,
+            rep_instruction(&mnemonic).unwrap_or(&mnemonic),
+            &operands,
+            groups,
+        );
 
         Instruction {
             address: insn.address(),
             mnemonic,
-            operands: (
-                operands.0.map(|s| s.to_string()),
-                operands.1.map(|s| s.to_string()),
-            ),
+            operands,
+            size: insn.bytes().len() as u16,
             latency,
         }
     }
 }
 
+/// Returns the x86 `mnemonic` of the AT&T syntax without the suffix with the size of its
+/// operands (e.g. `mov` of `movl`), or None if it has no such suffix. The suffix is not told
+/// apart from the last letter of the mnemonic (e.g. `shl`), so the result is only a fallback.
+pub fn without_size_suffix(mnemonic: &str) -> Option<&str> {
+    mnemonic
+        .strip_suffix(['b', 'w', 'l', 'q'])
+        .filter(|base| !base.is_empty())
+}
+
+/// Returns the string instruction repeated by a `rep`, `repe` or `repne` prefix of x86 (e.g.
+/// `movsb` of `rep movsb`), which executes it up to `rcx` times, or None without the prefix.
+pub fn rep_instruction(mnemonic: &str) -> Option<&str> {
+    let (prefix, instruction) = mnemonic.split_once(' ')?;
+    matches!(prefix, "rep" | "repe" | "repz" | "repne" | "repnz").then_some(instruction)
+}
+
+impl Instruction {
+    /// Returns the register written by the instruction, taken as its first operand if it is a
+    /// register, except for the comparisons, the pushes and the stores, which only read it.
+    /// The `$` and `%` prefixes of the register names are removed.
+    pub fn written_register(&self) -> Option<&str> {
+        let mnemonic = self.mnemonic.as_str();
+        let reads_only = ["cmp", "cmn", "test", "tst", "teq", "push", "st"]
+            .iter()
+            .any(|prefix| mnemonic.starts_with(prefix))
+            || matches!(mnemonic, "sb" | "sh" | "sw" | "sd" | "swc1" | "sdc1");
+        if reads_only {
+            return None;
+        }
+
+        let register = self.operands.first()?.trim_start_matches(['$', '%']);
+        (register.starts_with(|c: char| c.is_ascii_alphabetic())
+            && register.chars().all(|c| c.is_ascii_alphanumeric()))
+        .then_some(register)
+    }
+
+    /// Returns whether `register` appears in the operands of the instruction, also as base or
+    /// index of a memory operand. A written operand is counted as well, since most instructions
+    /// writing a register also read it (e.g. `add eax, ebx`).
+    pub fn uses_register(&self, register: &str) -> bool {
+        self.operands.iter().any(|operand| {
+            operand
+                .split(|c: char| !c.is_ascii_alphanumeric())
+                .any(|name| name == register)
+        })
+    }
+}
+
 impl std::fmt::Display for Instruction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let operands_str = match &self.operands {
-            (Some(op1), Some(op2)) => format!("{op1}, {op2}"),
-            (Some(op1), None) => op1.to_string(),
-            _ => "".to_string(),
-        };
         write!(
             f,
             "0x{:x} {} {}",
             self.address,
             self.mnemonic,
-            operands_str.trim()
+            self.operands.join(", ")
         )
     }
 }
+
+/// Splits the operands printed by capstone on the commas that are not enclosed in brackets,
+/// parentheses or braces, e.g. the AT&T memory operands `8(%rax,%rbx,4)` and the ARM register
+/// lists `{r4, r5, lr}` are kept whole.
+pub fn split_operands(op_str: &str) -> Vec<String> {
+    let mut operands = Vec::new();
+    let mut depth = 0; // nesting of the brackets
+    let mut start = 0;
+    for (index, c) in op_str.char_indices() {
+        match c {
+            '[' | '(' | '{' => depth += 1,
+            ']' | ')' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                operands.push(op_str[start..index].trim().to_string());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    let last = op_str[start..].trim();
+    if !last.is_empty() || !operands.is_empty() {
+        operands.push(last.to_string());
+    }
+
+    operands
+}
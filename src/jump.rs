@@ -1,12 +1,27 @@
+use capstone::arch::arm::{ArmCC, ArmOperandType};
+use capstone::arch::arm64::{Arm64CC, Arm64OperandType};
+use capstone::arch::mips::MipsOperand;
+use capstone::arch::ppc::PpcOperand;
+use capstone::arch::riscv::RiscVOperand;
+use capstone::arch::sparc::SparcOperand;
+use capstone::arch::x86::X86OperandType;
+use capstone::arch::{ArchDetail, ArchOperand};
 use capstone::{Arch, Insn, InsnDetail, InsnGroupType};
+use serde::Serialize;
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+use crate::config::Syntax;
+use crate::error::JumpError;
+use crate::indirect::IndirectTargets;
+use crate::instruction::without_size_suffix;
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize)]
 pub enum ExitJump {
     ConditionalRelative { taken: u64, not_taken: u64 },
     UnconditionalRelative(u64),
     ConditionalAbsolute { taken: u64, not_taken: u64 },
     UnconditionalAbsolute(u64),
     Indirect,
+    IndirectResolved(Vec<u64>), // targets provided by the user
     Ret(u64),
     Call(u64, u64), // target, return address
     Next(u64),
@@ -34,6 +49,17 @@ impl std::fmt::Display for ExitJump {
                 write!(f, "UnconditionalAbsolute {{ target: 0x{target:x} }}")
             }
             ExitJump::Indirect => write!(f, "Indirect"),
+            ExitJump::IndirectResolved(targets) => {
+                let targets = targets
+                    .iter()
+                    .map(|target| format!("0x{target:x}"))
+                    .collect::<Vec<String>>();
+                write!(
+                    f,
+                    "IndirectResolved {{ targets: [{}] }}",
+                    targets.join(", ")
+                )
+            }
             ExitJump::Ret(targets) => {
                 if *targets != 0 {
                     write!(f, "Ret {{ targets: 0x{targets:x} }}")
@@ -47,52 +73,83 @@ impl std::fmt::Display for ExitJump {
     }
 }
 
+impl ExitJump {
+    /// Returns the name of the variant, without its targets.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ExitJump::ConditionalRelative { .. } => "ConditionalRelative",
+            ExitJump::UnconditionalRelative(_) => "UnconditionalRelative",
+            ExitJump::ConditionalAbsolute { .. } => "ConditionalAbsolute",
+            ExitJump::UnconditionalAbsolute(_) => "UnconditionalAbsolute",
+            ExitJump::Indirect => "Indirect",
+            ExitJump::IndirectResolved(_) => "IndirectResolved",
+            ExitJump::Ret(_) => "Ret",
+            ExitJump::Call(_, _) => "Call",
+            ExitJump::Next(_) => "Next",
+        }
+    }
+}
+
+/// Returns the exit jump of `insn`, or None if it is not a jump. `next_address` is the address
+/// of the following instruction, i.e. the return address of a call and the not taken target of
+/// a conditional jump. The size suffixes of the x86 mnemonics are only dropped in the AT&T
+/// `syntax`.
+///
+/// Fails if the jumps of `arch` are not recognized or if the instruction has no mnemonic.
 pub fn get_exit_jump(
     insn: &Insn,
-    next_insn: &Insn,
+    next_address: u64,
     insn_detail: &InsnDetail,
     arch: Arch,
-) -> Option<ExitJump> {
-    let insn_group_ids = insn_detail.groups();
-
-    // check if the instruction is a jump and check its JumpType
-    let mut is_jump = false;
-    let mut is_relative = false;
-    let mut is_call = false;
-    let mut is_ret = false;
-
-    for id in insn_group_ids {
-        let id = id.0 as u32;
-
-        if id == InsnGroupType::CS_GRP_INT
-            || id == InsnGroupType::CS_GRP_CALL
-            || id == InsnGroupType::CS_GRP_JUMP
-            || id == InsnGroupType::CS_GRP_RET
-            || id == InsnGroupType::CS_GRP_IRET
-        {
-            is_jump = true;
-
-            if id == InsnGroupType::CS_GRP_CALL {
-                is_call = true;
-            } else if id == InsnGroupType::CS_GRP_RET {
-                is_ret = true;
-            }
-        } else if id == InsnGroupType::CS_GRP_BRANCH_RELATIVE {
-            is_relative = true;
-        }
-    }
+    syntax: Syntax,
+    indirect_targets: &IndirectTargets,
+) -> Result<Option<ExitJump>, JumpError> {
+    let op = insn
+        .mnemonic()
+        .ok_or(JumpError::MissingMnemonic(insn.address()))?;
+    let JumpKind {
+        is_jump,
+        is_relative,
+        is_call,
+        is_ret,
+    } = jump_kind(op, insn, insn_detail, arch);
 
-    if is_jump {
-        let op = insn.mnemonic().unwrap();
+    let exit_jump = if is_jump {
         let is_unconditional = match arch {
-            Arch::ARM => matches!(op, "b" | "bl" | "br" | "bx" | "blr" | "bcc" | "ret"),
-            Arch::ARM64 => matches!(op, "b" | "bl" | "br" | "blr" | "bcc" | "ret"),
-            Arch::MIPS => matches!(op, "j" | "jal" | "jr" | "jalr"),
-            Arch::X86 => matches!(op, "jmp" | "call" | "ret"),
+            // the condition code of ARM is a suffix of any mnemonic (e.g. beq, bleq, bxne, b.eq)
+            Arch::ARM | Arch::ARM64 => !is_arm_conditional(op, insn_detail),
+            Arch::MIPS => matches!(op, "b" | "bal" | "j" | "jal" | "jr" | "jalr"),
+            // the AT&T mnemonics can have a size suffix (e.g. jmpq, callq, retq)
+            Arch::X86 => [
+                Some(op),
+                without_size_suffix(op).filter(|_| syntax == Syntax::Att),
+            ]
+            .into_iter()
+            .flatten()
+            .any(|op| {
+                matches!(
+                    op,
+                    "jmp" | "call" | "ret" | "ljmp" | "lcall" | "lret" | "retf"
+                )
+            }),
             Arch::PPC => matches!(op, "b" | "bl" | "blr" | "bctr" | "bctrl"),
+            // the annul (,a) and prediction (,pt or ,pn) suffixes don't change the condition
             Arch::SPARC => matches!(
+                sparc_base_mnemonic(op),
+                "b" | "ba"
+                    | "bpa"
+                    | "fba"
+                    | "fbpa"
+                    | "call"
+                    | "ret"
+                    | "retl"
+                    | "rett"
+                    | "jmp"
+                    | "jmpl"
+            ),
+            Arch::SYSZ => matches!(
                 op,
-                "bpa" | "fbpa" | "call" | "ret" | "retl" | "rett" | "jmp" | "jmpl"
+                "j" | "jg" | "b" | "br" | "bal" | "balr" | "bas" | "basr" | "bras" | "brasl"
             ),
             Arch::RISCV => matches!(
                 op,
@@ -110,40 +167,297 @@ pub fn get_exit_jump(
                     | "c.jr"
                     | "c.jalr"
             ),
-            _ => panic!("Unsupported architecture!"),
+            _ => return Err(JumpError::UnsupportedArch(arch)),
         };
 
-        let operands = insn.op_str().unwrap();
-        let operands = operands.split(',').collect::<Vec<&str>>();
-        let last_operand = operands.last().unwrap().trim();
-
-        if last_operand.contains("0x") {
-            let last_operand =
-                u64::from_str_radix(last_operand.split("0x").collect::<Vec<&str>>()[1], 16)
-                    .unwrap();
+        if is_ret {
+            Some(ExitJump::Ret(0)) // the correct value can't be determined here
+        } else if let Some(mut last_operand) = direct_target(insn, insn_detail, arch) {
+            // on ARM the low bit of the target only selects the Thumb mode
+            if arch == Arch::ARM {
+                last_operand &= !1;
+            }
 
             if is_call {
-                return Some(ExitJump::Call(last_operand, next_insn.address()));
+                return Ok(Some(ExitJump::Call(last_operand, next_address)));
             }
 
             match (is_relative, is_unconditional) {
                 (true, true) => Some(ExitJump::UnconditionalRelative(last_operand)),
                 (true, false) => Some(ExitJump::ConditionalRelative {
                     taken: last_operand,
-                    not_taken: next_insn.address(),
+                    not_taken: next_address,
                 }),
                 (false, true) => Some(ExitJump::UnconditionalAbsolute(last_operand)),
                 (false, false) => Some(ExitJump::ConditionalAbsolute {
                     taken: last_operand,
-                    not_taken: next_insn.address(),
+                    not_taken: next_address,
                 }),
             }
-        } else if is_ret {
-            Some(ExitJump::Ret(0)) // the correct value can't be determined here
+        } else if is_call {
+            // the target of an indirect call (e.g. `call rax` or `call qword ptr [rip + 0x10]`)
+            // is not resolved, since it would need a return edge
+            Some(ExitJump::Indirect)
+        } else if let Some(targets) = indirect_targets.get(insn.address()) {
+            Some(ExitJump::IndirectResolved(targets.clone()))
         } else {
             Some(ExitJump::Indirect)
         }
     } else {
         None
+    };
+
+    Ok(exit_jump)
+}
+
+// kind of control transfer of an instruction, from its capstone groups and its mnemonic
+struct JumpKind {
+    is_jump: bool, // any control transfer, calls and returns included
+    is_relative: bool,
+    is_call: bool,
+    is_ret: bool,
+}
+
+fn jump_kind(op: &str, insn: &Insn, insn_detail: &InsnDetail, arch: Arch) -> JumpKind {
+    let insn_group_ids = insn_detail.groups();
+
+    // check if the instruction is a jump and check its JumpType
+    let mut is_jump = false;
+    let mut is_relative = false;
+    let mut is_call = false;
+    let mut is_ret = false;
+
+    for id in insn_group_ids {
+        let id = id.0 as u32;
+
+        if id == InsnGroupType::CS_GRP_INT
+            || id == InsnGroupType::CS_GRP_CALL
+            || id == InsnGroupType::CS_GRP_JUMP
+            || id == InsnGroupType::CS_GRP_RET
+            || id == InsnGroupType::CS_GRP_IRET
+        {
+            is_jump = true;
+
+            if id == InsnGroupType::CS_GRP_CALL {
+                is_call = true;
+            } else if id == InsnGroupType::CS_GRP_RET {
+                is_ret = true;
+            }
+        } else if id == InsnGroupType::CS_GRP_BRANCH_RELATIVE {
+            is_relative = true;
+        }
+    }
+
+    // the compressed jumps of RISC-V are recognized by their mnemonic, since the groups can miss
+    if arch == Arch::RISCV
+        && matches!(
+            op,
+            "c.beqz" | "c.bnez" | "c.j" | "c.jal" | "c.jr" | "c.jalr"
+        )
+    {
+        is_jump = true;
+        is_relative |= matches!(op, "c.beqz" | "c.bnez" | "c.j" | "c.jal");
+        is_call |= matches!(op, "c.jal" | "c.jalr");
+        is_ret |= op == "c.jr" && insn.op_str() == Some("ra");
+    }
+
+    // SystemZ has no call and ret groups, and its calls have no group at all: a call saves the
+    // return address in a register (e.g. brasl %r14, target) and the return is a branch to that
+    // register (br %r14)
+    if arch == Arch::SYSZ {
+        is_call = matches!(op, "bal" | "balr" | "bas" | "basr" | "bras" | "brasl");
+        is_ret = is_jump && op == "br" && insn.op_str() == Some("%r14");
+        is_jump |= is_call;
+    }
+
+    // the groups of ARM, PowerPC and MIPS miss the returns through the link register (bx lr,
+    // pop {..., pc}, blr, jr $ra), the ones of PowerPC miss the calls (bl) and the relative
+    // calls of MIPS (bal) have no group at all
+    match arch {
+        Arch::ARM => match op {
+            "bx" if insn.op_str() == Some("lr") => {
+                is_jump = true;
+                is_ret = true;
+            }
+            "pop" | "pop.w" if insn.op_str().is_some_and(|regs| regs.contains("pc")) => {
+                is_jump = true;
+                is_ret = true;
+            }
+            _ => {}
+        },
+        Arch::PPC if is_jump => {
+            is_call |= matches!(op, "bl" | "bla" | "bctrl" | "blrl");
+            is_ret |= op == "blr";
+        }
+        Arch::MIPS => {
+            if matches!(op, "bal" | "bgezal" | "bltzal") {
+                is_jump = true;
+                is_relative = true;
+            }
+            is_call |= is_jump && matches!(op, "jal" | "jalr" | "bal" | "bgezal" | "bltzal");
+            is_ret |= is_jump && op == "jr" && insn.op_str() == Some("$ra");
+        }
+        _ => {}
+    }
+
+    // the groups of SPARC miss the calls, the returns and the indirect jumps, which are thus
+    // recognized by their mnemonic: `ret` and `retl` jump to the return address (%i7 + 8 or
+    // %o7 + 8), while `call` to a register saves the return address as the direct one; the
+    // branches never taken (bn, fbn) don't transfer control
+    if arch == Arch::SPARC {
+        match sparc_base_mnemonic(op) {
+            "call" => {
+                is_jump = true;
+                is_call = true;
+            }
+            "ret" | "retl" | "rett" => {
+                is_jump = true;
+                is_ret = true;
+            }
+            "jmp" | "jmpl" => is_jump = true,
+            "bn" | "bpn" | "fbn" | "fbpn" => is_jump = false,
+            _ => {}
+        }
+    }
+
+    JumpKind {
+        is_jump,
+        is_relative,
+        is_call,
+        is_ret,
+    }
+}
+
+/// Returns the SPARC mnemonic `op` without its annul (,a) and prediction (,pt or ,pn)
+/// suffixes, e.g. `bne` for `bne,a,pt`.
+fn sparc_base_mnemonic(op: &str) -> &str {
+    op.split(',').next().unwrap_or(op)
+}
+
+/// Returns whether `insn` is a call, either direct or indirect (e.g. `call rax`).
+pub fn is_call(insn: &Insn, insn_detail: &InsnDetail, arch: Arch) -> bool {
+    insn.mnemonic()
+        .is_some_and(|op| jump_kind(op, insn, insn_detail, arch).is_call)
+}
+
+/// Returns whether the ARM or AArch64 jump `op` is executed only if its condition code holds
+/// (e.g. `beq`, `b.ne`), read from the details of capstone, or if it compares a register with
+/// zero (`cbz`, `cbnz`, `tbz`, `tbnz`), which has no condition code.
+fn is_arm_conditional(op: &str, insn_detail: &InsnDetail) -> bool {
+    if matches!(op, "cbz" | "cbnz" | "tbz" | "tbnz") {
+        return true;
+    }
+
+    match insn_detail.arch_detail() {
+        ArchDetail::ArmDetail(detail) => {
+            !matches!(detail.cc(), ArmCC::ARM_CC_AL | ArmCC::ARM_CC_INVALID)
+        }
+        ArchDetail::Arm64Detail(detail) => !matches!(
+            detail.cc(),
+            Arm64CC::ARM64_CC_AL | Arm64CC::ARM64_CC_NV | Arm64CC::ARM64_CC_INVALID
+        ),
+        _ => false,
+    }
+}
+
+/// Returns the number of instructions made conditional by `insn` if it is a Thumb IT
+/// instruction (e.g. 3 for `itte eq`), or 0 otherwise.
+pub fn it_block_length(insn: &Insn, arch: Arch) -> usize {
+    match insn.mnemonic() {
+        Some(op)
+            if arch == Arch::ARM
+                && op.len() <= 5
+                && op.starts_with("it")
+                && op[2..].chars().all(|c| c == 't' || c == 'e') =>
+        {
+            op.len() - 1
+        }
+        _ => 0,
+    }
+}
+
+/// Returns whether the jump `insn` has a delay slot, i.e. the following instruction is always
+/// executed before the jump takes effect, as for the jumps of MIPS (except the compact ones of
+/// MIPS32r6) and SPARC (except the traps and the unconditional branches annulling their slot,
+/// e.g. `ba,a`, whose following instruction is never executed).
+pub fn has_delay_slot(insn: &Insn, arch: Arch) -> bool {
+    let Some(op) = insn.mnemonic() else {
+        return false;
+    };
+
+    match arch {
+        Arch::MIPS => {
+            !(matches!(
+                op,
+                "bc" | "balc" | "jic" | "jialc" | "eret" | "deret" | "syscall" | "break"
+            ) || (op.starts_with('b') && op.ends_with("zc")))
+        }
+        Arch::SPARC => {
+            let annulled = op.split(',').skip(1).any(|suffix| suffix == "a");
+            !(op.starts_with('t')
+                || annulled
+                    && matches!(sparc_base_mnemonic(op), "b" | "ba" | "bpa" | "fba" | "fbpa"))
+        }
+        _ => false,
+    }
+}
+
+/// Returns the exit jump of an instruction inside an IT block, which falls through to
+/// `next_address` when its condition is false. A conditional return is dropped, so that
+/// the fall through path is the one followed.
+pub fn conditional_exit_jump(exit_jump: ExitJump, next_address: u64) -> Option<ExitJump> {
+    match exit_jump {
+        ExitJump::UnconditionalRelative(taken) => Some(ExitJump::ConditionalRelative {
+            taken,
+            not_taken: next_address,
+        }),
+        ExitJump::UnconditionalAbsolute(taken) => Some(ExitJump::ConditionalAbsolute {
+            taken,
+            not_taken: next_address,
+        }),
+        ExitJump::IndirectResolved(mut targets) => {
+            if !targets.contains(&next_address) {
+                targets.push(next_address);
+            }
+            Some(ExitJump::IndirectResolved(targets))
+        }
+        ExitJump::Ret(_) => None,
+        exit_jump => Some(exit_jump),
+    }
+}
+
+/// Returns the target of a direct jump or call, read from the immediate operand in the details
+/// of the instruction, or None if the target is in a register or in memory (e.g. `call rax` or
+/// `jmp qword ptr [rip + 0x10]`, whose displacement is not a target).
+fn direct_target(insn: &Insn, insn_detail: &InsnDetail, arch: Arch) -> Option<u64> {
+    // the operand details of SystemZ are not available, so its target is parsed from the text
+    if arch == Arch::SYSZ {
+        let last_operand = insn.op_str()?.split(',').next_back()?.trim();
+        return u64::from_str_radix(last_operand.strip_prefix("0x")?, 16).ok();
+    }
+
+    let operands = insn_detail.arch_detail().operands();
+
+    match operands.last()? {
+        ArchOperand::X86Operand(op) => match op.op_type {
+            X86OperandType::Imm(imm) => Some(imm as u64),
+            _ => None,
+        },
+        ArchOperand::ArmOperand(op) => match op.op_type {
+            ArmOperandType::Imm(imm) => Some(imm as u32 as u64),
+            _ => None,
+        },
+        ArchOperand::Arm64Operand(op) => match op.op_type {
+            Arm64OperandType::Imm(imm) => Some(imm as u64),
+            _ => None,
+        },
+        ArchOperand::MipsOperand(MipsOperand::Imm(imm)) => Some(*imm as u64),
+        ArchOperand::PpcOperand(PpcOperand::Imm(imm)) => Some(*imm as u64),
+        ArchOperand::SparcOperand(SparcOperand::Imm(imm)) => Some(*imm as u64),
+        // the immediate of the RISC-V jumps (compressed ones included) is the offset from the pc
+        ArchOperand::RiscVOperand(RiscVOperand::Imm(imm)) => {
+            Some(insn.address().wrapping_add(*imm as u64))
+        }
+        _ => None,
     }
 }
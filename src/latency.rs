@@ -0,0 +1,464 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use capstone::Arch;
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::bounds::env_number;
+use crate::config::Syntax;
+use crate::instruction::without_size_suffix;
+
+/// Latencies (in clock cycles) of the instructions, keyed by architecture and mnemonic.
+///
+/// The table is built by layering the `LatencyLayer`s of the files on top of the default or
+/// of a built-in table (see `for_cpu`), and can be loaded from a TOML or JSON file, e.g.:
+///
+/// ```toml
+/// default = 1
+/// branch_penalty = 2
+/// call_latency = 3
+/// ret_latency = 3
+///
+/// [groups]
+/// jump = 3
+///
+/// [X86]
+/// imul = 4
+/// mov = { base = 2, rules = [{ operands = ["reg", "mem"], latency = 5 }] }
+///
+/// [profiles.little.ARM64]
+/// mul = 4
+/// ```
+///
+/// The `profiles` are named layers with the same format (e.g. the cores of a heterogeneous
+/// SoC), which can be layered on top of the rest of the table with `profile`.
+///
+/// The latency of an instruction is resolved with the following precedence (below the
+/// `LatencyOverrides` of its address, if any):
+/// 1. the `ARCH_MNEMONIC` environment variable (e.g. `X86_MOV=2`), kept for backward compatibility
+/// 2. the first operand rule of the mnemonic matching the operands of the instruction
+/// 3. the latency (or the `base` latency) of the mnemonic in the section of the architecture
+/// 4. the highest latency of the capstone groups of the instruction (e.g. `jump`, `call`)
+/// 5. the `default` latency of the table (1 if not specified)
+///
+/// Architecture, mnemonic and group keys are case insensitive. The `branch_penalty` (0 if not specified)
+/// is added to the weight of the edges of the taken conditional branches, so the longest path
+/// pays it every time it follows a taken branch, also inside the cycles. In the same way, the
+/// `call_latency` and `ret_latency` (0 if not specified) are added to the edges of the calls
+/// and of the returns. The `hazard_penalty` (0 if not specified) is added to the instructions
+/// reading the register written by the previous instruction of their block.
+#[derive(Debug, Clone)]
+pub struct LatencyTable {
+    pub default: u32,
+    pub branch_penalty: u32, // clock cycles added to each taken conditional branch
+    pub call_latency: u32,   // clock cycles added to each call
+    pub ret_latency: u32,    // clock cycles added to each return
+    pub hazard_penalty: u32, // clock cycles added to an instruction reading the register written by the previous one
+    pub groups: HashMap<String, u32>, // capstone group name -> latency, for every architecture
+    pub profiles: HashMap<String, LatencyLayer>, // name -> layer of a core, on top of this table
+    pub archs: HashMap<String, HashMap<String, MnemonicLatency>>, // arch -> mnemonic -> latency
+}
+
+/// Latencies of a file or of a profile, layered on top of a `LatencyTable` with `merge`: the
+/// values which are not set are the ones of the table.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LatencyLayer {
+    pub default: Option<u32>,
+    pub branch_penalty: Option<u32>,
+    pub call_latency: Option<u32>,
+    pub ret_latency: Option<u32>,
+    pub hazard_penalty: Option<u32>,
+    #[serde(default)]
+    pub groups: HashMap<String, u32>, // capstone group name -> latency, for every architecture
+    #[serde(default)]
+    pub profiles: HashMap<String, LatencyLayer>, // name -> layer of a core, on top of this one
+    #[serde(flatten)]
+    pub archs: HashMap<String, HashMap<String, MnemonicLatency>>, // arch -> mnemonic -> latency
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum MnemonicLatency {
+    Fixed(u32),
+    Rules { base: u32, rules: Vec<OperandRule> },
+}
+
+/// Latency applied when the kinds of the operands of the instruction match `operands`
+/// positionally. Operands not listed in the rule are not checked.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OperandRule {
+    pub operands: Vec<OperandKind>,
+    pub latency: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperandKind {
+    #[serde(alias = "register")]
+    Reg,
+    #[serde(alias = "memory")]
+    Mem,
+    #[serde(alias = "immediate")]
+    Imm,
+}
+
+impl OperandKind {
+    pub fn of(operand: &str) -> Self {
+        let operand = operand.trim();
+        if operand.contains('[') || operand.contains('(') || operand.contains("ptr") {
+            OperandKind::Mem
+        } else if operand.starts_with('#')
+            || operand.starts_with('$')
+            || operand.starts_with('-')
+            || operand.starts_with(|c: char| c.is_ascii_digit())
+        {
+            OperandKind::Imm
+        } else {
+            OperandKind::Reg
+        }
+    }
+}
+
+impl OperandRule {
+    pub fn matches(&self, operands: &[String]) -> bool {
+        self.operands.len() <= operands.len()
+            && self
+                .operands
+                .iter()
+                .zip(operands)
+                .all(|(kind, operand)| OperandKind::of(operand) == *kind)
+    }
+}
+
+impl MnemonicLatency {
+    pub fn resolve(&self, operands: &[String]) -> u32 {
+        match self {
+            MnemonicLatency::Fixed(latency) => *latency,
+            MnemonicLatency::Rules { base, rules } => rules
+                .iter()
+                .find(|rule| rule.matches(operands))
+                .map(|rule| rule.latency)
+                .unwrap_or(*base),
+        }
+    }
+}
+
+impl Default for LatencyTable {
+    fn default() -> Self {
+        LatencyTable {
+            default: 1,
+            branch_penalty: 0,
+            call_latency: 0,
+            ret_latency: 0,
+            hazard_penalty: 0,
+            groups: HashMap::new(),
+            profiles: HashMap::new(),
+            archs: HashMap::new(),
+        }
+    }
+}
+
+/// Processor models with a built-in latency table, approximating the latencies of the common
+/// integer, memory and floating point instructions (loads take the L1 hit latency).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CpuModel {
+    /// Every instruction takes 1 clock cycle
+    Unit,
+    /// Skylake for x86-64 and Cortex-A72 for ARM64
+    Generic,
+    /// Intel Skylake (x86-64)
+    Skylake,
+    /// AMD Zen 2 (x86-64)
+    Zen2,
+    /// Arm Cortex-A53 (ARM64)
+    #[value(name = "cortex-a53")]
+    CortexA53,
+    /// Arm Cortex-A72 (ARM64)
+    #[value(name = "cortex-a72")]
+    CortexA72,
+}
+
+const SKYLAKE_X86: &str = r#"
+[X86]
+mov = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 5 }] }
+movzx = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 5 }] }
+movsx = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 5 }] }
+movsxd = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 5 }] }
+add = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 6 }] }
+sub = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 6 }] }
+and = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 6 }] }
+or = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 6 }] }
+xor = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 6 }] }
+cmp = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 6 }, { operands = ["mem"], latency = 6 }] }
+test = 1
+lea = 1
+push = 2
+pop = 5
+leave = 5
+imul = 3
+mul = 4
+div = 26
+idiv = 42
+shl = 1
+shr = 1
+sar = 1
+cdq = 1
+cqo = 1
+cdqe = 1
+call = 3
+ret = 5
+movsd = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 5 }] }
+movss = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 5 }] }
+addsd = 4
+subsd = 4
+mulsd = 4
+divsd = 14
+sqrtsd = 18
+cvtsi2sd = 5
+cvttsd2si = 6
+"#;
+
+const ZEN2_X86: &str = r#"
+[X86]
+mov = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 4 }] }
+movzx = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 4 }] }
+movsx = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 4 }] }
+movsxd = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 4 }] }
+add = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 5 }] }
+sub = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 5 }] }
+and = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 5 }] }
+or = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 5 }] }
+xor = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 5 }] }
+cmp = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 5 }, { operands = ["mem"], latency = 5 }] }
+test = 1
+lea = 1
+push = 2
+pop = 4
+leave = 4
+imul = 3
+mul = 3
+div = 45
+idiv = 45
+shl = 1
+shr = 1
+sar = 1
+cdq = 1
+cqo = 1
+cdqe = 1
+call = 3
+ret = 4
+movsd = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 4 }] }
+movss = { base = 1, rules = [{ operands = ["reg", "mem"], latency = 4 }] }
+addsd = 3
+subsd = 3
+mulsd = 3
+divsd = 13
+sqrtsd = 20
+cvtsi2sd = 4
+cvttsd2si = 4
+"#;
+
+const CORTEX_A53_ARM64: &str = r#"
+[ARM64]
+ldr = 3
+ldur = 3
+ldrb = 3
+ldrh = 3
+ldrsw = 3
+ldp = 3
+str = 1
+stur = 1
+strb = 1
+stp = 2
+mul = 3
+madd = 3
+msub = 3
+smull = 3
+umull = 3
+sdiv = 19
+udiv = 19
+fadd = 4
+fsub = 4
+fmul = 4
+fmadd = 8
+fdiv = 22
+fsqrt = 32
+scvtf = 4
+fcvtzs = 4
+"#;
+
+const CORTEX_A72_ARM64: &str = r#"
+[ARM64]
+ldr = 4
+ldur = 4
+ldrb = 4
+ldrh = 4
+ldrsw = 5
+ldp = 4
+str = 1
+stur = 1
+strb = 1
+stp = 1
+mul = 3
+madd = 3
+msub = 3
+smull = 3
+umull = 3
+sdiv = 12
+udiv = 12
+fadd = 4
+fsub = 4
+fmul = 4
+fmadd = 7
+fdiv = 17
+fsqrt = 32
+scvtf = 5
+fcvtzs = 5
+"#;
+
+impl LatencyTable {
+    /// Returns the built-in table of the processor model, e.g. to be extended with `merge`.
+    pub fn for_cpu(cpu: CpuModel) -> Self {
+        let tables: &[&str] = match cpu {
+            CpuModel::Unit => &[],
+            CpuModel::Generic => &[SKYLAKE_X86, CORTEX_A72_ARM64],
+            CpuModel::Skylake => &[SKYLAKE_X86],
+            CpuModel::Zen2 => &[ZEN2_X86],
+            CpuModel::CortexA53 => &[CORTEX_A53_ARM64],
+            CpuModel::CortexA72 => &[CORTEX_A72_ARM64],
+        };
+
+        let mut latency_table = LatencyTable::default();
+        for table in tables {
+            let layer = toml::from_str(table).expect("Invalid built-in latency table");
+            latency_table.merge(LatencyLayer::normalized(layer));
+        }
+        latency_table
+    }
+
+    /// Layers `other` on top of this table: its mnemonics, groups and profiles replace the ones
+    /// of this table, as well as its `default` latency, `branch_penalty`, `call_latency`,
+    /// `ret_latency` and `hazard_penalty`, if they are set.
+    pub fn merge(&mut self, other: LatencyLayer) {
+        self.default = other.default.unwrap_or(self.default);
+        self.branch_penalty = other.branch_penalty.unwrap_or(self.branch_penalty);
+        self.call_latency = other.call_latency.unwrap_or(self.call_latency);
+        self.ret_latency = other.ret_latency.unwrap_or(self.ret_latency);
+        self.hazard_penalty = other.hazard_penalty.unwrap_or(self.hazard_penalty);
+        self.groups.extend(other.groups);
+        self.profiles.extend(other.profiles);
+        for (arch, mnemonics) in other.archs {
+            self.archs.entry(arch).or_default().extend(mnemonics);
+        }
+    }
+
+    /// Loads the table from a file (see `LatencyLayer::from_file`), on top of the default one.
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut latency_table = LatencyTable::default();
+        latency_table.merge(LatencyLayer::from_file(path)?);
+        Ok(latency_table)
+    }
+
+    /// Returns the table of the profile `name` layered on top of this table, without the
+    /// profiles, or None if there is no such profile.
+    pub fn profile(&self, name: &str) -> Option<LatencyTable> {
+        let profile = self.profiles.get(name)?;
+        let mut table = LatencyTable {
+            profiles: HashMap::new(),
+            ..self.clone()
+        };
+        table.merge(profile.clone());
+        Some(table)
+    }
+
+    /// Returns the latency of an instruction, given the names of its capstone `groups`. An x86
+    /// mnemonic in the AT&T `syntax` without an environment variable or not in the table is
+    /// also looked up without its size suffix (e.g. `X86_MOV` and `mov` for `movl`), while
+    /// the Intel mnemonics have no suffix (e.g. `shl` is not `sh`).
+    pub fn get_latency(
+        &self,
+        arch: Arch,
+        syntax: Syntax,
+        mnemonic: &str,
+        operands: &[String],
+        groups: &[String],
+    ) -> u32 {
+        let base_mnemonic = match (arch, syntax) {
+            (Arch::X86, Syntax::Att) => without_size_suffix(mnemonic).map(str::to_uppercase),
+            _ => None,
+        };
+        let arch = arch.to_string().to_uppercase();
+        let mnemonic = mnemonic.to_uppercase();
+
+        for mnemonic in [Some(&mnemonic), base_mnemonic.as_ref()]
+            .into_iter()
+            .flatten()
+        {
+            if let Some(latency) = env_number(&format!("{arch}_{mnemonic}")) {
+                return latency;
+            }
+        }
+
+        self.archs
+            .get(&arch)
+            .and_then(|mnemonics| {
+                mnemonics
+                    .get(&mnemonic)
+                    .or_else(|| mnemonics.get(base_mnemonic.as_ref()?))
+            })
+            .map(|latency| latency.resolve(operands))
+            .or_else(|| {
+                groups
+                    .iter()
+                    .filter_map(|group| self.groups.get(&group.to_uppercase()))
+                    .max()
+                    .copied()
+            })
+            .unwrap_or(self.default)
+    }
+}
+
+impl LatencyLayer {
+    /// Loads the layer from a JSON file if the extension is `.json`, from a TOML file otherwise.
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(path)?;
+
+        let layer: LatencyLayer = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&content)?,
+            _ => toml::from_str(&content)?,
+        };
+
+        Ok(LatencyLayer::normalized(layer))
+    }
+
+    fn normalized(layer: LatencyLayer) -> Self {
+        // normalize the keys to make the lookup case insensitive
+        let archs = layer
+            .archs
+            .into_iter()
+            .map(|(arch, mnemonics)| {
+                let mnemonics = mnemonics
+                    .into_iter()
+                    .map(|(mnemonic, latency)| (mnemonic.to_uppercase(), latency))
+                    .collect();
+                (arch.to_uppercase(), mnemonics)
+            })
+            .collect();
+
+        LatencyLayer {
+            groups: layer
+                .groups
+                .into_iter()
+                .map(|(group, latency)| (group.to_uppercase(), latency))
+                .collect(),
+            profiles: layer
+                .profiles
+                .into_iter()
+                .map(|(name, profile)| (name, LatencyLayer::normalized(profile)))
+                .collect(),
+            archs,
+            ..layer
+        }
+    }
+}
@@ -0,0 +1,416 @@
+pub mod analysis;
+pub mod annotations;
+pub mod arch;
+pub mod block;
+pub mod bounds;
+pub mod config;
+pub mod cost;
+pub mod cycle;
+#[cfg(feature = "dwarf")]
+pub mod dwarf;
+pub mod error;
+pub mod graph;
+pub mod indirect;
+pub mod instruction;
+pub mod jump;
+pub mod latency;
+pub mod symbols;
+pub mod trace;
+pub mod wcet;
+
+use std::time::Instant;
+
+use capstone::{Arch, Capstone, Endian, ExtraMode, Instructions, Mode};
+use object::{
+    elf, macho, pe, BinaryFormat, Object, ObjectSection, ObjectSegment, SectionFlags, SectionIndex,
+    SectionKind,
+};
+
+pub use crate::analysis::Analysis;
+pub use crate::arch::ArchMode;
+pub use crate::block::Block;
+pub use crate::config::{Config, EntryMode, IndirectPolicy, Syntax};
+pub use crate::cost::CostModel;
+pub use crate::cycle::LoopListing;
+pub use crate::error::{JumpError, WcetError};
+pub use crate::graph::{GraphStats, MappedGraph};
+pub use crate::jump::ExitJump;
+pub use crate::wcet::{
+    analyze_graph, build_control_flow, build_graph, calculate_wcet, merge_straight_line_blocks,
+    reweight_graph, BlockDump, ControlFlow, WcetReport,
+};
+
+use crate::arch::arm_code_regions;
+use crate::symbols::function_symbols;
+use crate::trace::Event;
+
+#[doc(hidden)]
+pub use log; // used by printwarning
+
+/// Logs a warning about an approximation of the analysis (kept for compatibility, it is a
+/// `log::warn!`).
+#[macro_export]
+macro_rules! printwarning {
+    ($($arg:tt)*) => {
+        $crate::log::warn!($($arg)*)
+    };
+}
+
+/// Analyzes the object file contained in `bytes` and returns its WCET report.
+/// If `config.functions` (`config.program_entry`) is empty, the functions (the entry point) are
+/// taken from the symbol table (the header) of the object. With the `dwarf` feature, an empty
+/// `config.line_table` is read from the debug information. The addresses of the object are
+/// moved by `config.load_bias`, while the ones of the configuration are already moved.
+pub fn analyze_bytes(bytes: &[u8], config: &Config) -> Result<WcetReport, WcetError> {
+    let (mut report, disassembly_time) = disassemble_object(bytes, config, calculate_core_wcets)?;
+    report
+        .timings
+        .insert(0, ("disassembly".to_string(), disassembly_time));
+
+    Ok(report)
+}
+
+/// Disassembles the object file contained in `bytes` and passes its instructions to `analysis`,
+/// with the configuration completed from the object (see `analyze_bytes`). Returns the result
+/// of `analysis` and the duration of the disassembly in milliseconds.
+pub(crate) fn disassemble_object<T>(
+    bytes: &[u8],
+    config: &Config,
+    analysis: impl FnOnce(&Capstone, &ArchMode, &[Instructions], &Config) -> Result<T, WcetError>,
+) -> Result<(T, f64), WcetError> {
+    let obj_file = object::File::parse(bytes).map_err(WcetError::InvalidObject)?;
+
+    let arch_mode = ArchMode::try_from(obj_file.architecture())?;
+
+    let mut config = config.clone();
+    if config.functions.is_empty() {
+        config.functions = function_symbols(&obj_file)
+            .into_iter()
+            .map(|(address, name)| (address + config.load_bias, name))
+            .collect();
+    }
+    #[cfg(feature = "dwarf")]
+    if config.line_table.is_empty() {
+        config.line_table = dwarf::line_table(&obj_file)
+            .into_iter()
+            .map(|(address, location)| (address + config.load_bias, location))
+            .collect();
+    }
+    // relocatable objects have no entry point
+    if config.program_entry.is_none() && obj_file.entry() != 0 {
+        // the low bit of an ARM entry point only selects the Thumb mode
+        let program_entry = match arch_mode.arch {
+            Arch::ARM => obj_file.entry() & !1,
+            _ => obj_file.entry(),
+        };
+        // the entry point of Mach-O (LC_MAIN) is an offset in the file
+        let program_entry = match obj_file.format() {
+            BinaryFormat::MachO => obj_file.segments().find_map(|segment| {
+                let (offset, size) = segment.file_range();
+                (offset..offset + size)
+                    .contains(&program_entry)
+                    .then(|| segment.address() + program_entry - offset)
+            }),
+            _ => Some(program_entry),
+        };
+        config.program_entry = program_entry.map(|entry| entry + config.load_bias);
+    }
+    select_program_entry(&mut config)?;
+
+    // the big endian objects (e.g. MIPS, PowerPC, SPARC) are disassembled in their byte order
+    let endian = match obj_file.endianness() {
+        object::Endianness::Big => Some(Endian::Big),
+        object::Endianness::Little => None,
+    };
+
+    let cs = new_capstone(arch_mode.arch, arch_mode.mode, endian, &config)?;
+
+    // ARM binaries can interleave ARM and Thumb code, so a second handle is used for ARM mode
+    let cs_arm = if arch_mode.arch == Arch::ARM {
+        Some(new_capstone(arch_mode.arch, Mode::Arm, endian, &config)?)
+    } else {
+        None
+    };
+
+    // the offset skips the start of a single section, e.g. the vector table of the text
+    let offset_section = if config.text_offset > 0 {
+        Some(offset_section(&obj_file, &config)?)
+    } else {
+        None
+    };
+
+    // disassemble each executable section separately at its own virtual address, moved by the
+    // load bias (the relative jumps are decoded from the address of the instruction)
+    let start = Instant::now();
+    let mut sections = Vec::new();
+    for section in obj_file.sections() {
+        if is_code_section(&section) {
+            let data = section.data().map_err(WcetError::InvalidObject)?;
+            if is_stub_section(&section) {
+                let start = section.address() + config.load_bias;
+                config.stub_ranges.push(start..start + data.len() as u64);
+            }
+
+            let regions = if arch_mode.arch == Arch::ARM {
+                arm_code_regions(&obj_file, &section, arch_mode.mode)
+            } else {
+                vec![(
+                    section.address(),
+                    section.address() + data.len() as u64,
+                    arch_mode.mode,
+                )]
+            };
+
+            // the skipped bytes are not code, but the addresses of the rest are kept
+            let code_start = match offset_section {
+                Some(index) if index == section.index() => section.address() + config.text_offset,
+                _ => section.address(),
+            };
+            for (start, end, mode) in regions {
+                let start = start.max(code_start);
+                if start >= end {
+                    continue;
+                }
+                let code =
+                    &data[(start - section.address()) as usize..(end - section.address()) as usize];
+                let cs = match &cs_arm {
+                    Some(cs_arm) if mode == Mode::Arm => cs_arm,
+                    _ => &cs,
+                };
+                sections.extend(disassemble(cs, code, start + config.load_bias, &config)?);
+            }
+        }
+    }
+
+    let disassembly_time = start.elapsed().as_secs_f64() * 1000.0;
+
+    Ok((
+        analysis(&cs, &arch_mode, &sections, &config)?,
+        disassembly_time,
+    ))
+}
+
+/// Returns whether `section` contains code: the sections of kind text and the ones flagged as
+/// executable by the format, whatever their name (e.g. the `.init`, `.fini` and `.plt` of ELF,
+/// or the `__stubs` of Mach-O, whose kind is only given to `__TEXT,__text`).
+fn is_code_section<'data>(section: &impl ObjectSection<'data>) -> bool {
+    section.kind() == SectionKind::Text
+        || match section.flags() {
+            SectionFlags::Elf { sh_flags } => sh_flags & elf::SHF_EXECINSTR as u64 != 0,
+            SectionFlags::MachO { flags } => {
+                flags & (macho::S_ATTR_PURE_INSTRUCTIONS | macho::S_ATTR_SOME_INSTRUCTIONS) != 0
+            }
+            SectionFlags::Coff { characteristics } => {
+                characteristics & (pe::IMAGE_SCN_CNT_CODE | pe::IMAGE_SCN_MEM_EXECUTE) != 0
+            }
+            _ => false,
+        }
+}
+
+/// Returns the index of the executable section whose first `config.text_offset` bytes are
+/// skipped: the one named `config.text_section`, or else `.text` (`__text` in Mach-O) or the
+/// first executable section.
+fn offset_section(obj_file: &object::File, config: &Config) -> Result<SectionIndex, WcetError> {
+    let mut code_sections = obj_file.sections().filter(is_code_section);
+    let section = match &config.text_section {
+        Some(name) => code_sections
+            .find(|section| section.name() == Ok(name.as_str()))
+            .ok_or_else(|| WcetError::InvalidOption("text-section", name.clone()))?,
+        None => {
+            let first = obj_file.sections().find(is_code_section);
+            code_sections
+                .find(|section| matches!(section.name(), Ok(".text" | "__text")))
+                .or(first)
+                .ok_or(WcetError::EmptyText)?
+        }
+    };
+
+    Ok(section.index())
+}
+
+/// Returns whether `section` holds the stubs jumping to the external functions through a table
+/// of addresses: the `.plt` sections of ELF and the symbol stubs (`__stubs`) of Mach-O.
+fn is_stub_section<'data>(section: &impl ObjectSection<'data>) -> bool {
+    match section.flags() {
+        SectionFlags::Elf { .. } => section
+            .name()
+            .is_ok_and(|name| name.starts_with(".plt") || name == ".iplt"),
+        SectionFlags::MachO { flags } => flags & macho::SECTION_TYPE == macho::S_SYMBOL_STUBS,
+        _ => false,
+    }
+}
+
+/// Analyzes a raw binary (e.g. a firmware dump or a ROM image) without an object header: the
+/// whole content is code of the given architecture loaded at `base`, in big endian byte order
+/// if `big_endian`. There are no symbols, so the functions are found from the call targets
+/// and, if `config.program_entry` is not set, the entry point is the start of the code.
+pub fn analyze_raw(
+    bytes: &[u8],
+    arch_mode: &ArchMode,
+    base: u64,
+    big_endian: bool,
+    config: &Config,
+) -> Result<WcetReport, WcetError> {
+    let endian = big_endian.then_some(Endian::Big);
+    let cs = new_capstone(arch_mode.arch, arch_mode.mode, endian, config)?;
+
+    // the skipped bytes are not code, but the addresses of the rest are kept
+    let code = bytes.get(config.text_offset as usize..).unwrap_or_default();
+    let code_start = base + config.load_bias + config.text_offset;
+
+    let mut config = config.clone();
+    config.program_entry.get_or_insert(code_start);
+    select_program_entry(&mut config)?;
+
+    let start = Instant::now();
+    let sections = disassemble(&cs, code, code_start, &config)?;
+    let disassembly_time = start.elapsed().as_secs_f64() * 1000.0;
+
+    let mut report = calculate_core_wcets(&cs, arch_mode, &sections, &config)?;
+    report
+        .timings
+        .insert(0, ("disassembly".to_string(), disassembly_time));
+
+    Ok(report)
+}
+
+/// Computes the WCET report of the disassembled `sections` with the latency table of `config`
+/// or, if `config.core_latency_tables` is not empty, once with the table of each core: the
+/// report of the core with the highest WCET then includes the reports of all the cores.
+fn calculate_core_wcets(
+    cs: &Capstone,
+    arch_mode: &ArchMode,
+    sections: &[Instructions],
+    config: &Config,
+) -> Result<WcetReport, WcetError> {
+    if config.core_latency_tables.is_empty() {
+        return calculate_wcet(cs, arch_mode, sections, config);
+    }
+
+    let mut core_reports = Vec::with_capacity(config.core_latency_tables.len());
+    for (core, latency_table) in config.core_latency_tables.iter() {
+        let config = Config {
+            latency_table: latency_table.clone(),
+            core_latency_tables: Vec::new(),
+            ..config.clone()
+        };
+        core_reports.push((
+            core.clone(),
+            calculate_wcet(cs, arch_mode, sections, &config)?,
+        ));
+    }
+
+    let (_, worst_report) = core_reports
+        .iter()
+        .max_by_key(|(_, report)| report.wcet)
+        .expect("No core requested");
+    Ok(WcetReport {
+        core_reports: core_reports.clone(),
+        ..worst_report.clone()
+    })
+}
+
+/// In the `Program` entry mode, analyzes only the entry point of the program or, if the object
+/// has none (e.g. a relocatable object), its main function, unless an entry is already given.
+fn select_program_entry(config: &mut Config) -> Result<(), WcetError> {
+    if config.entry_mode != EntryMode::Program
+        || config.entry.is_some()
+        || !config.entries.is_empty()
+    {
+        return Ok(());
+    }
+
+    let main = config
+        .functions
+        .iter()
+        .find(|(_, name)| *name == "main" || *name == "_main")
+        .map(|(address, _)| *address);
+    config.entry = Some(
+        config
+            .program_entry
+            .or(main)
+            .ok_or(WcetError::NoProgramEntry)?,
+    );
+    Ok(())
+}
+
+/// Disassembles the code region starting at `address`. Capstone stops at the first bytes that
+/// are not a valid instruction (unless `config.skip_data`): the address is reported and, with
+/// `config.resume_alignment`, the disassembly resumes at the following aligned address, giving
+/// a list of instructions for each decoded part of the region.
+fn disassemble<'a>(
+    cs: &'a Capstone,
+    code: &[u8],
+    address: u64,
+    config: &Config,
+) -> Result<Vec<Instructions<'a>>, WcetError> {
+    let end = address + code.len() as u64;
+    let mut parts = Vec::new();
+    let mut start = address;
+    while start < end {
+        let instructions = cs
+            .disasm_all(&code[(start - address) as usize..], start)
+            .map_err(WcetError::DisassembleFailed)?;
+        let stop = instructions
+            .last()
+            .map_or(start, |last| last.address() + last.bytes().len() as u64);
+        if !instructions.is_empty() {
+            parts.push(instructions);
+        }
+        if stop >= end {
+            break;
+        }
+
+        match config.resume_alignment {
+            Some(alignment) => {
+                let alignment = alignment.max(1);
+                start = (stop / alignment + 1) * alignment;
+                trace::emit(config, Event {
+                    event: "invalid_instruction",
+                    address: Some(stop),
+                    reason: format!(
+                        "The disassembly stopped at the invalid instruction at 0x{stop:x}, resumed at 0x{start:x}"
+                    ),
+                    ..Event::default()
+                });
+            }
+            None => {
+                trace::emit(config, Event {
+                    event: "invalid_instruction",
+                    address: Some(stop),
+                    reason: format!(
+                        "The disassembly stopped at the invalid instruction at 0x{stop:x}, the last {} bytes of the code are not analyzed",
+                        end - stop
+                    ),
+                    ..Event::default()
+                });
+                break;
+            }
+        }
+    }
+
+    Ok(parts)
+}
+
+/// Creates a Capstone handle with the details of the instructions, skipping the data if
+/// `config.skip_data`. The compressed instructions of RISC-V (e.g. c.beqz) are decoded too.
+fn new_capstone(
+    arch: Arch,
+    mode: Mode,
+    endian: Option<Endian>,
+    config: &Config,
+) -> Result<Capstone, WcetError> {
+    let extra_mode = (arch == Arch::RISCV).then_some(ExtraMode::RiscVC);
+    let mut cs = Capstone::new_raw(arch, mode, extra_mode.into_iter(), endian)
+        .map_err(WcetError::DisassembleFailed)?;
+    cs.set_detail(true).map_err(WcetError::DisassembleFailed)?;
+    cs.set_skipdata(config.skip_data)
+        .map_err(WcetError::DisassembleFailed)?;
+    // the syntax can only be chosen for x86
+    if arch == Arch::X86 && config.syntax == Syntax::Att {
+        cs.set_syntax(capstone::Syntax::Att)
+            .map_err(WcetError::DisassembleFailed)?;
+    }
+
+    Ok(cs)
+}
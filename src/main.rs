@@ -1,68 +1,119 @@
-#[macro_use]
+mod cli;
 
-mod arch;
-mod block;
-mod cycle;
-mod graph;
-mod instruction;
-mod jump;
-mod wcet;
+use std::io::Write;
 
-use std::cell::RefCell;
+use clap::Parser;
+use log::{Level, LevelFilter};
+use object::Object;
 
-use capstone::{Capstone, NO_EXTRA_MODE};
-use object::{Object, ObjectSection};
+use timing_analysis_tool::{analyze_bytes, analyze_raw, ArchMode, WcetError, WcetReport};
 
-use crate::arch::ArchMode;
-use crate::wcet::calculate_wcet;
+use crate::cli::{Cli, OutputFormat};
 
-#[macro_export]
-macro_rules! printwarning {
-    ($($arg:tt)*) => {
-        println!("WARNING: {}", format_args!($($arg)*))
-    };
-}
+fn main() {
+    dotenv::dotenv().ok(); // load .env file
 
-thread_local! {
-    static CURRENT_ARCH: RefCell<Option<ArchMode>> = RefCell::new(None);
+    let cli = Cli::parse();
+    init_logger(cli.verbose);
+
+    if let Err(e) = run(&cli) {
+        eprintln!("ERROR: {e}");
+        std::process::exit(1);
+    }
 }
 
-const GRAPHS_DIR: &str = "graphs";
+fn run(cli: &Cli) -> Result<(), WcetError> {
+    let file_bytes = std::fs::read(&cli.file).map_err(|e| WcetError::Io(cli.file.clone(), e))?;
 
-fn main() {
-    dotenv::dotenv().ok(); // load .env file
+    let report = if cli.raw {
+        // required by --raw, an empty name is reported as unsupported
+        let arch = cli.arch.as_deref().unwrap_or_default();
+        let arch_mode = ArchMode::from_names(arch, cli.mode.as_deref())?;
+        let config = cli.to_config(None)?;
 
-    let file_name = std::env::args().nth(1).expect("File name not found"); // read the file name from the command line argument
+        if cli.output == OutputFormat::Text {
+            println!("Format: Raw (base 0x{:x})", cli.base);
+            println!("{arch_mode:?}");
+        }
 
-    let file_bytes = std::fs::read(file_name).expect("File not found!");
-    let obj_file = object::File::parse(file_bytes.as_slice()).unwrap();
+        analyze_raw(&file_bytes, &arch_mode, cli.base, cli.big_endian, &config)?
+    } else {
+        let obj_file =
+            object::File::parse(file_bytes.as_slice()).map_err(WcetError::InvalidObject)?;
+        let config = cli.to_config(Some(&obj_file))?;
 
-    let arch = obj_file.architecture();
-    let arch_mode = ArchMode::from(arch);
-    CURRENT_ARCH.with(|current_arch| {
-        *current_arch.borrow_mut() = Some(arch_mode.clone());
-    });
+        if cli.output == OutputFormat::Text {
+            let arch_mode = ArchMode::try_from(obj_file.architecture())?;
+            println!("Format: {:?} ({:?})", obj_file.format(), obj_file.kind());
+            println!("{arch_mode:?}");
+        }
 
-    println!("{arch_mode:?}");
+        analyze_bytes(&file_bytes, &config)?
+    };
 
-    let mut text_section = Vec::new();
-    for section in obj_file.sections() {
-        // join all the sections .text in one
-        if section.name().unwrap().contains("text") {
-            text_section.extend_from_slice(section.data().unwrap());
+    match cli.output {
+        OutputFormat::Text if cli.disasm_only => print_disassembly(&report),
+        OutputFormat::Text if cli.list_loops => {
+            for listing in &report.loops {
+                println!("{listing}");
+            }
+        }
+        OutputFormat::Text => {
+            for block in &report.blocks {
+                println!("{block}");
+            }
+            if cli.stats {
+                println!("{}", report.stats);
+            }
+            if cli.timings {
+                for (phase, ms) in &report.timings {
+                    println!("{phase:<20} {ms:>10.3} ms");
+                }
+                let total: f64 = report.timings.iter().map(|(_, ms)| ms).sum();
+                println!("{:<20} {total:>10.3} ms", "total");
+            }
+            println!("{report}");
         }
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&report).expect("Unable to serialize the report")
+        ),
     }
 
-    let mut cs = Capstone::new_raw(arch_mode.arch, arch_mode.mode, NO_EXTRA_MODE, None)
-        .expect("Failed to create Capstone handle");
-    cs.set_detail(true).unwrap();
-    cs.set_skipdata(false).unwrap();
-
-    let instructions = cs
-        .disasm_all(&text_section, 0x1000)
-        .expect("Failed to disassemble given code");
+    Ok(())
+}
 
-    let wcet = calculate_wcet(&cs, &arch_mode, &instructions);
+/// Prints each decoded instruction of `report` with its latency and exit jump (`-` if it
+/// doesn't end a block).
+fn print_disassembly(report: &WcetReport) {
+    for (instruction, exit_jump) in &report.disassembly {
+        let exit_jump = exit_jump
+            .as_ref()
+            .map_or("-".to_string(), |exit_jump| exit_jump.to_string());
+        println!(
+            "{:<48} latency {:<3} exit jump: {exit_jump}",
+            instruction.to_string(),
+            instruction.latency
+        );
+    }
+}
 
-    println!("WCET: {wcet} clock cycles");
+/// Prints the warnings (and the phases of the analysis, if `verbose`) on stderr. The level
+/// can also be set with the `RUST_LOG` env variable.
+fn init_logger(verbose: bool) {
+    env_logger::Builder::new()
+        .filter_level(if verbose {
+            LevelFilter::Info
+        } else {
+            LevelFilter::Warn
+        })
+        .parse_default_env()
+        .format(|buf, record| {
+            let level = match record.level() {
+                Level::Warn => "WARNING",
+                level => level.as_str(),
+            };
+            writeln!(buf, "{level}: {}", record.args())
+        })
+        .init();
 }
@@ -0,0 +1,42 @@
+use std::collections::BTreeMap;
+
+use object::{Object, ObjectSymbol, SymbolKind};
+
+/// Returns the functions defined in the symbol table of the object file (address -> name).
+/// The map is empty for stripped binaries.
+pub fn function_symbols(obj_file: &object::File) -> BTreeMap<u64, String> {
+    // the low bit of the address of an ARM function only selects the Thumb mode
+    let address_mask = match obj_file.architecture() {
+        object::Architecture::Arm => !1,
+        _ => !0,
+    };
+
+    obj_file
+        .symbols()
+        .filter(|symbol| symbol.kind() == SymbolKind::Text && symbol.is_definition())
+        .filter_map(|symbol| match symbol.name() {
+            Ok(name) if !name.is_empty() => {
+                Some((symbol.address() & address_mask, name.to_string()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the name of the function enclosing the address, i.e. the closest function starting
+/// at or before it, or `sub_<address>` if there is none.
+pub fn enclosing_function(functions: &BTreeMap<u64, String>, address: u64) -> String {
+    match functions.range(..=address).next_back() {
+        Some((_, name)) => name.clone(),
+        None => format!("sub_{address:x}"),
+    }
+}
+
+/// Returns the source location of the address in the line table (see `Config::line_table`),
+/// i.e. the one of the closest row at or before it.
+pub fn source_location(line_table: &BTreeMap<u64, String>, address: u64) -> Option<&String> {
+    line_table
+        .range(..=address)
+        .next_back()
+        .map(|(_, location)| location)
+}
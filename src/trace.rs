@@ -0,0 +1,63 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::printwarning;
+
+/// Decision taken by the analysis (e.g. the exit chosen for a cycle, or an ignored jump), which
+/// is printed as a warning and written as a JSON object on a line of the trace, if enabled.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Event {
+    pub event: &'static str, // kind of decision, e.g. "ignored_exit"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<u64>, // instruction or block the decision is about
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cycle: Option<u64>, // entry block of the cycle the decision is about
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry: Option<u64>, // entry block chosen or ignored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit: Option<u64>, // exit block chosen or ignored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<u64>, // target of the jump the decision is about
+    pub reason: String,      // the warning
+}
+
+/// Destination of the events of the analyses using a configuration (e.g. a file), one JSON
+/// object per line; the copies of the configuration share it. After a failed write (e.g. a
+/// full disk or a closed pipe) the error is printed once and no more events are written.
+#[derive(Clone)]
+pub struct TraceOutput(Arc<Mutex<Option<Box<dyn Write + Send>>>>);
+
+impl TraceOutput {
+    pub fn new(output: impl Write + Send + 'static) -> Self {
+        TraceOutput(Arc::new(Mutex::new(Some(Box::new(output)))))
+    }
+
+    fn write(&self, event: &Event) {
+        let mut output = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(writer) = output.as_mut() {
+            let line = serde_json::to_string(event).expect("Unable to serialize the event");
+            if let Err(e) = writeln!(writer, "{line}") {
+                printwarning!("Unable to write the trace, the following events are dropped: {e}");
+                *output = None;
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for TraceOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TraceOutput")
+    }
+}
+
+/// Prints the reason of the event as a warning and writes the event to the trace of `config`.
+pub fn emit(config: &Config, event: Event) {
+    printwarning!("{}", event.reason);
+
+    if let Some(output) = &config.trace_output {
+        output.write(&event);
+    }
+}
@@ -1,46 +1,639 @@
-use std::collections::{hash_map, BTreeMap, HashMap, HashSet};
-use std::io::Write;
+use std::collections::{btree_map, hash_map, BTreeMap, BTreeSet, HashMap, HashSet};
+use std::time::Instant;
 
-use capstone::{Capstone, Instructions};
-use petgraph::Direction::Incoming;
+use capstone::{Capstone, Insn, Instructions};
+use log::info;
+use petgraph::Direction::{Incoming, Outgoing};
+use serde::Serialize;
 
-use crate::arch::ArchMode;
+use crate::arch::{ArchMode, SUPPORTED_ARCHS};
 use crate::block::Block;
-use crate::cycle::condensate_graph;
-use crate::graph::MappedGraph;
-use crate::jump::{get_exit_jump, ExitJump};
-use crate::printwarning;
+use crate::config::{Config, IndirectPolicy, NO_RETURN_FUNCTIONS};
+use crate::cost::CostModel;
+use crate::cycle::{condensate_graph, loop_names, LoopListing};
+use crate::error::WcetError;
+use crate::graph::{weight_to_latency, GraphStats, MappedCondensedGraph, MappedGraph};
+use crate::instruction::{rep_instruction, Instruction};
+use crate::jump::{
+    conditional_exit_jump, get_exit_jump, has_delay_slot, is_call, it_block_length, ExitJump,
+};
+use crate::latency::LatencyTable;
+use crate::symbols::{enclosing_function, source_location};
+use crate::trace::{self, Event};
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WcetReport {
+    pub wcet: u64,                                      // clock cycles
+    pub bcet: u64, // clock cycles, best case of the entry of the WCET
+    pub entry_wcets: Vec<(u64, u64)>, // (entry_leader, latency) for each entry node
+    pub recursive_functions: Vec<u64>, // addresses of the detected recursive functions
+    pub recursive_bounds: Vec<(u64, u64, u32)>, // (function_address, ret_address, max_recursions) of each recursive function
+    pub cycles: Vec<(u64, u32)>,                // (cycle_entry_address, max_cycles)
+    pub loop_latencies: BTreeMap<u64, (u64, u32, u64)>, // cycle_entry_address -> (iteration_latency, max_cycles, total_latency)
+    pub non_terminating_loops: Vec<u64>, // cycle_entry_address of the cycles without exit, counted once
+    pub function_wcets: Vec<(String, u64)>, // (function_name, latency) for each function
+    pub critical_path: Vec<u64>,         // leaders of the blocks along the WCET path
+    pub source_lines: BTreeMap<u64, String>, // leader -> file:line of the blocks of the critical path
+    pub stats: GraphStats,                   // metrics of the control flow graph
+    pub wcet_ns: Option<f64>,                // WCET in nanoseconds, if the clock frequency is known
+    pub timings: Vec<(String, f64)>,         // (phase, milliseconds) of the analysis
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub loops: Vec<LoopListing>, // cycles of the graph with their bounds, only with config.list_loops
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub blocks: Vec<BlockDump>, // basic blocks of the graph ordered by leader, only with config.dump_blocks
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub disassembly: Vec<(Instruction, Option<ExitJump>)>, // decoded instructions with their exit jump, only with config.disasm_only
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub entry_reports: Vec<(u64, WcetReport)>, // (entry_leader, report) for each of config.entries
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub core_reports: Vec<(String, WcetReport)>, // (core, report) for each of config.core_latency_tables
+}
+
+/// Basic block of the graph as listed by `config.dump_blocks`, to check the detection of the
+/// leaders and the classification of the jumps.
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockDump {
+    pub leader: u64,
+    pub function: String,               // name of the enclosing function
+    pub byte_range: (u64, u64),         // (start, end) addresses of the bytes of its instructions
+    pub latency: u32,                   // clock cycles of an execution
+    pub successors: Vec<u64>,           // leaders of the blocks it can jump to
+    pub instructions: Vec<Instruction>, // instructions, with their latencies
+    pub exit_jump: Option<ExitJump>,    // jump ending the block
+}
+
+impl BlockDump {
+    fn new(block: &Block) -> Self {
+        let byte_range = block.byte_range();
+        BlockDump {
+            leader: block.leader,
+            function: block.function.clone(),
+            byte_range: (byte_range.start, byte_range.end),
+            latency: block.get_latency(),
+            successors: block.get_targets(),
+            instructions: block.instructions.clone(),
+            exit_jump: block.exit_jump.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for BlockDump {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let successors = self
+            .successors
+            .iter()
+            .map(|target| format!("0x{target:x}"))
+            .collect::<Vec<String>>();
+        writeln!(
+            f,
+            "Block 0x{:x} ({}): bytes [0x{:x}, 0x{:x}), latency {}, successors [{}]",
+            self.leader,
+            self.function,
+            self.byte_range.0,
+            self.byte_range.1,
+            self.latency,
+            successors.join(", ")
+        )?;
+        for instruction in self.instructions.iter() {
+            writeln!(f, "{instruction}")?;
+        }
+        match &self.exit_jump {
+            Some(exit_jump) => writeln!(f, "Exit jump: {exit_jump}"),
+            None => writeln!(f, "Exit jump: None"),
+        }
+    }
+}
+
+impl std::fmt::Display for WcetReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (core, report) in self.core_reports.iter() {
+            writeln!(f, "WCET on {core}: {} clock cycles", report.wcet)?;
+        }
+        if self.entry_wcets.len() > 1 {
+            for (entry_leader, latency) in self.entry_wcets.iter() {
+                writeln!(
+                    f,
+                    "WCET: {latency} clock cycles for the graph starting at entry node: 0x{entry_leader:x}"
+                )?;
+            }
+        }
+        for (function, latency) in self.function_wcets.iter() {
+            writeln!(f, "WCET of {function}: {latency} clock cycles")?;
+        }
+        for (entry_address, (iteration_latency, max_cycles, total_latency)) in
+            self.loop_latencies.iter()
+        {
+            let share = *total_latency as f64 / self.wcet.max(1) as f64 * 100.0;
+            let iterations = if self.non_terminating_loops.contains(entry_address) {
+                "1 iteration (non-terminating)".to_string()
+            } else {
+                format!("{max_cycles} iterations")
+            };
+            writeln!(
+                f,
+                "Loop 0x{entry_address:x}: {iteration_latency} clock cycles x {iterations}, \
+                {total_latency} clock cycles ({share:.1}% of the WCET)"
+            )?;
+        }
+        if !self.critical_path.is_empty() {
+            let critical_path = self
+                .critical_path
+                .iter()
+                .map(|leader| match self.source_lines.get(leader) {
+                    Some(location) => format!("0x{leader:x} ({location})"),
+                    None => format!("0x{leader:x}"),
+                })
+                .collect::<Vec<String>>();
+            writeln!(f, "Critical path: {}", critical_path.join(" -> "))?;
+        }
+        writeln!(f, "BCET: {} clock cycles", self.bcet)?;
+        match self.wcet_ns {
+            Some(wcet_ns) => write!(f, "WCET: {} clock cycles ({wcet_ns:.1} ns)", self.wcet),
+            None => write!(f, "WCET: {} clock cycles", self.wcet),
+        }
+    }
+}
+
+pub fn calculate_wcet(
+    cs: &Capstone,
+    arch_mode: &ArchMode,
+    sections: &[Instructions], // instructions of each text section
+    config: &Config,
+) -> Result<WcetReport, WcetError> {
+    build_control_flow(cs, arch_mode, sections, config)?.analyze(config)
+}
+
+/// Blocks of a binary, with the functions they belong to and their copies for each call site,
+/// from which the graph is built. Their latencies can be recomputed with another latency table
+/// by `set_latencies`, without disassembling the binary again.
+#[derive(Debug, Clone)]
+pub struct ControlFlow {
+    pub arch_mode: ArchMode,
+    pub blocks: BTreeMap<u64, Block>,           // leader -> block
+    pub recursive_functions: HashMap<u64, u64>, // function_address -> ret_address
+    pub fictious_map: HashMap<u64, u64>,        // fictious_address -> real_address
+    pub functions: BTreeMap<u64, String>,       // function_address -> name
+    pub latency_table: LatencyTable,            // table of the latencies of the blocks
+    pub timings: Vec<(String, f64)>, // (phase, milliseconds) of the building of the blocks
+    pub disassembly: Vec<(Instruction, Option<ExitJump>)>, // decoded instructions with their exit jump, without blocks, only with config.disasm_only
+    groups: HashMap<u64, Vec<String>>,                     // instruction_address -> capstone groups
+    base_latencies: HashMap<u64, u32>, // instruction_address -> latency before the added costs
+    hazards: HashSet<u64>,             // addresses of the instructions paying the hazard_penalty
+    summarized_calls: HashMap<u64, u64>, // call_insn_address -> function_address, of the summarized calls
+    summaries: Vec<Summary>,             // summarized functions, each after the functions it calls
+}
+
+impl ControlFlow {
+    /// Replaces the latencies of the blocks with the ones of `latency_table`, updating in place
+    /// the `graph` built from them: the latency of each instruction is resolved again, while
+    /// the costs added to it (e.g. the annotations, the stubs and the cache misses) are kept, as
+    /// well as the blocks and their edges. The summaries of `config.summarize_calls` are
+    /// computed again on the blocks of their functions.
+    pub fn set_latencies(
+        &mut self,
+        graph: &mut MappedGraph,
+        latency_table: &LatencyTable,
+        config: &Config,
+    ) -> Result<(), WcetError> {
+        let mut timer = PhaseTimer::new();
+        let config = Config {
+            latency_table: latency_table.clone(),
+            ..config.clone()
+        };
+        let mut base_latencies = HashMap::<u64, u32>::new(); // instruction_address -> latency
+
+        // the summary of a function uses the summaries of the functions it calls
+        let mut summaries = self.summaries.clone();
+        let mut summary_latencies = HashMap::<u64, (u32, u32)>::new(); // function_address -> (latency, new latency)
+        for summary in summaries.iter_mut() {
+            for block in summary.blocks.values_mut() {
+                self.reweight(block, &summary_latencies, &mut base_latencies, &config);
+            }
+            let latency = call_latency(summary.function, &summary.blocks, &config)?
+                .ok_or(WcetError::LatencyOverflow(summary.function))?;
+            summary_latencies.insert(summary.function, (summary.latency, latency));
+            summary.latency = latency;
+        }
+
+        let mut blocks = std::mem::take(&mut self.blocks);
+        for block in blocks.values_mut() {
+            self.reweight(block, &summary_latencies, &mut base_latencies, &config);
+        }
+        reweight_graph(graph, &blocks, config.cost_model())?;
+
+        self.blocks = blocks;
+        self.base_latencies.extend(base_latencies);
+        self.summaries = summaries;
+        self.latency_table = latency_table.clone();
+        timer.lap("reweighting");
+        self.timings = timer.timings;
+        Ok(())
+    }
+
+    /// Replaces the latencies of the instructions of `block` with the ones of the latency table
+    /// of `config`, and the summaries of its calls with the new ones in `summary_latencies`.
+    fn reweight(
+        &self,
+        block: &mut Block,
+        summary_latencies: &HashMap<u64, (u32, u32)>, // function_address -> (latency, new latency)
+        base_latencies: &mut HashMap<u64, u32>,       // instruction_address -> new latency
+        config: &Config,
+    ) {
+        let latency_table = &config.latency_table;
+        for instruction in block.instructions.iter_mut() {
+            if let Some((latency, new_latency)) = self
+                .summarized_calls
+                .get(&instruction.address)
+                .and_then(|function| summary_latencies.get(function))
+            {
+                instruction.latency = instruction.latency - latency + new_latency;
+            }
+
+            // the data skipped by capstone has no latency
+            let Some(base_latency) = self.base_latencies.get(&instruction.address) else {
+                continue;
+            };
+            let groups = self
+                .groups
+                .get(&instruction.address)
+                .map_or(&[][..], Vec::as_slice);
+            let mnemonic = rep_instruction(&instruction.mnemonic).unwrap_or(&instruction.mnemonic);
+            let latency = latency_table.get_latency(
+                self.arch_mode.arch,
+                config.syntax,
+                mnemonic,
+                &instruction.operands,
+                groups,
+            );
+            let new_base_latency = bounded_latency(instruction, latency, config);
+
+            let (hazard_penalty, new_hazard_penalty) =
+                match self.hazards.contains(&instruction.address) {
+                    true => (
+                        self.latency_table.hazard_penalty,
+                        latency_table.hazard_penalty,
+                    ),
+                    false => (0, 0),
+                };
+            instruction.latency = instruction.latency - base_latency - hazard_penalty
+                + new_base_latency
+                + new_hazard_penalty;
+            base_latencies.insert(instruction.address, new_base_latency);
+        }
+    }
+
+    /// Builds the graph of the blocks and computes the WCET report, with the latency table of
+    /// the blocks instead of the one of `config`.
+    pub fn analyze(&self, config: &Config) -> Result<WcetReport, WcetError> {
+        let config = Config {
+            latency_table: self.latency_table.clone(),
+            ..config.clone()
+        };
+        let mut timer = PhaseTimer::new();
+        let graph = build_graph(&self.blocks, config.cost_model())?;
+        timer.lap("graph building");
+
+        let mut report = self.analyze_with_graph(&graph, &config)?;
+        let phases = self.timings.len();
+        report.timings.splice(phases..phases, timer.timings);
+
+        Ok(report)
+    }
+
+    /// Computes the WCET report on the `graph` built from the blocks (see `build_graph` and
+    /// `set_latencies`), with the latency table of the blocks instead of the one of `config`.
+    pub fn analyze_with_graph(
+        &self,
+        graph: &MappedGraph,
+        config: &Config,
+    ) -> Result<WcetReport, WcetError> {
+        let config = Config {
+            latency_table: self.latency_table.clone(),
+            ..config.clone()
+        };
+
+        // only the listing of the instructions, to check the disassembly before the analysis
+        if config.disasm_only {
+            return Ok(WcetReport {
+                disassembly: self.disassembly.clone(),
+                timings: self.timings.clone(),
+                ..WcetReport::default()
+            });
+        }
+
+        let mut report = analyze_graph(
+            graph,
+            &self.blocks,
+            &self.recursive_functions,
+            &mut self.fictious_map.clone(),
+            &self.functions,
+            &config,
+        )?;
+        report.timings.splice(0..0, self.timings.iter().cloned());
+        if config.dump_blocks {
+            report.blocks = self.blocks.values().map(BlockDump::new).collect();
+        }
+
+        Ok(report)
+    }
+}
+
+/// Returns the latency of `instruction`, whose latency in the latency table is `latency`,
+/// before the costs added to it: the latency given for its address, if any, or the latency
+/// of the table times the iterations of its rep prefix.
+fn bounded_latency(instruction: &Instruction, latency: u32, config: &Config) -> u32 {
+    // the latency given for the address replaces the one of the latency table
+    if let Some(latency) = config.latency_overrides.latencies.get(&instruction.address) {
+        return *latency;
+    }
+    // a string instruction with a rep prefix is a loop of a single instruction, whose
+    // iterations depend on the data like the ones of the cycles
+    match rep_instruction(&instruction.mnemonic) {
+        Some(_) => {
+            let max_iterations = config.loop_bounds.rep_bound(instruction.address);
+            latency.saturating_mul(max_iterations.unwrap_or(1))
+        }
+        None => latency,
+    }
+}
+
+/// Decodes the instructions of `sections` and builds their blocks, or only lists the decoded
+/// instructions in `disassembly`, without blocks, if `config.disasm_only`.
+pub fn build_control_flow(
+    cs: &Capstone,
+    arch_mode: &ArchMode,
+    sections: &[Instructions], // instructions of each text section
+    config: &Config,
+) -> Result<ControlFlow, WcetError> {
+    if !SUPPORTED_ARCHS.contains(&arch_mode.arch) {
+        return Err(WcetError::UnsupportedJumps(arch_mode.arch));
+    }
+
+    let latency_table = &config.latency_table;
+    let mut timer = PhaseTimer::new();
 
-pub fn calculate_wcet(cs: &Capstone, arch_mode: &ArchMode, instructions: &Instructions) -> u32 {
     let mut leaders = HashSet::new();
     let mut jumps: HashMap<u64, ExitJump> = HashMap::new(); // jump_address -> ExitJump
     let mut call_map = HashMap::<u64, u64>::new(); // call_target_address -> return_addresses (ret)
-    let mut duplicated = HashMap::<(u64, u64), (u64, u64)>::new(); // (call_target_address, call_insn_address) -> (fictious address, return_address)
-    let mut counter = 0;
-    let mut vacant_ret = Vec::<u64>::new();
 
-    let mut insns_addresses = HashSet::new();
+    // sorted to allocate the fictious addresses of the duplicated blocks deterministically
+    let mut duplicated = BTreeMap::<(u64, u64), (u64, u64)>::new(); // (call_target_address, call_insn_address) -> (fictious address, return_address)
+    let mut stub_calls = HashMap::<u64, u32>::new(); // call_insn_address -> stub cost
+    let mut no_return_calls = HashSet::<u64>::new(); // call_insn_address
 
-    instructions.iter().for_each(|insn| {
-        insns_addresses.insert(insn.address());
-    });
+    // the functions never returning, given by address or known by name (e.g. abort)
+    let mut no_return = config.no_return.clone();
+    no_return.extend(
+        config
+            .functions
+            .iter()
+            .filter(|(_, name)| {
+                NO_RETURN_FUNCTIONS.contains(&name.as_str())
+                    || name
+                        .strip_prefix('_')
+                        .is_some_and(|name| NO_RETURN_FUNCTIONS.contains(&name))
+            })
+            .map(|(address, _)| *address),
+    );
 
-    // iteration to find all leaders and exit jumps
-    instructions.windows(2).for_each(|window| {
-        let instruction = &window[0];
-        let next_instruction = &window[1];
+    // decode each instruction once, finding its exit jump from the details of capstone
+    let mut instruction_groups = HashMap::<u64, Vec<String>>::new(); // instruction_address -> capstone groups
+    let mut base_latencies = HashMap::<u64, u32>::new(); // instruction_address -> latency
+    let mut indirect_calls = HashSet::<u64>::new(); // call_insn_address of the indirect calls
+    let mut decoded_sections = Vec::with_capacity(sections.len());
+    let mut code_ranges = Vec::<(u64, u64)>::new(); // (start_address, end_address)
+    for instructions in sections {
+        let instructions: &[Insn] = instructions; // iterated by reference
+
+        // the blocks are identified by their leader, so the addresses of the code regions
+        // can't be shared (e.g. relocatable sections that have not been assigned an address)
+        if let (Some(first), Some(last)) = (instructions.first(), instructions.last()) {
+            let range = (first.address(), last.address() + last.bytes().len() as u64);
+            if let Some(other) = code_ranges
+                .iter()
+                .find(|other| range.0 < other.1 && other.0 < range.1)
+            {
+                return Err(WcetError::OverlappingCode(other.0, range.0));
+            }
+            code_ranges.push(range);
+        }
+
+        let mut decoded = Vec::with_capacity(instructions.len()); // (instruction, exit_jump, next_address)
+        let mut it_remaining = 0; // instructions left in the current Thumb IT block
+        let mut delayed_jump = None; // jump taking effect after the current delay slot
+        for (index, insn) in instructions.iter().enumerate() {
+            // the last instruction has no successor, so the address following it is used as
+            // a sentinel (it is not the leader of any block)
+            let next_address = instructions
+                .get(index + 1)
+                .map_or(insn.address() + insn.bytes().len() as u64, |next_insn| {
+                    next_insn.address()
+                });
+
+            // the data skipped by capstone (.byte) has no details and is never executed
+            if insn.id().0 == 0 {
+                let mut instruction =
+                    Instruction::from_insn(insn, arch_mode, config.syntax, &[], latency_table);
+                instruction.latency = 0;
+                decoded.push((instruction, None, next_address));
+                continue;
+            }
+
+            let insn_detail = cs.insn_detail(insn).map_err(WcetError::DisassembleFailed)?;
+
+            // the group names are kept for the tables with group latencies, also to recompute
+            // the latencies with another table
+            let groups = insn_detail
+                .groups()
+                .filter_map(|group| cs.group_name(group))
+                .collect::<Vec<String>>();
+
+            // the instruction following a jump of MIPS or SPARC (its delay slot) is executed on
+            // every path, so the jump ends the block after it and falls through past it
+            let delay_slot = has_delay_slot(insn, arch_mode.arch) && index + 1 < instructions.len();
+            let fall_through_address = if delay_slot {
+                let slot = &instructions[index + 1];
+                instructions
+                    .get(index + 2)
+                    .map_or(slot.address() + slot.bytes().len() as u64, |after_slot| {
+                        after_slot.address()
+                    })
+            } else {
+                next_address
+            };
+
+            let mut exit_jump = get_exit_jump(
+                insn,
+                fall_through_address,
+                &insn_detail,
+                arch_mode.arch,
+                config.syntax,
+                &config.indirect_targets,
+            )
+            .map_err(WcetError::InvalidJump)?;
+            // an indirect call ending a block after its delay slot is found at the address of the slot
+            if exit_jump == Some(ExitJump::Indirect) && is_call(insn, &insn_detail, arch_mode.arch)
+            {
+                let address = match delay_slot {
+                    true => instructions[index + 1].address(),
+                    false => insn.address(),
+                };
+                indirect_calls.insert(address);
+            }
+            // a stub jumps to an external function, which returns to the caller of the stub
+            if exit_jump == Some(ExitJump::Indirect)
+                && config
+                    .stub_ranges
+                    .iter()
+                    .any(|range| range.contains(&insn.address()))
+                && !is_call(insn, &insn_detail, arch_mode.arch)
+            {
+                trace::emit(config, Event {
+                    event: "external_stub",
+                    address: Some(insn.address()),
+                    reason: format!(
+                        "Jump of the stub at address 0x{:x} to an external function considered as a return",
+                        insn.address()
+                    ),
+                    ..Event::default()
+                });
+                exit_jump = Some(ExitJump::Ret(0));
+            }
+
+            if let Some(jump) = delayed_jump.take() {
+                if exit_jump.is_some() {
+                    trace::emit(
+                        config,
+                        Event {
+                            event: "ignored_delay_slot_jump",
+                            address: Some(insn.address()),
+                            reason: format!(
+                                "Jump at address 0x{:x} in the delay slot of another jump ignored",
+                                insn.address()
+                            ),
+                            ..Event::default()
+                        },
+                    );
+                }
+                exit_jump = Some(jump);
+            } else if delay_slot && exit_jump.is_some() {
+                delayed_jump = exit_jump.take();
+            }
+
+            // the instructions of an IT block are always counted in the latency (the worst case),
+            // while their jumps can also fall through when the condition is false
+            if it_remaining > 0 {
+                it_remaining -= 1;
+                if let Some(jump) = exit_jump {
+                    if let ExitJump::Ret(_) = jump {
+                        trace::emit(config, Event {
+                            event: "conditional_return",
+                            address: Some(insn.address()),
+                            reason: format!(
+                                "Conditional return at address 0x{:x} inside an IT block -> only the fall through path is considered",
+                                insn.address()
+                            ),
+                            ..Event::default()
+                        });
+                    }
+                    exit_jump = conditional_exit_jump(jump, next_address);
+                }
+            }
+            it_remaining = it_remaining.max(it_block_length(insn, arch_mode.arch));
 
-        let insn_detail = cs.insn_detail(instruction).unwrap();
+            let mut instruction =
+                Instruction::from_insn(insn, arch_mode, config.syntax, &groups, latency_table);
+            if rep_instruction(&instruction.mnemonic).is_some() {
+                let max_iterations = config.loop_bounds.rep_bound(instruction.address);
+                let max_iterations = max_iterations.unwrap_or(1);
+                trace::emit(config, Event {
+                    event: "rep_bound",
+                    address: Some(instruction.address),
+                    reason: format!(
+                        "Found a rep prefixed instruction at address 0x{:x} -> {max_iterations} iterations considered \
+                        for the wcet calculation. If you want to change the value, please set the env var REP_0x{:x} \
+                        or its bound in the rep section of the loop bounds file",
+                        instruction.address, instruction.address
+                    ),
+                    ..Event::default()
+                });
+            }
+            instruction.latency = bounded_latency(&instruction, instruction.latency, config);
+            base_latencies.insert(instruction.address, instruction.latency);
+            if !groups.is_empty() {
+                instruction_groups.insert(instruction.address, groups);
+            }
+            decoded.push((instruction, exit_jump, next_address));
+        }
+        decoded_sections.push(decoded);
+    }
+    timer.lap("decoding");
+
+    let insns_addresses = decoded_sections
+        .iter()
+        .flatten()
+        .map(|(instruction, _, _)| instruction.address)
+        .collect::<HashSet<u64>>();
+
+    if insns_addresses.is_empty() {
+        return Err(WcetError::EmptyText);
+    }
+    if let Some(address) = config
+        .latency_overrides
+        .latencies
+        .keys()
+        .find(|address| !insns_addresses.contains(address))
+    {
+        return Err(WcetError::LatencyOverrideOutsideCode(*address));
+    }
+    info!(
+        "Disassembly: {} instructions in {} sections",
+        insns_addresses.len(),
+        decoded_sections.len()
+    );
+
+    // only the listing of the instructions, to check the disassembly before the analysis
+    if config.disasm_only {
+        return Ok(ControlFlow {
+            arch_mode: arch_mode.clone(),
+            blocks: BTreeMap::new(),
+            recursive_functions: HashMap::new(),
+            fictious_map: HashMap::new(),
+            functions: BTreeMap::new(),
+            latency_table: latency_table.clone(),
+            timings: timer.timings,
+            disassembly: decoded_sections
+                .into_iter()
+                .flatten()
+                .map(|(instruction, exit_jump, _)| (instruction, exit_jump))
+                .collect(),
+            groups: instruction_groups,
+            base_latencies,
+            hazards: HashSet::new(),
+            summarized_calls: HashMap::new(),
+            summaries: Vec::new(),
+        });
+    }
+
+    // the duplicated blocks get fictious leaders allocated above the end of the code, so they
+    // can't collide with the real leaders nor with each other
+    let code_end = code_ranges.iter().map(|range| range.1).max().unwrap_or(0);
+    let first_fictious_address = (code_end | 0xffff) + 1;
+    let mut next_fictious_address = first_fictious_address;
+
+    let mut unresolved_jumps = Vec::new(); // (jump_address, next_address) of the unresolved indirect jumps
 
-        let exit_jump = get_exit_jump(instruction, next_instruction, &insn_detail, arch_mode.arch);
+    // iteration to find all leaders and exit jumps
+    for (instruction, exit_jump, next_address) in decoded_sections.iter().flatten() {
+        let next_address = *next_address;
 
         // if the instruction is a jump, add the jump target address and the next instruction address to the leaders
         // Then add the jump instruction to the jumps map
-        if let Some(exit_jump) = exit_jump {
+        if let Some(exit_jump) = exit_jump.clone() {
             if !matches!(exit_jump, ExitJump::Call(_, _)) {
-                jumps.insert(instruction.address(), exit_jump.clone());
+                jumps.insert(instruction.address, exit_jump.clone());
                 // insert next instruction as leader
-                leaders.insert(next_instruction.address());
+                leaders.insert(next_address);
             }
 
             match exit_jump {
@@ -53,40 +646,78 @@ pub fn calculate_wcet(cs: &Capstone, arch_mode: &ArchMode, instructions: &Instru
                     leaders.insert(taken);
                     // not taken is the next instruction, so it is already inserted
                 }
+                ExitJump::IndirectResolved(ref targets) => {
+                    leaders.extend(targets);
+                }
+                // the targets are known once all the leaders are found
+                ExitJump::Indirect
+                    if config.indirect_policy == IndirectPolicy::Conservative
+                        || config.indirect_policy == IndirectPolicy::SameFunction
+                            && !indirect_calls.contains(&instruction.address) =>
+                {
+                    unresolved_jumps.push((instruction.address, next_address));
+                }
                 ExitJump::Indirect => {
-                    jumps.remove(&instruction.address());
-                    leaders.remove(&next_instruction.address());
-                    printwarning!(
-                        "Indirect jump instruction at address 0x{:x} ignored",
-                        instruction.address()
+                    jumps.remove(&instruction.address);
+                    leaders.remove(&next_address);
+                    trace::emit(
+                        config,
+                        Event {
+                            event: "ignored_indirect_jump",
+                            address: Some(instruction.address),
+                            reason: format!(
+                                "Indirect jump instruction at address 0x{:x} ignored",
+                                instruction.address
+                            ),
+                            ..Event::default()
+                        },
                     );
                 }
+                ExitJump::Call(target, _) if no_return.contains(&target) => {
+                    // the path ends with the call, the code after it is reached by other paths
+                    // only; the callee is not analyzed, but its stub cost is still added
+                    leaders.insert(next_address);
+                    no_return_calls.insert(instruction.address);
+                    if let Some(stub_cost) = config.stub_costs.get(&target) {
+                        stub_calls.insert(instruction.address, *stub_cost);
+                    }
+                }
+                ExitJump::Call(target, _) if config.stub_costs.contains_key(&target) => {
+                    // the call is kept inside the block, with the cost of the stub
+                    stub_calls.insert(instruction.address, config.stub_costs[&target]);
+                }
                 ExitJump::Call(target, _) => {
-                    if next_instruction.address() != target
-                        && target != instruction.address()
+                    if next_address != target
+                        && target != instruction.address
                         && insns_addresses.contains(&target)
                     {
                         leaders.insert(target);
                         if let hash_map::Entry::Vacant(e) = call_map.entry(target) {
-                            e.insert(next_instruction.address());
+                            e.insert(next_address);
                         } else {
-                            let fictious_address = instruction.address() << (1 + counter);
-
-                            if let hash_map::Entry::Vacant(e) =
-                                duplicated.entry((target, instruction.address()))
+                            if let btree_map::Entry::Vacant(e) =
+                                duplicated.entry((target, instruction.address))
                             {
-                                e.insert((fictious_address, next_instruction.address()));
-                                leaders.insert(fictious_address);
+                                e.insert((next_fictious_address, next_address));
+                                leaders.insert(next_fictious_address);
+                                next_fictious_address += 1;
                             }
-                            counter += 1;
                         }
-                        jumps.insert(instruction.address(), exit_jump);
+                        jumps.insert(instruction.address, exit_jump);
                         // insert next instruction as leader
-                        leaders.insert(next_instruction.address());
+                        leaders.insert(next_address);
                     } else {
-                        printwarning!(
-                            "External Call instruction at address 0x{:x} ignored",
-                            instruction.address()
+                        trace::emit(
+                            config,
+                            Event {
+                                event: "ignored_external_call",
+                                address: Some(instruction.address),
+                                reason: format!(
+                                    "External Call instruction at address 0x{:x} ignored",
+                                    instruction.address
+                                ),
+                                ..Event::default()
+                            },
                         );
                     }
                 }
@@ -94,76 +725,348 @@ pub fn calculate_wcet(cs: &Capstone, arch_mode: &ArchMode, instructions: &Instru
                 ExitJump::Next(_) => {}
             }
         }
-    });
+    }
+
+    // with the conservative policy, an indirect jump or call without targets can reach every
+    // leader, the entries of the functions included (e.g. an indirect tail call), or every
+    // candidate given by the user, so that the WCET is an over-approximation; a call also
+    // returns to the next instruction. With the same-function policy, a jump only reaches the
+    // leaders of its function after the start (e.g. the cases of a switch)
+    if !unresolved_jumps.is_empty() {
+        let function_starts = config
+            .functions
+            .keys()
+            .chain(call_map.keys())
+            .chain(code_ranges.iter().map(|range| &range.0))
+            .copied()
+            .collect::<BTreeSet<u64>>();
+        let candidates = config
+            .indirect_candidates
+            .iter()
+            .copied()
+            .collect::<BTreeSet<u64>>();
+        for (address, next_address) in unresolved_jumps {
+            let mut targets = if !candidates.is_empty() {
+                candidates.clone()
+            } else if config.indirect_policy == IndirectPolicy::SameFunction {
+                let start = function_starts.range(..=address).next_back().copied();
+                let end = function_starts.range(address + 1..).next().copied();
+                leaders
+                    .iter()
+                    .filter(|leader| start.is_none_or(|start| **leader > start))
+                    .filter(|leader| end.is_none_or(|end| **leader < end))
+                    .filter(|leader| insns_addresses.contains(leader))
+                    .copied()
+                    .collect::<BTreeSet<u64>>()
+            } else {
+                leaders
+                    .iter()
+                    .chain(&function_starts)
+                    .filter(|leader| insns_addresses.contains(leader))
+                    .copied()
+                    .collect::<BTreeSet<u64>>()
+            };
+            if indirect_calls.contains(&address) {
+                targets.insert(next_address);
+            }
+            trace::emit(config, Event {
+                event: "conservative_indirect_jump",
+                address: Some(address),
+                reason: format!(
+                    "Indirect jump instruction at address 0x{address:x} considered jumping to {} possible targets, \
+                    which may create many cycles. If you know its targets, please add them to the indirect targets file",
+                    targets.len()
+                ),
+                ..Event::default()
+            });
+            for target in targets
+                .iter()
+                .filter(|target| !insns_addresses.contains(target))
+            {
+                trace::emit(config, Event {
+                    event: "invalid_jump_target",
+                    address: Some(address),
+                    target: Some(*target),
+                    reason: format!(
+                        "The target 0x{target:x} of the jump at address 0x{address:x} is not the address of an instruction, the edge is ignored"
+                    ),
+                    ..Event::default()
+                });
+            }
+            jumps.insert(
+                address,
+                ExitJump::IndirectResolved(targets.iter().copied().collect()),
+            );
+            leaders.extend(targets);
+        }
+    }
+
+    // a target that is not the address of an instruction (e.g. in the middle of one, or in the
+    // data) has no block and its edge is dropped, which usually means that the code has been
+    // disassembled from a wrong address or that the target has been decoded wrongly
+    for (instruction, exit_jump, _) in decoded_sections.iter().flatten() {
+        let targets = match exit_jump {
+            Some(
+                ExitJump::UnconditionalAbsolute(target) | ExitJump::UnconditionalRelative(target),
+            ) => vec![*target],
+            Some(
+                ExitJump::ConditionalAbsolute { taken, .. }
+                | ExitJump::ConditionalRelative { taken, .. },
+            ) => vec![*taken],
+            Some(ExitJump::IndirectResolved(targets)) => targets.clone(),
+            _ => continue,
+        };
+        for target in targets {
+            if !insns_addresses.contains(&target) {
+                trace::emit(config, Event {
+                    event: "invalid_jump_target",
+                    address: Some(instruction.address),
+                    target: Some(target),
+                    reason: format!(
+                        "The target 0x{target:x} of the jump at address 0x{:x} is not the address of an instruction, the edge is ignored",
+                        instruction.address
+                    ),
+                    ..Event::default()
+                });
+            }
+        }
+    }
+
+    info!(
+        "Leader detection: {} leaders, {} jumps",
+        leaders.len(),
+        jumps.len()
+    );
+    timer.lap("leader detection");
 
-    // iterate through all instructions and create the basic blocks
-    let first_instruction = instructions.first().unwrap();
-    let mut current_block: Block = Block::new(first_instruction.into());
     // we need to keep the order of the blocks to have a consistent entry point of a condensed node
     let mut blocks = BTreeMap::<u64, Block>::new();
 
-    let mut graph = MappedGraph::new();
+    // iterate through all instructions of each section and create the basic blocks
+    for instructions in decoded_sections.iter() {
+        let mut current_block: Option<Block> = None;
 
-    // for each window of 2 instructions
-    instructions
-        .windows(2)
-        .enumerate()
-        .for_each(|(index, window)| {
-            let insn = &window[0];
-            let next_insn = &window[1];
-
-            // if the next instruction is a leader, push the current block to the list of blocks
-            if leaders.contains(&next_insn.address()) {
-                if let Some(exit_jump) = jumps.get(&insn.address()) {
-                    if call_map.contains_key(&current_block.leader) {
-                        vacant_ret.push(current_block.leader);
-                    }
+        for (index, (instruction, _, _)) in instructions.iter().enumerate() {
+            let address = instruction.address;
+            let instruction = instruction.clone();
+            match current_block.as_mut() {
+                Some(block) => block.add_instruction(instruction),
+                None => current_block = Some(Block::new(instruction)),
+            }
 
-                    if let ExitJump::Ret(_) = exit_jump {
-                        if let Some(targets) = call_map.get(&current_block.leader) {
-                            vacant_ret.pop().unwrap();
-                            current_block.set_exit_jump(ExitJump::Ret(*targets));
-                        } else if !vacant_ret.is_empty() {
-                            if let Some(ret) = call_map.get(&vacant_ret.pop().unwrap()) {
-                                current_block.set_exit_jump(ExitJump::Ret(*ret));
-                            }
-                        }
-                    } else if let ExitJump::Call(target, _) = exit_jump {
-                        if let Some((fictious_address, return_address)) =
-                            duplicated.get(&(*target, insn.address()))
-                        {
-                            current_block
-                                .set_exit_jump(ExitJump::Call(*fictious_address, *return_address));
-                        } else {
-                            current_block.set_exit_jump(exit_jump.clone());
-                        }
+            // the block ends before the next leader or with the last instruction of the section
+            let next_address = instructions
+                .get(index + 1)
+                .map(|(next_instruction, _, _)| next_instruction.address);
+            if next_address.is_some_and(|next_address| !leaders.contains(&next_address)) {
+                continue;
+            }
+            let mut block = current_block.take().unwrap();
+
+            if let Some(exit_jump) = jumps.get(&address) {
+                // the targets of the rets are resolved once the functions are known
+                if let ExitJump::Call(target, _) = exit_jump {
+                    if let Some((fictious_address, return_address)) =
+                        duplicated.get(&(*target, address))
+                    {
+                        block.set_exit_jump(ExitJump::Call(*fictious_address, *return_address));
                     } else {
-                        current_block.set_exit_jump(exit_jump.clone());
+                        block.set_exit_jump(exit_jump.clone());
                     }
                 } else {
-                    current_block.set_exit_jump(ExitJump::Next(next_insn.address()));
-                    if call_map.contains_key(&current_block.leader) {
-                        vacant_ret.push(current_block.leader);
-                    }
+                    block.set_exit_jump(exit_jump.clone());
                 }
+            } else if let Some(next_address) = next_address {
+                // a call never returning has no successor
+                if !no_return_calls.contains(&address) {
+                    block.set_exit_jump(ExitJump::Next(next_address));
+                }
+            }
 
-                // insert the current block to the list of blocks
-                blocks.insert(current_block.leader, current_block.clone());
-                current_block = Block::new(next_insn.into());
-            } else {
-                // push the instruction to the current block
-                current_block.add_instruction(next_insn.into());
+            // insert the current block to the list of blocks
+            blocks.insert(block.leader, block);
+        }
+    }
+
+    info!("Block building: {} blocks", blocks.len());
+
+    // add the cost of the stubbed callees to their call instructions
+    for block in blocks.values_mut() {
+        for instruction in block.instructions.iter_mut() {
+            if let Some(stub_cost) = stub_calls.get(&instruction.address) {
+                instruction.latency += stub_cost;
+            }
+        }
+    }
+
+    // add the annotated costs to the instructions at their address, in the block containing it
+    for (address, cost) in config.cost_annotations.costs.iter() {
+        let instruction = blocks
+            .range_mut(..=address)
+            .next_back()
+            .and_then(|(_, block)| {
+                block
+                    .instructions
+                    .iter_mut()
+                    .find(|instruction| instruction.address == *address)
+            })
+            .ok_or(WcetError::AnnotationOutsideCode(*address))?;
+        instruction.latency += cost;
+    }
+
+    // the instructions using the result of the previous one wait for it
+    let hazards = blocks
+        .values()
+        .flat_map(Block::hazards)
+        .collect::<HashSet<u64>>();
+    if latency_table.hazard_penalty > 0 {
+        let hazards = blocks
+            .values_mut()
+            .map(|block| block.add_hazard_penalties(latency_table.hazard_penalty))
+            .sum::<usize>();
+        info!("Hazards: {hazards} read after write dependencies");
+    }
+
+    // every line of the instruction cache touched by a block is a miss
+    if let Some(cache) = &config.cache {
+        let lines = blocks
+            .values_mut()
+            .map(|block| block.add_cache_miss_penalties(cache))
+            .sum::<usize>();
+        info!("Cache: {lines} lines touched by the blocks");
+    }
+
+    // functions are delimited by the symbols, or by the call targets for stripped binaries
+    let mut functions = config.functions.clone();
+    let function_starts = sections
+        .iter()
+        .filter_map(|instructions| instructions.first())
+        .map(|insn| insn.address())
+        .chain(call_map.keys().copied())
+        .collect::<Vec<u64>>();
+    for address in function_starts {
+        functions
+            .entry(address)
+            .or_insert_with(|| format!("sub_{address:x}"));
+    }
+    for block in blocks.values_mut() {
+        block.function = enclosing_function(&functions, block.leader);
+    }
+
+    timer.lap("block building");
+
+    // a jump to the start of another function is a tail call: the callee returns to the caller
+    // of the jumping function, so it takes its return address if it has none, or gets its own
+    // copy of the blocks otherwise (like a second call site). The return address of a function
+    // reached only through tail calls is known once its tail call is resolved, so the tail calls
+    // are resolved until no new return address is found; the remaining ones return from the
+    // analyzed code (0)
+    let mut tail_calls = blocks
+        .values()
+        .filter_map(|block| match block.exit_jump {
+            Some(ExitJump::UnconditionalAbsolute(target))
+            | Some(ExitJump::UnconditionalRelative(target)) => {
+                let (caller_start, _) = functions.range(..=block.leader).next_back()?;
+                (functions.contains_key(&target) && *caller_start != target).then_some((
+                    block.leader,
+                    *caller_start,
+                    target,
+                ))
             }
+            _ => None,
+        })
+        .collect::<Vec<(u64, u64, u64)>>(); // (jump_block_leader, caller_start, target)
+    let mut resolve_unknown = false;
+    while !tail_calls.is_empty() {
+        let pending = tail_calls.len();
+        let mut index = 0;
+        while index < tail_calls.len() {
+            let (leader, caller_start, target) = tail_calls[index];
+            let return_address = match call_map.get(&caller_start) {
+                Some(return_address) => *return_address,
+                None if resolve_unknown => 0,
+                None => {
+                    index += 1;
+                    continue;
+                }
+            };
+            tail_calls.swap_remove(index);
 
-            // last instruction pair -> add last instruction to block and push block (exit_jump is None)
-            if index == instructions.len() - 2 {
-                current_block.add_instruction(next_insn.into());
-                blocks.insert(current_block.leader, current_block.clone());
+            match call_map.get(&target) {
+                None if return_address != 0 => {
+                    call_map.insert(target, return_address);
+                }
+                Some(target_return_address) if *target_return_address != return_address => {
+                    duplicated.insert((target, leader), (next_fictious_address, return_address));
+                    if let Some(block) = blocks.get_mut(&leader) {
+                        block.modify_targets(next_fictious_address, target);
+                    }
+                    next_fictious_address += 1;
+                }
+                _ => {}
             }
-        });
+        }
+        // without progress, the callers of the remaining tail calls are never called
+        resolve_unknown = tail_calls.len() == pending;
+    }
+
+    // a ret returns to the call site that first reached its function (call_map), while every
+    // other call site gets its own copy of the function blocks returning to it (duplicated)
+    for block in blocks.values_mut() {
+        if let Some(ExitJump::Ret(0)) = block.exit_jump {
+            let function_start = functions.range(..=block.leader).next_back();
+            if let Some(return_address) = function_start.and_then(|(start, _)| call_map.get(start))
+            {
+                block.set_exit_jump(ExitJump::Ret(*return_address));
+            }
+        }
+    }
+
+    // with the summaries, a function called from more than one site is analyzed once, and the
+    // other call sites take the WCET of the call as latency instead of a copy of its blocks
+    let mut summarized_count = 0;
+    let mut summarized_calls = HashMap::new();
+    let mut summaries = Vec::new();
+    if config.summarize_calls {
+        let call_sites = duplicated
+            .iter()
+            .filter(|((_, call_address), _)| {
+                matches!(jumps.get(call_address), Some(ExitJump::Call(_, _)))
+            })
+            .map(|((call_target, _), (fictious_address, _))| (*fictious_address, *call_target))
+            .collect::<HashMap<u64, u64>>();
+        let call_leaders = blocks
+            .values()
+            .filter(|block| {
+                matches!(block.exit_jump, Some(ExitJump::Call(target, _)) if call_sites.contains_key(&target))
+            })
+            .map(|block| block.leader)
+            .collect::<Vec<u64>>();
+
+        let mut call_summaries = CallSummaries {
+            call_map: &call_map,
+            call_sites,
+            summaries: HashMap::new(),
+            summarized: HashSet::new(),
+            summarized_calls: HashMap::new(),
+            order: Vec::new(),
+            first_fictious_address,
+            config,
+        };
+        for leader in call_leaders {
+            call_summaries.summarize_call(&mut blocks, leader)?;
+        }
+
+        summarized_calls = call_summaries.summarized_calls;
+        summaries = call_summaries.order;
+        let summarized = call_summaries.summarized;
+        summarized_count = summarized.len();
+        duplicated.retain(|_, (fictious_address, _)| !summarized.contains(fictious_address));
+    }
 
     let mut recursive_functions = HashMap::<u64, u64>::new();
     let mut fictious_map = HashMap::<u64, u64>::new(); // real_address -> fictious address
+    let duplicated_count = duplicated.len();
 
     // add duplicated blocks to the graph for the call targets
     for ((call_target, _), (fictious_address, ret_address)) in duplicated {
@@ -171,27 +1074,221 @@ pub fn calculate_wcet(cs: &Capstone, arch_mode: &ArchMode, instructions: &Instru
             let mut new_block = block.clone();
 
             if let Some(ExitJump::Ret(_)) = new_block.exit_jump {
+                fictious_map.insert(fictious_address, new_block.leader);
                 new_block.leader = fictious_address;
                 new_block.set_exit_jump(ExitJump::Ret(ret_address));
                 blocks.insert(new_block.leader, new_block.clone());
             } else {
-                let mut visited_nodes = HashMap::<u64, u64>::new();
-
-                duplicate(
+                let mut duplicator = Duplicator {
+                    visited_nodes: HashMap::new(),
+                    fictious_map: &mut fictious_map,
+                    next_fictious_address: &mut next_fictious_address,
+                    max_depth: config.max_depth,
+                    recursive_functions: &mut recursive_functions,
+                };
+                let call_target_address = new_block.leader;
+                duplicator.duplicate(
                     &mut blocks,
-                    &mut new_block.clone(),
+                    new_block,
                     fictious_address,
                     ret_address,
-                    *call_map.get(&call_target).unwrap(),
-                    &mut recursive_functions,
-                    new_block.leader,
-                    &mut visited_nodes,
-                    &mut fictious_map,
-                );
+                    *call_map
+                        .get(&call_target)
+                        .ok_or(WcetError::UnknownReturnAddress(call_target))?,
+                    call_target_address,
+                )?;
+            }
+        }
+    }
+
+    info!(
+        "Duplication: {} call sites, {} duplicated blocks, {} recursive functions, {} summarized calls",
+        duplicated_count,
+        fictious_map.len(),
+        recursive_functions.len(),
+        summarized_count
+    );
+
+    if let Some(entry) = config
+        .entries
+        .iter()
+        .chain(&config.entry)
+        .find(|entry| !blocks.contains_key(entry))
+    {
+        return Err(WcetError::EntryNotFound(*entry));
+    }
+
+    // analyze only the blocks reachable from the entry points requested by the user or, if
+    // none, from the entry point of the program and the functions of the symbol table (which
+    // can be reached through indirect calls, e.g. main), dropping padding and data decoded as code
+    let entries = match config.entry {
+        _ if !config.entries.is_empty() => config.entries.clone(),
+        Some(entry) => vec![entry],
+        None => match config.program_entry {
+            Some(program_entry) if blocks.contains_key(&program_entry) => {
+                let mut entries = vec![program_entry];
+                entries.extend(config.functions.keys().filter(|f| blocks.contains_key(f)));
+                entries
             }
+            _ => vec![],
+        },
+    };
+    if let Some(&entry) = entries.first() {
+        let mut reachable = reachable_blocks(&blocks, &entries);
+
+        // the original blocks of a recursive function are not connected to its callers,
+        // so they are kept if one of its copies is reachable
+        let recursive_entries = recursive_functions
+            .keys()
+            .filter(|function| {
+                reachable
+                    .iter()
+                    .any(|leader| fictious_map.get(leader).unwrap_or(leader) == *function)
+            })
+            .copied()
+            .collect::<Vec<u64>>();
+        reachable.extend(reachable_blocks(&blocks, &recursive_entries));
+
+        let blocks_count = blocks.len();
+        blocks.retain(|leader, _| reachable.contains(leader));
+        if blocks.len() < blocks_count {
+            trace::emit(
+                config,
+                Event {
+                    event: "unreachable_blocks",
+                    entry: Some(entry),
+                    reason: format!(
+                        "Removed {} blocks not reachable from the entry point 0x{entry:x}",
+                        blocks_count - blocks.len()
+                    ),
+                    ..Event::default()
+                },
+            );
+        }
+    }
+
+    timer.lap("duplication");
+
+    if config.merge_blocks {
+        // the entries and the functions are looked up by the leaders of their blocks
+        let keep = config
+            .entries
+            .iter()
+            .chain(&config.entry)
+            .chain(&config.program_entry)
+            .chain(functions.keys())
+            .chain(recursive_functions.keys())
+            .copied()
+            .collect::<HashSet<u64>>();
+        let blocks_count = blocks.len();
+        let merged = merge_straight_line_blocks(&mut blocks, &keep);
+        info!(
+            "Block merging: {blocks_count} blocks before, {} blocks after ({merged} merged)",
+            blocks.len()
+        );
+    }
+
+    Ok(ControlFlow {
+        arch_mode: arch_mode.clone(),
+        blocks,
+        recursive_functions,
+        fictious_map,
+        functions,
+        latency_table: latency_table.clone(),
+        timings: timer.timings,
+        disassembly: Vec::new(),
+        groups: instruction_groups,
+        base_latencies,
+        hazards,
+        summarized_calls,
+        summaries,
+    })
+}
+
+/// Measures the durations of the consecutive phases of the analysis.
+struct PhaseTimer {
+    start: Instant,              // end of the previous phase
+    timings: Vec<(String, f64)>, // (phase, milliseconds)
+}
+
+impl PhaseTimer {
+    fn new() -> Self {
+        PhaseTimer {
+            start: Instant::now(),
+            timings: Vec::new(),
         }
     }
 
+    /// Records the end of `phase`, which started at the end of the previous one.
+    fn lap(&mut self, phase: &str) {
+        let now = Instant::now();
+        self.timings
+            .push((phase.to_string(), (now - self.start).as_secs_f64() * 1000.0));
+        self.start = now;
+    }
+}
+
+/// Merges each block ending with an `ExitJump::Next` into the block it falls through to, when
+/// the latter has no other predecessor, belongs to the same function and is not in `keep`:
+/// the merged block has the instructions (and so the latency) of both and the exit jump of the
+/// second one, so the WCET doesn't change. Returns the number of blocks merged away.
+pub fn merge_straight_line_blocks(blocks: &mut BTreeMap<u64, Block>, keep: &HashSet<u64>) -> usize {
+    let mut predecessors = HashMap::<u64, usize>::new(); // leader -> incoming edges
+    for block in blocks.values() {
+        for target in block.get_targets() {
+            *predecessors.entry(target).or_default() += 1;
+        }
+    }
+
+    let mut merged = 0;
+    let leaders = blocks.keys().copied().collect::<Vec<u64>>();
+    for leader in leaders {
+        // the block may have been merged into a previous one
+        let Some(mut block) = blocks.remove(&leader) else {
+            continue;
+        };
+        while let Some(ExitJump::Next(next)) = block.exit_jump {
+            let mergeable = next != block.leader
+                && predecessors.get(&next) == Some(&1)
+                && !keep.contains(&next)
+                && blocks
+                    .get(&next)
+                    .is_some_and(|next_block| next_block.function == block.function);
+            if !mergeable {
+                break;
+            }
+
+            let next_block = blocks.remove(&next).unwrap();
+            block.instructions.extend(next_block.instructions);
+            block.exit_jump = next_block.exit_jump;
+            merged += 1;
+        }
+        blocks.insert(leader, block);
+    }
+
+    merged
+}
+
+/// Builds the control flow graph of the blocks: each block is connected to the blocks of its
+/// targets, with the cost of the target block plus the cost of the control transfer as weight.
+/// With a `LatencyTable`, the cost is the latency of the target block, plus the
+/// `branch_penalty` for the taken conditional jumps and the `call_latency` and `ret_latency`
+/// for calls and returns.
+///
+/// The blocks must satisfy the following invariants:
+/// - each block is keyed by its `leader`, the address of its first instruction
+/// - `exit_jump` is the jump ending the block (`None` for the last block of a section) and
+///   `get_targets` returns the leaders of its successors; targets without a block are ignored
+/// - `ExitJump::Ret(0)` marks a return with unknown target and `ExitJump::Call` targets the
+///   leader of the called function, whose ret blocks return to the call return address
+///
+/// Fails if the cost model returns a negative or non-finite cost.
+pub fn build_graph(
+    blocks: &BTreeMap<u64, Block>,
+    cost_model: &dyn CostModel,
+) -> Result<MappedGraph, WcetError> {
+    let mut graph = MappedGraph::new();
+
     // add edges to the graph (it also adds the nodes)
     for block in blocks.values() {
         for target in block.get_targets() {
@@ -199,58 +1296,290 @@ pub fn calculate_wcet(cs: &Capstone, arch_mode: &ArchMode, instructions: &Instru
                 graph.add_edge(
                     block.clone(),
                     target_block.clone(),
-                    target_block.get_latency() as f32,
+                    block.edge_latency(target_block, cost_model)?,
                 );
             }
         }
     }
 
-    let graph_dir = crate::GRAPHS_DIR;
-    if !std::path::Path::new(graph_dir).exists() {
-        std::fs::create_dir(graph_dir).expect("Unable to create graph directory");
-    } else {
-        // remove old files
-        for entry in std::fs::read_dir(graph_dir).expect("Unable to read graph directory") {
-            let entry = entry.expect("Unable to read graph directory");
-            let path = entry.path();
-            if path.is_file() {
-                std::fs::remove_file(path).expect("Unable to remove file");
+    // the blocks without edges (e.g. a function made of a single block) are added after the
+    // others, to keep the order of the nodes
+    for block in blocks.values() {
+        graph.add_node(block.clone());
+    }
+
+    Ok(graph)
+}
+
+/// Updates in place the graph built by `build_graph` from the same blocks, with their new
+/// latencies: each node takes its block again and each edge its new weight.
+pub fn reweight_graph(
+    graph: &mut MappedGraph,
+    blocks: &BTreeMap<u64, Block>,
+    cost_model: &dyn CostModel,
+) -> Result<(), WcetError> {
+    let mut weights = HashMap::<(u64, u64), f64>::new(); // (source, target) -> weight
+    for block in blocks.values() {
+        if let Some(&node_index) = graph.node_index_map.get(&block.leader) {
+            graph.graph[node_index] = block.clone();
+        }
+        for target in block.get_targets() {
+            if let Some(target_block) = blocks.get(&target) {
+                let weight = block.edge_latency(target_block, cost_model)?;
+                let edge_weight = weights.entry((block.leader, target)).or_insert(weight);
+                *edge_weight = edge_weight.max(weight);
             }
         }
     }
 
-    let mut dot_file =
-        std::fs::File::create(format!("{graph_dir}/graph.dot")).expect("Unable to create file");
-    let digraph = graph.to_dot_graph();
-    dot_file
-        .write_all(digraph.as_bytes())
-        .expect("Unable to write dot file");
+    for (edge, weight) in weights {
+        if let Some(&edge_index) = graph.edge_index_map.get(&edge) {
+            graph.graph[edge_index] = weight;
+        }
+    }
+
+    Ok(())
+}
+
+/// Condenses the cycles of the graph built by `build_graph` and computes the WCET report.
+/// Blocks not built from a binary can be analyzed with empty `recursive_functions`,
+/// `fictious_map` and `functions`.
+pub fn analyze_graph(
+    graph: &MappedGraph,
+    blocks: &BTreeMap<u64, Block>,
+    recursive_functions: &HashMap<u64, u64>, // function_address -> ret_address
+    fictious_map: &mut HashMap<u64, u64>,    // fictious_address -> real_address
+    functions: &BTreeMap<u64, String>,       // function_address -> name
+    config: &Config,
+) -> Result<WcetReport, WcetError> {
+    config.prepare_graphs_dir()?;
+
+    let mut timer = PhaseTimer::new();
+    let stats = graph.stats();
+    info!("Graph: {} nodes, {} edges", stats.nodes, stats.edges);
+
+    config.write_graph("graph.dot", || graph.to_dot_graph())?;
+    config.write_graph("graph.json", || graph.to_json())?;
+    config.write_graph("graph.graphml", || graph.to_graphml())?;
 
-    let mut condensed_entry_node_latency = HashMap::<u64, u32>::new(); // block_leader -> latency
-    let mut latency_map = HashMap::<u64, u32>::new(); // ret_address -> latency
+    let mut condensed_entry_node_latency = HashMap::<u64, f64>::new(); // block_leader -> latency
+    let mut latency_map = HashMap::<u64, f64>::new(); // ret_address -> latency
+    let mut cycles = Vec::<(u64, u32)>::new(); // (cycle_entry_address, max_cycles)
+    let mut loop_latencies = BTreeMap::<u64, (u64, u32, u64)>::new(); // cycle_entry_address -> (iteration_latency, max_cycles, total_latency)
+    let mut non_terminating_loops = Vec::<u64>::new(); // cycle_entry_address
+    let mut loops = Vec::<LoopListing>::new(); // cycles listed with config.list_loops
 
     // condense the graph
     let condensed_graph = condensate_graph(
         graph.clone(),
         &mut condensed_entry_node_latency,
-        &blocks,
-        &recursive_functions,
+        blocks,
+        recursive_functions,
         &mut latency_map,
-        &mut fictious_map,
-    );
+        fictious_map,
+        &mut cycles,
+        &mut loop_latencies,
+        &mut non_terminating_loops,
+        &mut loops,
+        config,
+        0,
+    )?;
+    non_terminating_loops.sort();
+    non_terminating_loops.dedup();
+    for address in non_terminating_loops.iter() {
+        info!("Non-terminating loop 0x{address:x}: the WCET includes a single iteration");
+    }
 
-    let mut dot_file = std::fs::File::create(format!("{graph_dir}/condensed_graph.dot"))
-        .expect("Unable to create file");
-    let digraph = condensed_graph.to_dot_graph();
-    dot_file
-        .write_all(digraph.as_bytes())
-        .expect("Unable to write dot file");
+    config.write_graph("condensed_graph.dot", || condensed_graph.to_dot_graph())?;
+    config.write_graph("condensed_graph.json", || condensed_graph.to_json())?;
+    config.write_graph("condensed_graph.graphml", || condensed_graph.to_graphml())?;
 
     // find all the entry nodes of the condesed graph
     let condensed_graph_nodes = condensed_graph.get_nodes();
+    info!(
+        "Condensation: {} nodes, {} cycles",
+        condensed_graph_nodes.len(),
+        cycles.len()
+    );
+    timer.lap("condensation");
+
+    // the loops have been listed during the condensation, to annotate their bounds
+    if config.list_loops {
+        return Ok(WcetReport {
+            loops,
+            ..WcetReport::default()
+        });
+    }
+
+    // a cycle without a bound makes the WCET unbounded, not a single iteration, except the
+    // cycles that never terminate, whose single iteration is the expected WCET
+    if config.strict_bounds {
+        let loop_names = loop_names(blocks, fictious_map);
+        let mut unbounded_cycles = cycles
+            .iter()
+            .map(|(cycle_entry_address, _)| *cycle_entry_address)
+            .filter(|address| !non_terminating_loops.contains(address))
+            .filter(|address| {
+                // the return cycle of a recursive function is bounded by its recursions
+                let recursive_function =
+                    blocks.get(address).and_then(|block| match block.exit_jump {
+                        Some(ExitJump::Ret(ret_address)) => recursive_functions
+                            .iter()
+                            .find(|(_, recursive_ret_address)| {
+                                **recursive_ret_address == ret_address
+                            })
+                            .map(|(function, _)| *function),
+                        _ => None,
+                    });
+                match recursive_function {
+                    Some(function) => config.loop_bounds.recursive_bound(function).is_none(),
+                    None => config
+                        .loop_bounds
+                        .cycle_bound(*address, loop_names.get(address))
+                        .is_none(),
+                }
+            })
+            .collect::<Vec<u64>>();
+        unbounded_cycles.sort();
+        unbounded_cycles.dedup();
+        if !unbounded_cycles.is_empty() {
+            return Err(WcetError::UnboundedCycles(unbounded_cycles));
+        }
+    }
+
+    // WCET of each function: longest path from its entry, cutting the edges of its own returns
+    let mut function_wcets = Vec::<(String, u64)>::new();
+    for (address, name) in functions.iter() {
+        let Some(entry_node) = condensed_graph_nodes
+            .iter()
+            .find(|node| node.iter().any(|block| block.leader == *address))
+        else {
+            continue;
+        };
+
+        let mut function_graph = condensed_graph.clone();
+        for node in condensed_graph_nodes.iter() {
+            let is_function_ret = node.iter().any(|block| {
+                block.function == *name && matches!(block.exit_jump, Some(ExitJump::Ret(_)))
+            });
+            if is_function_ret {
+                for (source, target, _) in function_graph.edges_directed(node, Outgoing) {
+                    function_graph.remove_edge(&source, &target);
+                }
+            }
+        }
+
+        let entry_node_latency = match condensed_entry_node_latency.get(&entry_node[0].leader) {
+            Some(latency) => *latency,
+            None => entry_node[0].cost(config.cost_model())?,
+        };
+        let max_path_latency = longest_path_to_exits(&function_graph, entry_node)?;
+
+        function_wcets.push((
+            name.clone(),
+            weight_to_latency(entry_node_latency + max_path_latency, *address)?,
+        ));
+    }
+
+    let condensation = Condensation {
+        graph: condensed_graph,
+        entry_node_latency: condensed_entry_node_latency,
+        latency_map,
+        cycles,
+    };
+
+    // the entries requested separately share the condensed graph, the report of the one with
+    // the highest WCET is completed with the results not depending on the entry
+    let mut report = if config.entries.is_empty() {
+        entry_report(
+            graph,
+            &condensation,
+            recursive_functions,
+            fictious_map,
+            config.entry,
+            config,
+        )?
+    } else {
+        let mut entry_reports = Vec::with_capacity(config.entries.len());
+        for entry in config.entries.iter() {
+            let report = entry_report(
+                graph,
+                &condensation,
+                recursive_functions,
+                fictious_map,
+                Some(*entry),
+                config,
+            )?;
+            entry_reports.push((*entry, report));
+        }
+
+        let (_, worst_report) = entry_reports
+            .iter()
+            .max_by_key(|(_, report)| report.wcet)
+            .expect("No entry requested");
+        WcetReport {
+            entry_wcets: entry_reports
+                .iter()
+                .map(|(entry, report)| (*entry, report.wcet))
+                .collect(),
+            entry_reports: entry_reports.clone(),
+            ..worst_report.clone()
+        }
+    };
+
+    // the recursions of a function without a bound are counted once, as in the condensation
+    let mut recursive_bounds = recursive_functions
+        .iter()
+        .map(|(function, ret_address)| {
+            let max_recursions = config.loop_bounds.recursive_bound(*function).unwrap_or(1);
+            (*function, *ret_address, max_recursions)
+        })
+        .collect::<Vec<(u64, u64, u32)>>();
+    recursive_bounds.sort();
+    for (function, ret_address, max_recursions) in recursive_bounds.iter() {
+        info!(
+            "Recursive function 0x{function:x}: returning to 0x{ret_address:x}, {max_recursions} recursions"
+        );
+    }
+    timer.lap("path computation");
+
+    report.recursive_functions = recursive_bounds
+        .iter()
+        .map(|(function, _, _)| *function)
+        .collect();
+    report.recursive_bounds = recursive_bounds;
+    report.cycles = condensation.cycles;
+    report.loop_latencies = loop_latencies;
+    report.non_terminating_loops = non_terminating_loops;
+    report.function_wcets = function_wcets;
+    report.stats = stats;
+    report.timings = timer.timings;
+
+    Ok(report)
+}
+
+/// Result of the condensation of the cycles of the graph, shared by the entries analyzed.
+struct Condensation {
+    graph: MappedCondensedGraph,
+    entry_node_latency: HashMap<u64, f64>, // block_leader -> latency
+    latency_map: HashMap<u64, f64>,        // ret_address -> latency
+    cycles: Vec<(u64, u32)>,               // (cycle_entry_address, max_cycles)
+}
+
+/// Computes the WCET, the BCET and the critical path from `entry`, or from every entry node of
+/// the condensed graph if None. The other fields of the report are left empty.
+fn entry_report(
+    graph: &MappedGraph,
+    condensation: &Condensation,
+    recursive_functions: &HashMap<u64, u64>, // function_address -> ret_address
+    fictious_map: &HashMap<u64, u64>,        // fictious_address -> real_address
+    entry: Option<u64>,
+    config: &Config,
+) -> Result<WcetReport, WcetError> {
+    let condensed_graph_nodes = condensation.graph.get_nodes();
     let mut entry_nodes = condensed_graph_nodes
         .iter()
-        .filter(|node| condensed_graph.edges_directed(node, Incoming).is_empty())
+        .filter(|node| condensation.graph.edges_directed(node, Incoming).is_empty())
         .collect::<Vec<_>>();
 
     let graph_nodes = graph.get_nodes();
@@ -259,18 +1588,55 @@ pub fn calculate_wcet(cs: &Capstone, arch_mode: &ArchMode, instructions: &Instru
         .filter(|node| graph.edges_directed(node, Incoming).is_empty())
         .collect::<Vec<_>>();
 
-    //filtering entry nodes excluding false ones, which can be created by exit blocks' removals
+    // filtering entry nodes excluding false ones, which can be created by exit blocks' removals;
+    // the requested entry, or else the entry point of the program, can be the header of a loop,
+    // whose node contains its back edges
+    let root = entry.or(config.program_entry);
+    let contains_entry =
+        |node: &Vec<Block>| root.is_some_and(|root| node.iter().any(|block| block.leader == root));
     entry_nodes.retain(|node| {
         original_entry_nodes.contains(&&node[0])
             || recursive_functions.contains_key(&node[0].leader)
+            || contains_entry(node)
     });
 
-    let mut wcet: u32 = 0;
-    let mut recursive_delay: u32 = 0;
+    // consider only the entry node requested, if any
+    if let Some(entry) = entry {
+        if !entry_nodes.iter().any(|node| contains_entry(node)) {
+            trace::emit(
+                config,
+                Event {
+                    event: "entry_not_root",
+                    entry: Some(entry),
+                    reason: format!(
+                        "The entry point 0x{entry:x} is not an entry node of the graph"
+                    ),
+                    ..Event::default()
+                },
+            );
+        }
+        entry_nodes.retain(|node| {
+            contains_entry(node) || recursive_functions.contains_key(&node[0].leader)
+        });
+    }
+
+    let mut wcet: f64 = 0.0;
+    let mut worst_entry = entry_nodes.first().map_or(0, |node| node[0].leader);
+    let mut bcet: u64 = 0;
+    let mut recursive_delay: f64 = 0.0;
     let mut count = 0;
+    let mut entry_wcets = Vec::<(u64, u64)>::new();
+    let mut critical_path = Vec::<u64>::new();
 
     if entry_nodes.is_empty() {
-        printwarning!("No entry nodes found");
+        trace::emit(
+            config,
+            Event {
+                event: "no_entry_nodes",
+                reason: "No entry nodes found".to_string(),
+                ..Event::default()
+            },
+        );
     } else if entry_nodes.len() > 1 {
         for entry in entry_nodes.clone() {
             if !recursive_functions.contains_key(&entry[0].leader) {
@@ -278,62 +1644,385 @@ pub fn calculate_wcet(cs: &Capstone, arch_mode: &ArchMode, instructions: &Instru
             }
         }
         if count > 1 {
-            printwarning!("More than one entry node found");
+            trace::emit(
+                config,
+                Event {
+                    event: "multiple_entry_nodes",
+                    reason: "More than one entry node found".to_string(),
+                    ..Event::default()
+                },
+            );
         }
     }
 
     for entry_node in entry_nodes.clone() {
-        let entry_node_latency = match condensed_entry_node_latency.get(&entry_node[0].leader) {
+        let entry_node_latency = match condensation.entry_node_latency.get(&entry_node[0].leader) {
             Some(latency) => *latency,
-            None => entry_node[0].get_latency(),
+            None => entry_node[0].cost(config.cost_model())?,
         };
 
-        let max_path_latency = condensed_graph.longest_path(entry_node).unwrap() as u32;
+        let max_path_latency = longest_path_to_exits(&condensation.graph, entry_node)?;
 
         if let Some(ret_address) = recursive_functions.get(&entry_node[0].leader) {
-            recursive_delay += *latency_map.get(ret_address).unwrap();
+            recursive_delay += *condensation
+                .latency_map
+                .get(ret_address)
+                .ok_or(WcetError::RecursionNotCondensed(entry_node[0].leader))?;
         } else {
-            if count > 1 {
-                println!(
-                    "WCET: {} clock cycles for the graph starting at entry node: 0x{:x}",
-                    entry_node_latency + max_path_latency,
-                    entry_node[0].leader
-                );
-            }
+            let entry_wcet = entry_node_latency + max_path_latency;
+            entry_wcets.push((
+                entry_node[0].leader,
+                weight_to_latency(entry_wcet, entry_node[0].leader)?,
+            ));
 
             //calculating the wcet only if the entry node is not a recursive function
-            wcet = wcet.max(entry_node_latency + max_path_latency);
+            if entry_wcet >= wcet {
+                worst_entry = entry_node[0].leader;
+                bcet = best_case_latency(
+                    graph,
+                    &entry_node[0],
+                    condensation.cycles.as_slice(),
+                    fictious_map,
+                    config,
+                )?;
+                critical_path = condensation
+                    .graph
+                    .longest_path_trace(entry_node)
+                    .map_err(|_| WcetError::NegativeCycle(entry_node[0].leader, false))?
+                    .iter()
+                    // report the real address of the duplicated blocks
+                    .map(|block| *fictious_map.get(&block.leader).unwrap_or(&block.leader))
+                    .collect();
+            }
+            wcet = wcet.max(entry_wcet);
         }
     }
 
-    wcet += recursive_delay;
+    let wcet = weight_to_latency(wcet + recursive_delay, worst_entry)?;
+    info!(
+        "WCET summation: {} entry nodes, {} cycles of recursive delay",
+        entry_nodes.len(),
+        recursive_delay
+    );
+
+    if bcet > wcet {
+        trace::emit(config, Event {
+            event: "bcet_above_wcet",
+            reason: format!(
+                "The BCET ({bcet}) is greater than the WCET ({wcet}): the minimum iterations of the cycles exceed their bounds"
+            ),
+            ..Event::default()
+        });
+    }
 
-    wcet
+    Ok(WcetReport {
+        wcet,
+        bcet,
+        entry_wcets,
+        source_lines: critical_path
+            .iter()
+            .filter_map(|leader| {
+                source_location(&config.line_table, *leader)
+                    .map(|location| (*leader, location.clone()))
+            })
+            .collect(),
+        critical_path,
+        wcet_ns: config
+            .clock_mhz
+            .map(|clock_mhz| wcet as f64 / (clock_mhz * 1e6) * 1e9),
+        ..WcetReport::default()
+    })
 }
 
-fn duplicate(
-    blocks: &mut BTreeMap<u64, Block>,
-    source: &mut Block,
+/// Returns the best-case execution time from `entry`: the shortest path to an exit block, where
+/// each cycle whose header is on the path is iterated `config.min_cycles` times. The recursive
+/// functions are not repeated.
+fn best_case_latency(
+    graph: &MappedGraph,
+    entry: &Block,
+    cycles: &[(u64, u32)],            // (cycle_entry_address, max_cycles)
+    fictious_map: &HashMap<u64, u64>, // fictious_address -> real_address
+    config: &Config,
+) -> Result<u64, WcetError> {
+    let Some((path_latency, path)) = graph.shortest_path_to_exits(entry) else {
+        return weight_to_latency(entry.cost(config.cost_model())?, entry.leader);
+    };
+
+    let mut cycles_latency = 0.0;
+    if config.min_cycles > 0 {
+        for block in path.iter() {
+            let real_address = fictious_map.get(&block.leader).unwrap_or(&block.leader);
+            if cycles
+                .iter()
+                .any(|(cycle_entry, _)| cycle_entry == real_address)
+            {
+                if let Some(cycle_latency) = graph.shortest_cycle(block) {
+                    cycles_latency += cycle_latency * config.min_cycles as f64;
+                }
+            }
+        }
+    }
+
+    weight_to_latency(
+        entry.cost(config.cost_model())? + path_latency + cycles_latency,
+        entry.leader,
+    )
+}
+
+/// Returns the latency of the longest path from `source` to one of the exit nodes of the graph
+/// (the nodes without successors), excluding the latency of `source`.
+fn longest_path_to_exits(graph: &MappedCondensedGraph, source: &[Block]) -> Result<f64, WcetError> {
+    graph
+        .longest_path_to_exits(source)
+        .map_err(|_| WcetError::NegativeCycle(source[0].leader, false))
+}
+
+/// Summaries of the functions called from more than one site: the latency of a call to each
+/// function, computed once on the blocks reached by its first call site.
+struct CallSummaries<'a> {
+    call_map: &'a HashMap<u64, u64>, // call_target_address -> return address of its blocks
+    call_sites: HashMap<u64, u64>,   // fictious_address -> call_target_address
+    summaries: HashMap<u64, Option<u32>>, // function_address -> latency of a call, None if not summarized
+    summarized: HashSet<u64>,             // fictious addresses of the summarized calls
+    summarized_calls: HashMap<u64, u64>, // call_insn_address -> function_address, of the summarized calls
+    order: Vec<Summary>,                 // summarized functions, each after the functions it calls
+    first_fictious_address: u64,
+    config: &'a Config,
+}
+
+/// Function analyzed once for the calls replaced by its summary, kept to compute the summary
+/// again with other latencies.
+#[derive(Debug, Clone)]
+struct Summary {
+    function: u64,                // function_address
+    blocks: BTreeMap<u64, Block>, // leader -> block reached from the function, with its calls summarized
+    latency: u32,                 // latency of a call
+}
+
+impl CallSummaries<'_> {
+    /// Replaces the call ending the block `leader`, if it targets the copy of a function, with
+    /// the latency of its summary, followed by the return address. Returns whether the call has
+    /// been summarized.
+    fn summarize_call(
+        &mut self,
+        blocks: &mut BTreeMap<u64, Block>,
+        leader: u64,
+    ) -> Result<bool, WcetError> {
+        let Some(ExitJump::Call(fictious_address, return_address)) = blocks
+            .get(&leader)
+            .and_then(|block| block.exit_jump.clone())
+        else {
+            return Ok(false);
+        };
+        let Some(&function) = self.call_sites.get(&fictious_address) else {
+            return Ok(false);
+        };
+        let Some(summary) = self.summary(blocks, function)? else {
+            return Ok(false);
+        };
+
+        let block = blocks.get_mut(&leader).expect("Call block not found");
+        let call = block.instructions.last_mut().expect("Empty block");
+        let Some(latency) = call.latency.checked_add(summary) else {
+            return Ok(false);
+        };
+        call.latency = latency;
+        self.summarized_calls.insert(call.address, function);
+        block.set_exit_jump(ExitJump::Next(return_address));
+
+        self.call_sites.remove(&fictious_address);
+        self.summarized.insert(fictious_address);
+        Ok(true)
+    }
+
+    /// Returns the latency of a call to `function`: the longest path from its entry to its
+    /// returns, with the latencies of the call and of the return. The calls it makes are
+    /// summarized first. Returns None if the function is recursive, reaches the copy of another
+    /// function (e.g. through a tail call) or never returns, so it must be duplicated.
+    fn summary(
+        &mut self,
+        blocks: &mut BTreeMap<u64, Block>,
+        function: u64,
+    ) -> Result<Option<u32>, WcetError> {
+        if let Some(summary) = self.summaries.get(&function) {
+            return Ok(*summary);
+        }
+        // a recursive call finds the function still being summarized
+        self.summaries.insert(function, None);
+
+        let own_return = self.call_map.get(&function).copied();
+        let mut returns = false;
+        let mut reachable = BTreeSet::new();
+        let mut pending = vec![function];
+        while let Some(leader) = pending.pop() {
+            if leader >= self.first_fictious_address {
+                return Ok(None);
+            }
+            if !reachable.insert(leader) {
+                continue;
+            }
+            self.summarize_call(blocks, leader)?;
+
+            let Some(block) = blocks.get(&leader) else {
+                continue;
+            };
+            // a call to a function being summarized (or not summarized) is a recursion
+            if let Some(ExitJump::Call(target, _)) = block.exit_jump {
+                if self.summaries.get(&target) == Some(&None) {
+                    return Ok(None);
+                }
+            }
+            if matches!(block.exit_jump, Some(ExitJump::Ret(ret)) if Some(ret) == own_return) {
+                returns = true;
+                continue;
+            }
+            pending.extend(block.get_targets());
+        }
+        if !returns {
+            return Ok(None);
+        }
+
+        let function_blocks = blocks
+            .iter()
+            .filter(|(leader, _)| reachable.contains(leader))
+            .map(|(leader, block)| (*leader, block.clone()))
+            .collect::<BTreeMap<u64, Block>>();
+        let summary = call_latency(function, &function_blocks, self.config)?;
+        if let Some(latency) = summary {
+            self.order.push(Summary {
+                function,
+                blocks: function_blocks,
+                latency,
+            });
+        }
+
+        self.summaries.insert(function, summary);
+        Ok(summary)
+    }
+}
+
+/// Returns the latency of a call to `function`, whose blocks are `function_blocks`: the longest
+/// path from its entry to its returns, with the latencies of the call and of the return. Returns
+/// None if the entry is not found or if the latency exceeds u32.
+fn call_latency(
+    function: u64,
+    function_blocks: &BTreeMap<u64, Block>,
+    config: &Config,
+) -> Result<Option<u32>, WcetError> {
+    let config = Config {
+        graphs_dir: None,
+        entry: Some(function),
+        entries: Vec::new(),
+        ..config.clone()
+    };
+    let latency_table = &config.latency_table;
+
+    let mut entry_node_latency = HashMap::<u64, f64>::new(); // block_leader -> latency
+    let condensed_graph = condensate_graph(
+        build_graph(function_blocks, config.cost_model())?,
+        &mut entry_node_latency,
+        function_blocks,
+        &HashMap::new(),
+        &mut HashMap::new(),
+        &mut HashMap::new(),
+        &mut Vec::new(),
+        &mut BTreeMap::new(),
+        &mut Vec::new(),
+        &mut Vec::new(),
+        &config,
+        0,
+    )?;
+    let Some(entry_node) = condensed_graph
+        .get_nodes()
+        .into_iter()
+        .find(|node| node.iter().any(|block| block.leader == function))
+    else {
+        return Ok(None);
+    };
+
+    let entry_node_latency = match entry_node_latency.get(&entry_node[0].leader) {
+        Some(latency) => *latency,
+        None => entry_node[0].cost(config.cost_model())?,
+    };
+    let latency = weight_to_latency(
+        entry_node_latency
+            + longest_path_to_exits(&condensed_graph, &entry_node)?
+            + latency_table.call_latency as f64
+            + latency_table.ret_latency as f64,
+        function,
+    )?;
+
+    Ok(u32::try_from(latency).ok())
+}
+
+/// Returns the leaders of the blocks reachable from `entries`, following jumps, calls and returns.
+fn reachable_blocks(blocks: &BTreeMap<u64, Block>, entries: &[u64]) -> HashSet<u64> {
+    let mut reachable = HashSet::new();
+    let mut to_visit = entries.to_vec();
+
+    while let Some(leader) = to_visit.pop() {
+        if !reachable.insert(leader) {
+            continue;
+        }
+        if let Some(block) = blocks.get(&leader) {
+            to_visit.extend(block.get_targets());
+        }
+    }
+
+    reachable
+}
+
+/// State of the duplication of the blocks of a called function for one of its call sites.
+struct Duplicator<'a> {
+    visited_nodes: HashMap<u64, u64>, // real_address -> fictious address
+    fictious_map: &'a mut HashMap<u64, u64>, // fictious_address -> real_address
+    next_fictious_address: &'a mut u64, // first fictious address not yet allocated
+    max_depth: usize,                 // nested calls allowed before giving up
+    recursive_functions: &'a mut HashMap<u64, u64>, // leader -> ret_address
+}
+
+/// Block being duplicated by `Duplicator::duplicate`, with the targets left to duplicate.
+struct DuplicatedBlock {
+    block: Block,
     fictious_address: u64,
-    ret_address: u64,          // return address of the duplicated function
-    original_ret_address: u64, // return address of the original function
-    recursive_functions: &mut HashMap<u64, u64>, // leader -> ret_address
-    call_target_address: u64,
-    visited_nodes: &mut HashMap<u64, u64>, // real_address -> fictious address
-    fictious_map: &mut HashMap<u64, u64>,  // fictious_address -> real_address
-) {
-    visited_nodes.insert(source.leader, fictious_address);
-    fictious_map.insert(fictious_address, source.leader);
-    let source_fictious_address = fictious_address;
-    let mut fictious_address = fictious_address << (1 + 1);
-
-    //duplicate and add to blocks all targets of the source block until a return is found
-    for target in source.get_targets() {
-        if let Some(target_block) = blocks.clone().get(&target) {
+    targets: std::vec::IntoIter<u64>,
+    depth: usize, // calls nested along the path to the block
+}
+
+impl Duplicator<'_> {
+    /// Duplicates the blocks reachable from `source` until the returns of the function, giving
+    /// them fictious leaders. The blocks are visited depth first with an explicit stack, so
+    /// only the calls nested in each other count towards the maximum depth, not the length of
+    /// the paths.
+    fn duplicate(
+        &mut self,
+        blocks: &mut BTreeMap<u64, Block>,
+        source: Block,
+        fictious_address: u64,
+        ret_address: u64,          // return address of the duplicated function
+        original_ret_address: u64, // return address of the original function
+        call_target_address: u64,
+    ) -> Result<(), WcetError> {
+        let mut stack = vec![self.visit(source, fictious_address, 0)];
+        while let Some(current) = stack.last_mut() {
+            // the block is complete once all its targets are duplicated
+            let Some(target) = current.targets.next() else {
+                let mut current = stack.pop().unwrap();
+                current.block.leader = current.fictious_address;
+                blocks.insert(current.block.leader, current.block);
+                continue;
+            };
+            let Some(target_block) = blocks.get(&target).cloned() else {
+                continue;
+            };
+
+            let fictious_address = *self.next_fictious_address;
+            *self.next_fictious_address += 1;
+
             //to modify one target of the source block with the new fictious address of the duplicated target block
-            source.modify_targets(fictious_address, target);
-            visited_nodes.insert(target, fictious_address);
-            fictious_map.insert(fictious_address, target);
+            current.block.modify_targets(fictious_address, target);
+            self.visited_nodes.insert(target, fictious_address);
+            self.fictious_map.insert(fictious_address, target);
 
             let current_ret = if let Some(ExitJump::Ret(ret)) = target_block.exit_jump {
                 Some(ret)
@@ -341,45 +2030,53 @@ fn duplicate(
                 None
             };
 
+            // a call nests the blocks of the called function, until their return
+            let depth = match current.block.exit_jump {
+                Some(ExitJump::Call(_, _)) => current.depth + 1,
+                Some(ExitJump::Ret(_)) => current.depth.saturating_sub(1),
+                _ => current.depth,
+            };
+
             if current_ret == Some(original_ret_address) {
-                let mut new_block = target_block.clone();
+                let mut new_block = target_block;
                 new_block.leader = fictious_address;
                 new_block.set_exit_jump(ExitJump::Ret(ret_address));
-                blocks.insert(new_block.leader, new_block.clone());
-            } else {
-                let mut new_block = target_block.clone();
-
-                if let Some(x) = target_block
-                    .get_targets()
-                    .iter()
-                    .find(|x| visited_nodes.contains_key(x))
-                {
-                    if let Some(ExitJump::Call(_, ret_address)) = target_block.exit_jump {
-                        if *x == call_target_address {
-                            recursive_functions.insert(call_target_address, ret_address);
-                        }
+                blocks.insert(new_block.leader, new_block);
+            } else if let Some(x) = target_block
+                .get_targets()
+                .into_iter()
+                .find(|x| self.visited_nodes.contains_key(x))
+            {
+                if let Some(ExitJump::Call(_, ret_address)) = target_block.exit_jump {
+                    if x == call_target_address {
+                        self.recursive_functions
+                            .insert(call_target_address, ret_address);
                     }
-                    new_block.leader = fictious_address;
-                    new_block.modify_targets(*visited_nodes.get(x).unwrap(), *x);
-                    blocks.insert(new_block.leader, new_block.clone());
-                } else {
-                    duplicate(
-                        blocks,
-                        &mut new_block,
-                        fictious_address,
-                        ret_address,
-                        original_ret_address,
-                        recursive_functions,
-                        call_target_address,
-                        visited_nodes,
-                        fictious_map,
-                    );
                 }
+                let mut new_block = target_block;
+                new_block.leader = fictious_address;
+                new_block.modify_targets(self.visited_nodes[&x], x);
+                blocks.insert(new_block.leader, new_block);
+            } else if depth > self.max_depth {
+                return Err(WcetError::AnalysisTooDeep(call_target_address));
+            } else {
+                let duplicated_block = self.visit(target_block, fictious_address, depth);
+                stack.push(duplicated_block);
             }
         }
 
-        fictious_address += 1;
+        Ok(())
+    }
+
+    fn visit(&mut self, block: Block, fictious_address: u64, depth: usize) -> DuplicatedBlock {
+        self.visited_nodes.insert(block.leader, fictious_address);
+        self.fictious_map.insert(fictious_address, block.leader);
+
+        DuplicatedBlock {
+            targets: block.get_targets().into_iter(),
+            block,
+            fictious_address,
+            depth,
+        }
     }
-    source.leader = source_fictious_address;
-    blocks.insert(source.leader, source.clone());
 }
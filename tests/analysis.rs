@@ -0,0 +1,1911 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use capstone::{Capstone, NO_EXTRA_MODE};
+use object::{Object, ObjectSection};
+
+use timing_analysis_tool::annotations::{CostAnnotations, LatencyOverrides};
+use timing_analysis_tool::bounds::LoopBounds;
+use timing_analysis_tool::latency::{CpuModel, LatencyLayer, LatencyTable, MnemonicLatency};
+use timing_analysis_tool::symbols::function_symbols;
+use timing_analysis_tool::trace::TraceOutput;
+use timing_analysis_tool::{
+    analyze_bytes, analyze_raw, build_control_flow, Analysis, ArchMode, Block, Config, CostModel,
+    EntryMode, ExitJump, IndirectPolicy, Syntax, WcetError, WcetReport,
+};
+
+use crate::common::function_address;
+
+mod common;
+
+// the objects are assembled from the `.s` files of the directory, e.g.
+// llvm-mc -triple=x86_64-linux-gnu -filetype=obj -o x86_64.o x86_64.s
+// llvm-mc -triple=x86_64-linux-gnu -filetype=obj -o calls.o calls.s
+// llvm-mc -triple=armv7-linux-gnueabihf -filetype=obj -o arm.o arm.s
+// llvm-mc -triple=thumbv7-linux-gnueabihf -filetype=obj -o thumb.o thumb.s
+// llvm-mc -triple=powerpc64-linux-gnu -filetype=obj -o ppc.o ppc.s
+// llvm-mc -triple=s390x-linux-gnu -filetype=obj -o systemz.o systemz.s
+// llvm-mc -triple=mips-linux-gnu -filetype=obj -o mips.o mips.s
+// llvm-mc -triple=sparcv9-linux-gnu -filetype=obj -o sparc.o sparc.s
+// llvm-mc -triple=riscv64-linux-gnu -mattr=+m,-relax -filetype=obj -o riscv64.o riscv64.s
+// and the executable is linked from the object of pie.s with
+// ld -pie --no-dynamic-linker -z norelro -z noseparate-code --build-id=none -o pie.elf pie.o
+// and the one of sections.s with
+// ld -z norelro -z noseparate-code --build-id=none -o sections.elf sections.o
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+const ARCHS: [&str; 2] = ["x86_64", "aarch64"];
+
+// the architectures whose fixtures have the golden functions (straight, diamond, count_loop,
+// leaf and caller), covering every supported one
+const GOLDEN_ARCHS: [&str; 9] = [
+    "x86_64", "aarch64", "arm", "thumb", "ppc", "systemz", "mips", "sparc", "riscv64",
+];
+
+// expected results of a function, with every instruction taking 1 clock cycle
+struct Golden {
+    wcet: u64,
+    bcet: u64,
+    nodes: usize,                  // blocks of the CFG reachable from the function
+    edges: usize,                  // edges of the CFG reachable from the function
+    critical_path: &'static [u64], // leaders of the blocks along the longest path
+}
+
+/// Analyzes the fixture of `arch` from the entry of `function`, with the loop bounds of
+/// *bounds.toml*.
+fn analyze(arch: &str, function: &str) -> WcetReport {
+    let loop_bounds = LoopBounds::from_file(&Path::new(FIXTURES).join("bounds.toml"))
+        .expect("Invalid loop bounds file");
+
+    analyze_with_bounds(arch, function, loop_bounds).expect("Analysis failed")
+}
+
+fn analyze_with_bounds(
+    arch: &str,
+    function: &str,
+    loop_bounds: LoopBounds,
+) -> Result<WcetReport, WcetError> {
+    let config = Config {
+        loop_bounds,
+        ..Config::default()
+    };
+
+    analyze_with_config(arch, function, config)
+}
+
+/// Returns the content of the fixture `file`.
+fn fixture(file: &str) -> Vec<u8> {
+    std::fs::read(Path::new(FIXTURES).join(file)).expect("Unable to read the fixture")
+}
+
+/// Analyzes the fixture of `arch` from the entry of `function`, without writing the graphs.
+fn analyze_with_config(
+    arch: &str,
+    function: &str,
+    config: Config,
+) -> Result<WcetReport, WcetError> {
+    let bytes = fixture(&format!("{arch}.o"));
+    let entry = function_address(&bytes, function);
+
+    let config = Config {
+        graphs_dir: None,
+        entry: Some(entry),
+        ..config
+    };
+
+    analyze_bytes(&bytes, &config)
+}
+
+fn check<const N: usize>(archs: [&str; N], function: &str, goldens: [Golden; N]) {
+    for (arch, golden) in archs.iter().zip(goldens) {
+        let report = analyze(arch, function);
+
+        assert_eq!(report.wcet, golden.wcet, "WCET of {function} on {arch}");
+        assert_eq!(report.bcet, golden.bcet, "BCET of {function} on {arch}");
+        assert_eq!(
+            report.stats.nodes, golden.nodes,
+            "blocks of {function} on {arch}"
+        );
+        assert_eq!(
+            report.stats.edges, golden.edges,
+            "edges of {function} on {arch}"
+        );
+        assert_eq!(
+            report.critical_path, golden.critical_path,
+            "critical path of {function} on {arch}"
+        );
+    }
+}
+
+#[test]
+fn straight_line() {
+    check(
+        GOLDEN_ARCHS,
+        "straight",
+        [
+            Golden {
+                wcet: 5,
+                bcet: 5,
+                nodes: 1,
+                edges: 0,
+                critical_path: &[0x0],
+            },
+            Golden {
+                wcet: 5,
+                bcet: 5,
+                nodes: 1,
+                edges: 0,
+                critical_path: &[0x0],
+            },
+            Golden {
+                wcet: 5,
+                bcet: 5,
+                nodes: 1,
+                edges: 0,
+                critical_path: &[0x0],
+            },
+            Golden {
+                wcet: 5,
+                bcet: 5,
+                nodes: 1,
+                edges: 0,
+                critical_path: &[0x0],
+            },
+            Golden {
+                wcet: 5,
+                bcet: 5,
+                nodes: 1,
+                edges: 0,
+                critical_path: &[0x0],
+            },
+            Golden {
+                wcet: 5,
+                bcet: 5,
+                nodes: 1,
+                edges: 0,
+                critical_path: &[0x0],
+            },
+            Golden {
+                wcet: 5,
+                bcet: 5,
+                nodes: 1,
+                edges: 0,
+                critical_path: &[0x18],
+            },
+            Golden {
+                wcet: 5,
+                bcet: 5,
+                nodes: 1,
+                edges: 0,
+                critical_path: &[0x28],
+            },
+            Golden {
+                wcet: 5,
+                bcet: 5,
+                nodes: 1,
+                edges: 0,
+                critical_path: &[0x0],
+            },
+        ],
+    );
+}
+
+#[test]
+fn conditional_diamond() {
+    check(
+        GOLDEN_ARCHS,
+        "diamond",
+        [
+            Golden {
+                wcet: 6,
+                bcet: 4,
+                nodes: 4,
+                edges: 4,
+                critical_path: &[0xc, 0x11, 0x1f],
+            },
+            Golden {
+                wcet: 6,
+                bcet: 4,
+                nodes: 4,
+                edges: 4,
+                critical_path: &[0x14, 0x1c, 0x2c],
+            },
+            Golden {
+                wcet: 6,
+                bcet: 4,
+                nodes: 4,
+                edges: 4,
+                critical_path: &[0x14, 0x1c, 0x2c],
+            },
+            Golden {
+                wcet: 6,
+                bcet: 4,
+                nodes: 4,
+                edges: 4,
+                critical_path: &[0xa, 0xe, 0x16],
+            },
+            Golden {
+                wcet: 6,
+                bcet: 4,
+                nodes: 4,
+                edges: 4,
+                critical_path: &[0x14, 0x1c, 0x2c],
+            },
+            Golden {
+                wcet: 6,
+                bcet: 4,
+                nodes: 4,
+                edges: 4,
+                critical_path: &[0x10, 0x18, 0x26],
+            },
+            Golden {
+                wcet: 7,
+                bcet: 5,
+                nodes: 4,
+                edges: 4,
+                critical_path: &[0x2c, 0x34, 0x44],
+            },
+            Golden {
+                wcet: 8,
+                bcet: 6,
+                nodes: 4,
+                edges: 4,
+                critical_path: &[0x3c, 0x48, 0x58],
+            },
+            Golden {
+                wcet: 6,
+                bcet: 4,
+                nodes: 4,
+                edges: 4,
+                critical_path: &[0x14, 0x18, 0x28],
+            },
+        ],
+    );
+}
+
+#[test]
+fn bounded_loop() {
+    check(
+        GOLDEN_ARCHS,
+        "count_loop",
+        [
+            Golden {
+                wcet: 21,
+                bcet: 6,
+                nodes: 3,
+                edges: 3,
+                critical_path: &[0x20, 0x27, 0x2d],
+            },
+            Golden {
+                wcet: 21,
+                bcet: 6,
+                nodes: 3,
+                edges: 3,
+                critical_path: &[0x30, 0x38, 0x44],
+            },
+            Golden {
+                wcet: 21,
+                bcet: 6,
+                nodes: 3,
+                edges: 3,
+                critical_path: &[0x30, 0x38, 0x44],
+            },
+            Golden {
+                wcet: 21,
+                bcet: 6,
+                nodes: 3,
+                edges: 3,
+                critical_path: &[0x18, 0x1c, 0x22],
+            },
+            Golden {
+                wcet: 21,
+                bcet: 6,
+                nodes: 3,
+                edges: 3,
+                critical_path: &[0x30, 0x38, 0x44],
+            },
+            Golden {
+                wcet: 21,
+                bcet: 6,
+                nodes: 3,
+                edges: 3,
+                critical_path: &[0x28, 0x30, 0x3a],
+            },
+            Golden {
+                wcet: 22,
+                bcet: 7,
+                nodes: 3,
+                edges: 3,
+                critical_path: &[0x4c, 0x54, 0x60],
+            },
+            Golden {
+                wcet: 28,
+                bcet: 8,
+                nodes: 3,
+                edges: 3,
+                critical_path: &[0x60, 0x68, 0x78],
+            },
+            Golden {
+                wcet: 21,
+                bcet: 6,
+                nodes: 3,
+                edges: 3,
+                critical_path: &[0x2c, 0x34, 0x40],
+            },
+        ],
+    );
+}
+
+#[test]
+fn function_call() {
+    check(
+        GOLDEN_ARCHS,
+        "caller",
+        [
+            Golden {
+                wcet: 6,
+                bcet: 6,
+                nodes: 3,
+                edges: 2,
+                critical_path: &[0x32, 0x2e, 0x3c],
+            },
+            Golden {
+                wcet: 8,
+                bcet: 8,
+                nodes: 3,
+                edges: 2,
+                critical_path: &[0x50, 0x48, 0x5c],
+            },
+            Golden {
+                wcet: 8,
+                bcet: 8,
+                nodes: 3,
+                edges: 2,
+                critical_path: &[0x50, 0x48, 0x5c],
+            },
+            Golden {
+                wcet: 8,
+                bcet: 8,
+                nodes: 3,
+                edges: 2,
+                critical_path: &[0x28, 0x24, 0x30],
+            },
+            Golden {
+                wcet: 8,
+                bcet: 8,
+                nodes: 3,
+                edges: 2,
+                critical_path: &[0x50, 0x48, 0x5c],
+            },
+            Golden {
+                wcet: 8,
+                bcet: 8,
+                nodes: 3,
+                edges: 2,
+                critical_path: &[0x42, 0x3c, 0x50],
+            },
+            Golden {
+                wcet: 8,
+                bcet: 8,
+                nodes: 3,
+                edges: 2,
+                critical_path: &[0x70, 0x68, 0x7c],
+            },
+            Golden {
+                wcet: 8,
+                bcet: 8,
+                nodes: 3,
+                edges: 2,
+                critical_path: &[0x88, 0x80, 0x94],
+            },
+            Golden {
+                wcet: 8,
+                bcet: 8,
+                nodes: 3,
+                edges: 2,
+                critical_path: &[0x4c, 0x44, 0x58],
+            },
+        ],
+    );
+}
+
+#[test]
+fn loop_bound_overflowing_u32() {
+    // 3 clock cycles for each of the 4e9 iterations exceed u32::MAX
+    let mut loop_bounds = LoopBounds::default();
+    loop_bounds
+        .named_cycles
+        .insert("count_loop#1".to_string(), 4_000_000_000);
+
+    for arch in ARCHS {
+        let report =
+            analyze_with_bounds(arch, "count_loop", loop_bounds.clone()).expect("Analysis failed");
+        assert_eq!(report.wcet, 12_000_000_006, "WCET of count_loop on {arch}");
+    }
+}
+
+#[test]
+fn program_entry_mode() {
+    for (arch, caller_wcet) in ARCHS.iter().zip([6, 8]) {
+        let bytes = fixture(&format!("{arch}.o"));
+        let caller = function_address(&bytes, "caller");
+
+        // the relocatable objects have no entry point and the fixtures have no main
+        let config = Config {
+            graphs_dir: None,
+            entry_mode: EntryMode::Program,
+            ..Config::default()
+        };
+        assert!(matches!(
+            analyze_bytes(&bytes, &config),
+            Err(WcetError::NoProgramEntry)
+        ));
+
+        let report = analyze_bytes(
+            &bytes,
+            &Config {
+                program_entry: Some(caller),
+                ..config
+            },
+        )
+        .expect("Analysis failed");
+        assert_eq!(report.wcet, caller_wcet, "WCET of the program on {arch}");
+        assert_eq!(report.entry_wcets, [(caller, caller_wcet)]);
+    }
+}
+
+#[test]
+fn last_instruction_ret() {
+    // the ret of count is the last instruction of the code, its exit jump returns to _start
+    let bytes = fixture("pie.elf");
+    let address = |function: &str| function_address(&bytes, function);
+    let config = Config {
+        graphs_dir: None,
+        entry: Some(address("_start")),
+        ..Config::default()
+    };
+
+    let report = analyze_bytes(&bytes, &config).expect("Analysis failed");
+    // movl and callq, the xorl, the loop (executed twice) and the retq of count, then the
+    // addl and the retq after the return
+    assert_eq!(report.critical_path.last(), Some(&(address("_start") + 10)));
+    assert!(report.critical_path.contains(&address("count")));
+    assert_eq!(report.wcet, 12);
+}
+
+#[test]
+fn position_independent_executable() {
+    let bytes = fixture("pie.elf");
+    let config = Config {
+        graphs_dir: None,
+        entry_mode: EntryMode::Program,
+        ..Config::default()
+    };
+    let report = analyze_bytes(&bytes, &config).expect("Analysis failed");
+
+    // the whole program is moved to the runtime base, the entry point included
+    let load_bias = 0x5555_5555_4000;
+    let moved_report = analyze_bytes(
+        &bytes,
+        &Config {
+            load_bias,
+            ..config
+        },
+    )
+    .expect("Analysis failed");
+    assert_eq!(moved_report.wcet, report.wcet);
+    assert_eq!(moved_report.entry_wcets, [(0x134 + load_bias, report.wcet)]);
+    assert_eq!(moved_report.function_wcets, report.function_wcets);
+    assert_eq!(
+        moved_report.critical_path,
+        report
+            .critical_path
+            .iter()
+            .map(|leader| leader + load_bias)
+            .collect::<Vec<u64>>()
+    );
+}
+
+#[test]
+fn text_linked_at_its_address() {
+    // the .text of the executable is linked at 0x4000bc, the leaders are the linked addresses
+    let bytes = fixture("sections.elf");
+    let obj_file = object::File::parse(&*bytes).expect("Invalid fixture");
+    let text = obj_file.section_by_name(".text").expect("No text section");
+    assert_eq!(text.address(), 0x4000bc);
+
+    let config = Config {
+        graphs_dir: None,
+        entry: Some(0x4000bc),
+        ..Config::default()
+    };
+    let report = analyze_bytes(&bytes, &config).expect("Analysis failed");
+    // _start, the stub in .plt, the call of helper, helper and the ret of _start
+    assert_eq!(
+        report.critical_path,
+        [0x4000bc, 0x4000b6, 0x4000c1, 0x4000c7, 0x4000c6]
+    );
+}
+
+#[test]
+fn graphs_dir_cleanup() {
+    let bytes = fixture("x86_64.o");
+    let graphs_dir = std::env::temp_dir().join(format!("graphs-{}", std::process::id()));
+    std::fs::create_dir_all(&graphs_dir).unwrap();
+    std::fs::write(graphs_dir.join("cycle_graph_99.dot"), "").unwrap();
+    std::fs::write(graphs_dir.join("notes.txt"), "").unwrap();
+
+    // only the graphs of the previous analysis are removed
+    let config = Config {
+        graphs_dir: Some(graphs_dir.clone()),
+        ..Config::default()
+    };
+    analyze_bytes(&bytes, &config).expect("Analysis failed");
+    assert!(graphs_dir.join("graph.dot").is_file());
+    assert!(graphs_dir.join("notes.txt").is_file());
+    assert!(!graphs_dir.join("cycle_graph_99.dot").exists());
+
+    // a file can't be the graphs directory
+    let config = Config {
+        graphs_dir: Some(graphs_dir.join("notes.txt")),
+        ..Config::default()
+    };
+    let result = analyze_bytes(&bytes, &config);
+    std::fs::remove_dir_all(&graphs_dir).unwrap();
+    assert!(matches!(result, Err(WcetError::Io(..))));
+}
+
+#[test]
+fn executable_sections() {
+    let bytes = fixture("sections.elf");
+    let address = |function: &str| function_address(&bytes, function);
+    let config = Config {
+        graphs_dir: None,
+        ..Config::default()
+    };
+
+    let events = traced_events(config, |config| {
+        let report = analyze_bytes(&bytes, config).expect("Analysis failed");
+
+        // the read-only section named like text is not code, so it adds no entry node
+        let mut entries = report
+            .entry_wcets
+            .iter()
+            .map(|(entry, _)| *entry)
+            .collect::<Vec<u64>>();
+        entries.sort();
+        assert_eq!(
+            entries,
+            [address("_init"), address("_start"), address("_fini")]
+        );
+
+        // the stub in .plt (0x4000b6) returns to the call of helper, after its own call
+        let start = address("_start");
+        assert_eq!(
+            report.critical_path,
+            [start, 0x4000b6, start + 5, address("helper"), start + 10]
+        );
+    });
+    assert!(events
+        .iter()
+        .any(|event| event["event"] == "external_stub" && event["address"] == 0x4000b6));
+}
+
+#[test]
+fn text_offset_of_a_single_section() {
+    let bytes = fixture("sections.elf");
+    let address = |function: &str| function_address(&bytes, function);
+    let entries = |text_offset: u64, text_section: Option<&str>| {
+        let config = Config {
+            graphs_dir: None,
+            text_offset,
+            text_section: text_section.map(str::to_string),
+            ..Config::default()
+        };
+        analyze_bytes(&bytes, &config).map(|report| {
+            let mut entries = report
+                .entry_wcets
+                .iter()
+                .map(|(entry, _)| *entry)
+                .collect::<Vec<u64>>();
+            entries.sort();
+            entries
+        })
+    };
+
+    // the two calls at the start of .text are skipped, while .init, .plt (whose stub is no
+    // longer called) and .fini are kept whole
+    assert_eq!(
+        entries(10, None).expect("Analysis failed"),
+        [
+            address("_init"),
+            0x4000b6,
+            address("_start") + 10,
+            address("_fini")
+        ]
+    );
+    // only the ret of .fini is skipped
+    assert_eq!(
+        entries(1, Some(".fini")).expect("Analysis failed"),
+        [address("_init"), address("_start")]
+    );
+    assert!(matches!(
+        entries(1, Some(".rodata")),
+        Err(WcetError::InvalidOption("text-section", _))
+    ));
+}
+
+#[test]
+fn text_outside_the_file() {
+    // the header of .text points past the end of the file, whose data can't be read
+    let mut bytes = fixture("x86_64.o");
+    let obj_file = object::File::parse(&*bytes).expect("Invalid fixture");
+    let text = obj_file
+        .section_by_name(".text")
+        .expect("No text section")
+        .index()
+        .0;
+    // e_shoff and e_shentsize of the ELF64 header, sh_offset of the section header
+    let section_headers = u64::from_le_bytes(bytes[0x28..0x30].try_into().unwrap()) as usize;
+    let header_size = u16::from_le_bytes(bytes[0x3a..0x3c].try_into().unwrap()) as usize;
+    let offset = section_headers + text * header_size + 0x18;
+    bytes[offset..offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+
+    let config = Config {
+        graphs_dir: None,
+        ..Config::default()
+    };
+    let outcome = analyze_bytes(&bytes, &config);
+    assert!(
+        matches!(outcome, Err(WcetError::InvalidObject(_))),
+        "{outcome:?}"
+    );
+}
+
+#[test]
+fn disassembly_only() {
+    // the instructions of straight are listed with their exit jump, without computing a WCET
+    let config = Config {
+        disasm_only: true,
+        ..Config::default()
+    };
+    let report = analyze_with_config("x86_64", "straight", config).expect("Analysis failed");
+    let straight = report
+        .disassembly
+        .iter()
+        .take(5)
+        .map(|(instruction, exit_jump)| (instruction.mnemonic.as_str(), exit_jump.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        straight,
+        [
+            ("mov", None),
+            ("add", None),
+            ("imul", None),
+            ("sub", None),
+            ("ret", Some(ExitJump::Ret(0))),
+        ]
+    );
+    assert_eq!(report.wcet, 0);
+    assert!(report.critical_path.is_empty());
+}
+
+#[test]
+fn dumped_blocks() {
+    // the four blocks of diamond, with their successors and latencies
+    let config = Config {
+        dump_blocks: true,
+        ..Config::default()
+    };
+    let report = analyze_with_config("x86_64", "diamond", config).expect("Analysis failed");
+    let blocks = report
+        .blocks
+        .iter()
+        .map(|block| (block.leader, block.latency, block.successors.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        blocks,
+        [
+            (0xc, 2, vec![0x1a, 0x11]),
+            (0x11, 3, vec![0x1f]),
+            (0x1a, 1, vec![0x1f]),
+            (0x1f, 1, vec![]),
+        ]
+    );
+    assert!(report
+        .blocks
+        .iter()
+        .all(|block| block.function == "diamond"));
+    assert_eq!(report.blocks[3].exit_jump, Some(ExitJump::Ret(0)));
+    assert_eq!(report.wcet, 6);
+}
+
+#[test]
+fn listed_loops() {
+    // the two loops of nested_loop, bounded by name in the loop bounds file
+    let config = Config {
+        loop_bounds: LoopBounds::from_file(&Path::new(FIXTURES).join("bounds.toml"))
+            .expect("Invalid loop bounds file"),
+        list_loops: true,
+        ..Config::default()
+    };
+    let report = analyze_with_config("x86_64", "nested_loop", config).expect("Analysis failed");
+    let loops = report
+        .loops
+        .iter()
+        .map(|listing| {
+            (
+                listing.entry,
+                listing.name.as_deref(),
+                listing.bound,
+                listing.origin,
+                listing.blocks.clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        loops,
+        [
+            (
+                0x47,
+                Some("nested_loop#1"),
+                Some(3),
+                "name in the loop bounds file",
+                vec![0x47, 0x4c, 0x52]
+            ),
+            (
+                0x4c,
+                Some("nested_loop#2"),
+                Some(4),
+                "name in the loop bounds file",
+                vec![0x4c]
+            ),
+        ]
+    );
+    assert_eq!(report.wcet, 0);
+}
+
+#[test]
+fn disassembly_resumed_after_invalid_bytes() {
+    // jmp 0x8, six invalid bytes, ret
+    let code = [0xeb, 0x06, 0x06, 0x06, 0x06, 0x06, 0x06, 0x06, 0xc3];
+    let arch_mode = ArchMode::from_names("x86", Some("64")).expect("Invalid architecture");
+    let config = Config {
+        graphs_dir: None,
+        ..Config::default()
+    };
+
+    // the jump target is not decoded without the realignment
+    let report = analyze_raw(&code, &arch_mode, 0, false, &config).expect("Analysis failed");
+    assert_eq!(report.critical_path, [0x0]);
+
+    let config = Config {
+        resume_alignment: Some(4),
+        ..config
+    };
+    let report = analyze_raw(&code, &arch_mode, 0, false, &config).expect("Analysis failed");
+    assert_eq!(report.critical_path, [0x0, 0x8]);
+    assert_eq!(report.wcet, 2);
+}
+
+#[test]
+fn thumb_literal_pool_skipped() {
+    // ldr r0, [pc, #0], b 0x1008, the literal pool of the ldr, bx lr
+    let code = [0x00, 0x48, 0x01, 0xe0, 0x00, 0xe8, 0x00, 0xe8, 0x70, 0x47];
+    let arch_mode = ArchMode::from_names("arm", Some("thumb")).expect("Invalid architecture");
+    let config = Config {
+        graphs_dir: None,
+        ..Config::default()
+    };
+
+    // the disassembly stops at the literal pool, so the target of the branch is not decoded
+    let events = traced_events(config.clone(), |config| {
+        let report =
+            analyze_raw(&code, &arch_mode, 0x1000, false, config).expect("Analysis failed");
+        assert_eq!(report.critical_path, [0x1000]);
+    });
+    assert!(events
+        .iter()
+        .any(|event| event["event"] == "invalid_instruction" && event["address"] == 0x1004));
+
+    let config = Config {
+        skip_data: true,
+        ..config
+    };
+    let events = traced_events(config.clone(), |config| {
+        let report =
+            analyze_raw(&code, &arch_mode, 0x1000, false, config).expect("Analysis failed");
+        assert_eq!(report.critical_path, [0x1000, 0x1008]);
+        assert_eq!(report.wcet, 3);
+    });
+    assert!(events
+        .iter()
+        .all(|event| event["event"] != "invalid_instruction"));
+
+    // the pool is kept as .byte pseudo-instructions, with no latency and no jumps: its block
+    // only falls through to the leader following it
+    let mut cs = Capstone::new_raw(arch_mode.arch, arch_mode.mode, NO_EXTRA_MODE, None)
+        .expect("Invalid capstone");
+    cs.set_detail(true).expect("Unable to enable the details");
+    cs.set_skipdata(true).expect("Unable to skip the data");
+    let instructions = cs.disasm_all(&code, 0x1000).expect("Unable to disassemble");
+    let control_flow = build_control_flow(&cs, &arch_mode, &[instructions], &config)
+        .expect("Unable to build the blocks");
+    let pool = control_flow
+        .blocks
+        .get(&0x1004)
+        .expect("Literal pool not decoded");
+    assert!(pool
+        .instructions
+        .iter()
+        .all(|instruction| instruction.mnemonic == ".byte" && instruction.latency == 0));
+    assert_eq!(pool.instructions.len(), 2);
+    assert_eq!(pool.exit_jump, Some(ExitJump::Next(0x1008)));
+    assert_eq!(control_flow.blocks[&0x1000].get_targets(), [0x1008]);
+}
+
+#[test]
+fn jump_target_inside_instruction() {
+    // jmp 0x3 (inside the mov), mov eax, 0, ret
+    let code = [0xeb, 0x01, 0xb8, 0x00, 0x00, 0x00, 0x00, 0xc3];
+    let arch_mode = ArchMode::from_names("x86", Some("64")).expect("Invalid architecture");
+    let config = Config {
+        graphs_dir: None,
+        ..Config::default()
+    };
+
+    let events = traced_events(config, |config| {
+        let report =
+            analyze_raw(&code, &arch_mode, 0x1000, false, config).expect("Analysis failed");
+        assert_eq!(report.critical_path, [0x1000]);
+    });
+    assert!(events
+        .iter()
+        .any(|event| event["event"] == "invalid_jump_target"
+            && event["address"] == 0x1000
+            && event["target"] == 0x1003));
+}
+
+#[test]
+fn entry_loop_header() {
+    // dec esi, je 0x1008, then the entry: dec edi, jne 0x1000, ret
+    let code = [0xff, 0xce, 0x74, 0x04, 0xff, 0xcf, 0x75, 0xf8, 0xc3];
+    let arch_mode = ArchMode::from_names("x86", Some("64")).expect("Invalid architecture");
+    let config = Config {
+        graphs_dir: None,
+        entry: Some(0x1004),
+        ..Config::default()
+    };
+
+    // the entry is the header of the loop, although it has an incoming edge
+    let events = traced_events(config, |config| {
+        let report =
+            analyze_raw(&code, &arch_mode, 0x1000, false, config).expect("Analysis failed");
+        assert_eq!(report.cycles, [(0x1004, 1)]);
+        assert_eq!(report.loop_latencies.get(&0x1004), Some(&(4, 1, 8)));
+        assert_eq!(report.wcet, 9);
+    });
+    assert!(events
+        .iter()
+        .any(|event| event["event"] == "cycle_bound" && event["cycle"] == 0x1004));
+}
+
+#[test]
+fn conservative_indirect_jumps() {
+    // jmp rax, nop, nop, ret, then 4 nop and ret only reachable through the jump
+    let code = [0xff, 0xe0, 0x90, 0x90, 0xc3, 0x90, 0x90, 0x90, 0x90, 0xc3];
+    let arch_mode = ArchMode::from_names("x86", Some("64")).expect("Invalid architecture");
+    let config = Config {
+        graphs_dir: None,
+        ..Config::default()
+    };
+
+    // the ignored jump falls through
+    let report = analyze_raw(&code, &arch_mode, 0x1000, false, &config).expect("Analysis failed");
+    assert_eq!(report.wcet, 4);
+
+    // it can reach every leader, 0x1000 (a cycle of a single iteration), 0x1002 and 0x1005
+    let config = Config {
+        indirect_policy: IndirectPolicy::Conservative,
+        ..config
+    };
+    let events = traced_events(config.clone(), |config| {
+        let report =
+            analyze_raw(&code, &arch_mode, 0x1000, false, config).expect("Analysis failed");
+        assert_eq!(report.wcet, 7);
+        assert_eq!(report.cycles, [(0x1000, 1)]);
+        assert_eq!(report.critical_path, [0x1000, 0x1005]);
+    });
+    assert!(events
+        .iter()
+        .any(|event| event["event"] == "conservative_indirect_jump" && event["address"] == 0x1000));
+
+    // or only the leaders of its function after the first block, 0x1002 and 0x1005
+    let report = analyze_raw(
+        &code,
+        &arch_mode,
+        0x1000,
+        false,
+        &Config {
+            indirect_policy: IndirectPolicy::SameFunction,
+            ..config.clone()
+        },
+    )
+    .expect("Analysis failed");
+    assert_eq!(report.wcet, 6);
+    assert_eq!(report.critical_path, [0x1000, 0x1005]);
+
+    // or only the candidates
+    let config = Config {
+        indirect_candidates: vec![0x1002, 0x2000],
+        ..config
+    };
+    let events = traced_events(config, |config| {
+        let report =
+            analyze_raw(&code, &arch_mode, 0x1000, false, config).expect("Analysis failed");
+        assert_eq!(report.wcet, 4);
+    });
+    assert!(events
+        .iter()
+        .any(|event| event["event"] == "invalid_jump_target" && event["target"] == 0x2000));
+}
+
+#[test]
+fn conservative_indirect_calls_and_tail_calls() {
+    // call 0x100c, call rax, jmp rax, nop, nop, ret, then the function called at 0x100c: nop, ret
+    let code = [
+        0xe8, 0x07, 0x00, 0x00, 0x00, 0xff, 0xd0, 0xff, 0xe0, 0x90, 0x90, 0xc3, 0x90, 0xc3,
+    ];
+    let arch_mode = ArchMode::from_names("x86", Some("64")).expect("Invalid architecture");
+    let mut cs = Capstone::new_raw(arch_mode.arch, arch_mode.mode, NO_EXTRA_MODE, None)
+        .expect("Invalid capstone");
+    cs.set_detail(true).expect("Unable to enable the details");
+    let targets = |indirect_policy| {
+        let instructions = cs.disasm_all(&code, 0x1000).expect("Unable to disassemble");
+        let config = Config {
+            graphs_dir: None,
+            indirect_policy,
+            ..Config::default()
+        };
+        let control_flow = build_control_flow(&cs, &arch_mode, &[instructions], &config)
+            .expect("Unable to build the blocks");
+        [0x1005, 0x1007].map(|leader| {
+            control_flow
+                .blocks
+                .get(&leader)
+                .and_then(|block| block.exit_jump.clone())
+        })
+    };
+
+    // the jump can be a tail call to the entry of any function, and the call also returns
+    let leaders = vec![0x1000, 0x1005, 0x1007, 0x1009, 0x100c];
+    assert_eq!(
+        targets(IndirectPolicy::Conservative),
+        [
+            Some(ExitJump::IndirectResolved(leaders.clone())),
+            Some(ExitJump::IndirectResolved(leaders))
+        ]
+    );
+
+    // the same-function policy ignores the call, which stays in the block of the jump, and
+    // misses the tail call
+    assert_eq!(
+        targets(IndirectPolicy::SameFunction),
+        [Some(ExitJump::IndirectResolved(vec![0x1005, 0x1009])), None]
+    );
+}
+
+#[test]
+fn rep_prefixed_instruction() {
+    // rep movsb, ret
+    let code = [0xf3, 0xa4, 0xc3];
+    let arch_mode = ArchMode::from_names("x86", Some("64")).expect("Invalid architecture");
+    let config = Config {
+        graphs_dir: None,
+        ..Config::default()
+    };
+
+    // a single iteration without a bound
+    let report = analyze_raw(&code, &arch_mode, 0, false, &config).expect("Analysis failed");
+    assert_eq!(report.wcet, 2);
+
+    let mut loop_bounds = LoopBounds::default();
+    loop_bounds.rep.insert(0x0, 16);
+    let config = Config {
+        loop_bounds,
+        ..config
+    };
+    let report = analyze_raw(&code, &arch_mode, 0, false, &config).expect("Analysis failed");
+    assert_eq!(report.wcet, 17);
+}
+
+#[test]
+fn loop_latency() {
+    // 3 clock cycles for each of the 5 iterations of the loop, and the last check of the exit
+    for (arch, header) in ARCHS.iter().zip([0x27, 0x38]) {
+        let report = analyze(arch, "count_loop");
+        assert_eq!(
+            report.loop_latencies.into_iter().collect::<Vec<_>>(),
+            [(header, (3, 5, 18))],
+            "loops of count_loop on {arch}"
+        );
+    }
+}
+
+#[test]
+fn nested_loops() {
+    // 4 iterations of the inner loop (3 clock cycles each) in each of the 3 iterations of the
+    // outer one, which also runs the first and the last instruction of its body
+    for (arch, (outer, inner)) in ARCHS.iter().zip([(0x47, 0x4c), (0x70, 0x74)]) {
+        let report = analyze(arch, "nested_loop");
+        assert_eq!(
+            report.loop_latencies.into_iter().collect::<Vec<_>>(),
+            [(outer, (18, 3, 72)), (inner, (3, 4, 15))],
+            "loops of nested_loop on {arch}"
+        );
+        assert_eq!(report.wcet, 75, "WCET of nested_loop on {arch}");
+    }
+}
+
+// counts the executions of the blocks, and 10 for each taken conditional jump
+#[derive(Debug)]
+struct BlockCount;
+
+impl CostModel for BlockCount {
+    fn block_cost(&self, _block: &Block) -> f64 {
+        1.0
+    }
+
+    fn edge_cost(&self, _from: &Block, to: &Block, kind: &ExitJump) -> f64 {
+        match kind {
+            ExitJump::ConditionalRelative { taken, .. } if *taken == to.leader => 10.0,
+            _ => 0.0,
+        }
+    }
+}
+
+#[test]
+fn custom_cost_model() {
+    let mut loop_bounds = LoopBounds::default();
+    loop_bounds
+        .named_cycles
+        .insert("count_loop#1".to_string(), 5);
+
+    for arch in ARCHS {
+        let config = Config {
+            loop_bounds: loop_bounds.clone(),
+            cost_model: Some(Arc::new(BlockCount)),
+            ..Config::default()
+        };
+        let report = analyze_with_config(arch, "count_loop", config).expect("Analysis failed");
+        // the entry, the loop executed 6 times jumping back to itself 5 times, and the exit
+        assert_eq!(report.wcet, 58, "cost of count_loop on {arch}");
+    }
+}
+
+// a constant cost for each execution of a block, and for each control transfer
+#[derive(Debug)]
+struct ConstantCost(f64, f64);
+
+impl CostModel for ConstantCost {
+    fn block_cost(&self, _block: &Block) -> f64 {
+        self.0
+    }
+
+    fn edge_cost(&self, _from: &Block, _to: &Block, _kind: &ExitJump) -> f64 {
+        self.1
+    }
+}
+
+#[test]
+fn fractional_cost_model() {
+    let mut loop_bounds = LoopBounds::default();
+    loop_bounds
+        .named_cycles
+        .insert("count_loop#1".to_string(), 5);
+
+    for arch in ARCHS {
+        let config = Config {
+            loop_bounds: loop_bounds.clone(),
+            cost_model: Some(Arc::new(ConstantCost(0.3, 0.0))),
+            ..Config::default()
+        };
+        let report = analyze_with_config(arch, "count_loop", config).expect("Analysis failed");
+        // 8 executions of blocks, whose costs are summed before rounding up the worst case
+        assert_eq!(report.wcet, 3, "cost of count_loop on {arch}");
+    }
+}
+
+#[test]
+fn invalid_costs() {
+    for (block_cost, edge_cost) in [(-1.0, 0.0), (f64::NAN, 0.0), (1.0, f64::INFINITY)] {
+        let config = Config {
+            cost_model: Some(Arc::new(ConstantCost(block_cost, edge_cost))),
+            ..Config::default()
+        };
+        let outcome = analyze_with_config("x86_64", "count_loop", config);
+        assert!(
+            matches!(outcome, Err(WcetError::InvalidCost(..))),
+            "costs {block_cost} and {edge_cost}: {outcome:?}"
+        );
+    }
+}
+
+#[test]
+fn att_syntax() {
+    // the operands of AT&T are reversed and its size suffixes dropped for the latency tables
+    let bytes = fixture("x86_64.o");
+    let obj_file = object::File::parse(&*bytes).expect("Invalid fixture");
+
+    for (_, function) in function_symbols(&obj_file) {
+        let [intel, att] = [Syntax::Intel, Syntax::Att].map(|syntax| {
+            let config = Config {
+                latency_table: LatencyTable::for_cpu(CpuModel::Skylake),
+                syntax,
+                ..Config::default()
+            };
+            analyze_with_config("x86_64", &function, config)
+                .map(|report| (report.wcet, report.bcet, report.critical_path))
+                .map_err(|err| err.to_string())
+        });
+        assert_eq!(intel, att, "analysis of {function} in AT&T syntax");
+    }
+}
+
+#[test]
+fn strict_bounds() {
+    // only the outer loop is bounded, the inner one would be counted once
+    let mut loop_bounds = LoopBounds::default();
+    loop_bounds
+        .named_cycles
+        .insert("nested_loop#1".to_string(), 3);
+
+    for (arch, inner) in ARCHS.iter().zip([0x4c, 0x74]) {
+        let config = Config {
+            loop_bounds: loop_bounds.clone(),
+            strict_bounds: true,
+            ..Config::default()
+        };
+        match analyze_with_config(arch, "nested_loop", config) {
+            Err(WcetError::UnboundedCycles(addresses)) => {
+                assert_eq!(
+                    addresses,
+                    [inner],
+                    "unbounded cycles of nested_loop on {arch}"
+                )
+            }
+            result => panic!("Unbounded inner loop accepted on {arch}: {result:?}"),
+        }
+
+        let config = Config {
+            loop_bounds: LoopBounds::from_file(&Path::new(FIXTURES).join("bounds.toml"))
+                .expect("Invalid loop bounds file"),
+            strict_bounds: true,
+            ..Config::default()
+        };
+        let report = analyze_with_config(arch, "nested_loop", config).expect("Analysis failed");
+        assert_eq!(report.wcet, 75, "WCET of nested_loop on {arch}");
+    }
+}
+
+#[test]
+fn invalid_environment_bound() {
+    // a bound that is not a number is ignored with a warning, keeping the default iteration
+    for arch in ARCHS {
+        let report =
+            analyze_with_config(arch, "count_loop", Config::default()).expect("Analysis failed");
+        let (header, max_cycles) = report.cycles[0];
+        assert_eq!(max_cycles, 1, "default bound of count_loop on {arch}");
+
+        let env_var_key = format!("CYCLE_0x{header:x}");
+        std::env::set_var(&env_var_key, "notanumber");
+        let result = analyze_with_config(arch, "count_loop", Config::default());
+        std::env::remove_var(&env_var_key);
+
+        let bounded = result.expect("Analysis failed");
+        assert_eq!(
+            bounded.cycles, report.cycles,
+            "bounds of count_loop on {arch}"
+        );
+        assert_eq!(bounded.wcet, report.wcet, "WCET of count_loop on {arch}");
+    }
+}
+
+#[test]
+fn multiple_entries() {
+    let functions = ["count_loop", "nested_loop", "straight"];
+    for arch in ARCHS {
+        let bytes = fixture(&format!("{arch}.o"));
+        let entries = functions
+            .iter()
+            .map(|function| function_address(&bytes, function))
+            .collect::<Vec<u64>>();
+
+        let config = Config {
+            loop_bounds: LoopBounds::from_file(&Path::new(FIXTURES).join("bounds.toml"))
+                .expect("Invalid loop bounds file"),
+            graphs_dir: None,
+            entries: entries.clone(),
+            ..Config::default()
+        };
+        let report = analyze_bytes(&bytes, &config).expect("Analysis failed");
+
+        // each entry gets the same results as when analyzed alone
+        let reports = functions.map(|function| analyze(arch, function));
+        assert_eq!(
+            report.entry_wcets,
+            entries
+                .iter()
+                .zip(&reports)
+                .map(|(entry, report)| (*entry, report.wcet))
+                .collect::<Vec<_>>(),
+            "WCET of each entry on {arch}"
+        );
+        assert_eq!(report.wcet, 75, "WCET of nested_loop on {arch}");
+        for ((entry, entry_report), report) in report.entry_reports.iter().zip(&reports) {
+            assert_eq!(
+                entry_report.bcet, report.bcet,
+                "BCET of 0x{entry:x} on {arch}"
+            );
+            assert_eq!(
+                entry_report.critical_path, report.critical_path,
+                "critical path of 0x{entry:x} on {arch}"
+            );
+        }
+    }
+}
+
+#[test]
+fn call_never_returning() {
+    // the call to abort ends the path, the code after it is not reachable
+    for arch in ARCHS {
+        let report = analyze(arch, "checked");
+        assert_eq!(report.wcet, 3, "WCET of checked on {arch}");
+        assert_eq!(report.stats.nodes, 3, "blocks of checked on {arch}");
+    }
+}
+
+// trace output shared with the test, which reads it back after the analysis
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns the events written to the trace while running `analysis` with `config`.
+fn traced_events(config: Config, analysis: impl FnOnce(&Config)) -> Vec<serde_json::Value> {
+    let buffer = SharedBuffer::default();
+    let config = Config {
+        trace_output: Some(TraceOutput::new(buffer.clone())),
+        ..config
+    };
+    analysis(&config);
+
+    let trace = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    trace
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).expect("Invalid event"))
+        .collect()
+}
+
+#[test]
+fn trace_events() {
+    let loop_bounds = LoopBounds::from_file(&Path::new(FIXTURES).join("bounds.toml"))
+        .expect("Invalid loop bounds file");
+    let config = Config {
+        loop_bounds,
+        ..Config::default()
+    };
+
+    let events = traced_events(config, |config| {
+        analyze_with_config("x86_64", "count_loop", config.clone()).expect("Analysis failed");
+    });
+    assert!(events.iter().any(|event| event["event"] == "cycle_bound"
+        && event["cycle"] == 0x27
+        && event["reason"]
+            .as_str()
+            .unwrap()
+            .contains("5 cycle iterations")));
+}
+
+// trace output which can't be written, counting the attempts
+#[derive(Clone, Default)]
+struct FailingOutput(Arc<Mutex<usize>>);
+
+impl Write for FailingOutput {
+    fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+        *self.0.lock().unwrap() += 1;
+        Err(std::io::Error::other("disk full"))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn trace_write_error() {
+    // jmp rax, then the first event (the ignored jump) disables the trace
+    let code = [0xff, 0xe0, 0xc3];
+    let arch_mode = ArchMode::from_names("x86", Some("64")).expect("Invalid architecture");
+    let output = FailingOutput::default();
+    let config = Config {
+        graphs_dir: None,
+        trace_output: Some(TraceOutput::new(output.clone())),
+        ..Config::default()
+    };
+
+    // the analysis goes on, and the output is not written again
+    let report = analyze_raw(&code, &arch_mode, 0x1000, false, &config).expect("Analysis failed");
+    assert_eq!(report.wcet, 2);
+    analyze_raw(&code, &arch_mode, 0x1000, false, &config).expect("Analysis failed");
+    assert_eq!(*output.0.lock().unwrap(), 1);
+}
+
+/// Returns the report of `analysis` without the timings, which change at each run, and the
+/// entry and exit blocks chosen or ignored for the cycles.
+fn analysis_outcome(
+    config: Config,
+    analysis: impl Fn(&Config) -> WcetReport,
+) -> (serde_json::Value, Vec<serde_json::Value>) {
+    let mut report = None;
+    let events = traced_events(config, |config| report = Some(analysis(config)));
+    let report = WcetReport {
+        timings: Vec::new(),
+        ..report.unwrap()
+    };
+    let events = events
+        .into_iter()
+        .filter(|event| event.get("entry").is_some() || event.get("exit").is_some())
+        .collect();
+
+    (serde_json::to_value(report).unwrap(), events)
+}
+
+#[test]
+fn deterministic_analysis() {
+    let bytes = fixture("x86_64.o");
+    let config = Config {
+        graphs_dir: None,
+        ..Config::default()
+    };
+    let analysis = |config: &Config| analyze_bytes(&bytes, config).expect("Analysis failed");
+    let outcome = analysis_outcome(config.clone(), analysis);
+    assert!(!outcome.0["cycles"].as_array().unwrap().is_empty());
+    for _ in 0..5 {
+        assert_eq!(analysis_outcome(config.clone(), analysis), outcome);
+    }
+
+    // nop, then a loop left from both of its blocks: dec edi, je 0x100b, dec esi, je 0x100c,
+    // jmp 0x1001, nop, ret
+    let code = [
+        0x90, 0xff, 0xcf, 0x74, 0x06, 0xff, 0xce, 0x74, 0x03, 0xeb, 0xf6, 0x90, 0xc3,
+    ];
+    let arch_mode = ArchMode::from_names("x86", Some("64")).expect("Invalid architecture");
+    let analysis = |config: &Config| {
+        analyze_raw(&code, &arch_mode, 0x1000, false, config).expect("Analysis failed")
+    };
+    let outcome = analysis_outcome(config.clone(), analysis);
+    assert!(!outcome.1.is_empty());
+    for _ in 0..5 {
+        assert_eq!(analysis_outcome(config.clone(), analysis), outcome);
+    }
+}
+
+#[test]
+fn phase_timings() {
+    let report = analyze("x86_64", "count_loop");
+    let phases = report
+        .timings
+        .iter()
+        .map(|(phase, _)| phase.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        phases,
+        [
+            "disassembly",
+            "decoding",
+            "leader detection",
+            "block building",
+            "duplication",
+            "graph building",
+            "condensation",
+            "path computation"
+        ]
+    );
+    assert!(report.timings.iter().all(|(_, ms)| *ms >= 0.0));
+}
+
+#[test]
+fn mips_delay_slot() {
+    // the delay slot of the branch (0x4) is part of its block, executed on both paths
+    let report = analyze("mips", "delay_slot");
+
+    assert_eq!(report.wcet, 6);
+    assert_eq!(report.bcet, 4);
+    assert_eq!(report.stats.nodes, 3);
+    assert_eq!(report.critical_path, [0x0, 0x8, 0x10]);
+}
+
+#[test]
+fn sparc_branches() {
+    // `ba` is unconditional, so its delay slot (0x18) is followed by the return, never by 0x1c
+    let report = analyze("sparc", "window");
+    assert_eq!(report.wcet, 9);
+    assert_eq!(report.bcet, 6);
+    assert_eq!(report.stats.nodes, 3);
+    assert_eq!(report.stats.edges, 3);
+    assert_eq!(report.critical_path, [0x0, 0x10, 0x20]);
+
+    // the spill and the fill of a register window, trapping when the windows are exhausted
+    let mut latency_table = LatencyTable::default();
+    latency_table.archs.insert(
+        "SPARC".to_string(),
+        HashMap::from([
+            ("SAVE".to_string(), MnemonicLatency::Fixed(20)),
+            ("RESTORE".to_string(), MnemonicLatency::Fixed(20)),
+        ]),
+    );
+    let config = Config {
+        latency_table,
+        ..Config::default()
+    };
+    let report = analyze_with_config("sparc", "window", config).expect("Analysis failed");
+    assert_eq!(report.wcet, 47);
+}
+
+#[test]
+fn cost_annotations() {
+    // a stall of 40 clock cycles on the second instruction of the function
+    for (arch, address) in ARCHS.iter().zip([0x2, 0x4]) {
+        let mut cost_annotations = CostAnnotations::default();
+        cost_annotations.costs.insert(address, 40);
+        let config = Config {
+            cost_annotations,
+            ..Config::default()
+        };
+        let report = analyze_with_config(arch, "straight", config).expect("Analysis failed");
+        assert_eq!(report.wcet, 45, "WCET of straight on {arch}");
+
+        // the address is inside the first instruction
+        let mut cost_annotations = CostAnnotations::default();
+        cost_annotations.costs.insert(address - 1, 40);
+        let config = Config {
+            cost_annotations,
+            ..Config::default()
+        };
+        assert!(matches!(
+            analyze_with_config(arch, "straight", config),
+            Err(WcetError::AnnotationOutsideCode(annotation)) if annotation == address - 1
+        ));
+    }
+}
+
+#[test]
+fn latency_overrides() {
+    // the second instruction of the function always takes 40 clock cycles
+    for (arch, address) in ARCHS.iter().zip([0x2, 0x4]) {
+        let mut latency_overrides = LatencyOverrides::default();
+        latency_overrides.latencies.insert(address, 40);
+        let config = Config {
+            latency_overrides: latency_overrides.clone(),
+            ..Config::default()
+        };
+        let report = analyze_with_config(arch, "straight", config).expect("Analysis failed");
+        assert_eq!(report.wcet, 44, "WCET of straight on {arch}");
+
+        // the cost annotations are added to the overridden latency
+        let mut cost_annotations = CostAnnotations::default();
+        cost_annotations.costs.insert(address, 10);
+        let config = Config {
+            latency_overrides,
+            cost_annotations,
+            ..Config::default()
+        };
+        let report = analyze_with_config(arch, "straight", config).expect("Analysis failed");
+        assert_eq!(report.wcet, 54, "WCET of straight on {arch}");
+
+        // the address is inside the first instruction
+        let mut latency_overrides = LatencyOverrides::default();
+        latency_overrides.latencies.insert(address - 1, 40);
+        let config = Config {
+            latency_overrides,
+            ..Config::default()
+        };
+        assert!(matches!(
+            analyze_with_config(arch, "straight", config),
+            Err(WcetError::LatencyOverrideOutsideCode(override_address)) if override_address == address - 1
+        ));
+    }
+}
+
+#[test]
+fn latency_profiles() {
+    // every instruction of the little core takes 3 clock cycles
+    let mut latency_table = LatencyTable {
+        branch_penalty: 2,
+        ..LatencyTable::default()
+    };
+    latency_table.profiles.insert(
+        "little".to_string(),
+        LatencyLayer {
+            default: Some(3),
+            ..LatencyLayer::default()
+        },
+    );
+    let little = latency_table.profile("little").expect("Profile not found");
+    assert!(latency_table.profile("big").is_none());
+
+    // the profile omits the branch penalty, which is the one of the table
+    assert_eq!(little.branch_penalty, 2);
+
+    for arch in ARCHS {
+        let config = Config {
+            core_latency_tables: vec![
+                ("big".to_string(), LatencyTable::default()),
+                ("little".to_string(), little.clone()),
+            ],
+            ..Config::default()
+        };
+        let report = analyze_with_config(arch, "straight", config).expect("Analysis failed");
+        let big = analyze(arch, "straight");
+        assert_eq!(
+            report
+                .core_reports
+                .iter()
+                .map(|(core, report)| (core.as_str(), report.wcet))
+                .collect::<Vec<_>>(),
+            [("big", big.wcet), ("little", 3 * big.wcet)],
+            "WCET of each core on {arch}"
+        );
+        // the report is the one of the slowest core
+        assert_eq!(report.wcet, 3 * big.wcet, "WCET of straight on {arch}");
+        assert!(report
+            .to_string()
+            .contains(&format!("WCET on little: {} clock cycles", 3 * big.wcet)));
+    }
+}
+
+#[test]
+fn reanalysis_with_other_latencies() {
+    let latency_tables = [
+        LatencyTable::default(),
+        LatencyTable::for_cpu(CpuModel::Generic),
+        LatencyTable {
+            branch_penalty: 3,
+            call_latency: 2,
+            hazard_penalty: 2,
+            ..LatencyTable::for_cpu(CpuModel::Generic)
+        },
+    ];
+    for arch in ARCHS {
+        let bytes = fixture(&format!("{arch}.o"));
+        for function in ["caller", "nested_loop", "checked"] {
+            let entry = function_address(&bytes, function);
+            let config = Config {
+                loop_bounds: LoopBounds::from_file(&Path::new(FIXTURES).join("bounds.toml"))
+                    .expect("Invalid loop bounds file"),
+                graphs_dir: None,
+                entry: Some(entry),
+                ..Config::default()
+            };
+            let mut analysis = Analysis::new(&bytes, &config).expect("Analysis failed");
+            assert!(analysis.is_of(&bytes));
+
+            // the blocks built once give the same results as a new analysis with each table
+            let wcets = latency_tables
+                .iter()
+                .map(|latency_table| {
+                    analysis
+                        .with_latencies(latency_table)
+                        .expect("Analysis failed")
+                        .wcet
+                })
+                .collect::<Vec<u64>>();
+            assert!(wcets[0] < wcets[2], "WCETs of {function} on {arch}");
+            for latency_table in &latency_tables {
+                let report = analysis
+                    .with_latencies(latency_table)
+                    .expect("Analysis failed");
+                let expected = analyze_bytes(
+                    &bytes,
+                    &Config {
+                        latency_table: latency_table.clone(),
+                        ..config.clone()
+                    },
+                )
+                .expect("Analysis failed");
+                assert_eq!(report.wcet, expected.wcet, "WCET of {function} on {arch}");
+                assert_eq!(report.bcet, expected.bcet, "BCET of {function} on {arch}");
+            }
+        }
+    }
+}
+
+#[test]
+fn reanalysis_with_summarized_calls() {
+    let bytes = fixture("calls.o");
+    let config = Config {
+        summarize_calls: true,
+        ..Config::default()
+    };
+    let mut analysis = Analysis::new(&bytes, &config).expect("Analysis failed");
+    let report = analysis.report().expect("Analysis failed");
+    assert_eq!(report.wcet, analyze_bytes(&bytes, &config).unwrap().wcet);
+
+    // the summary of the second call to scale is computed again with each table
+    for latency_table in [
+        LatencyTable::for_cpu(CpuModel::Generic),
+        LatencyTable {
+            branch_penalty: 3,
+            call_latency: 2,
+            ret_latency: 1,
+            ..LatencyTable::for_cpu(CpuModel::Generic)
+        },
+        LatencyTable::default(),
+    ] {
+        let report = analysis
+            .with_latencies(&latency_table)
+            .expect("Analysis failed");
+        let expected = analyze_bytes(
+            &bytes,
+            &Config {
+                latency_table: latency_table.clone(),
+                ..config.clone()
+            },
+        )
+        .expect("Analysis failed");
+        assert_eq!(report.wcet, expected.wcet);
+        assert_eq!(report.bcet, expected.bcet);
+        assert_eq!(report.function_wcets, expected.function_wcets);
+    }
+}
+
+#[test]
+fn function_called_from_three_sites() {
+    let bytes = fixture("calls.o");
+    let thrice = function_address(&bytes, "thrice");
+    let config = Config {
+        graphs_dir: None,
+        entry: Some(thrice),
+        dump_blocks: true,
+        ..Config::default()
+    };
+    let report = analyze_bytes(&bytes, &config).expect("Analysis failed");
+
+    // each copy of the ret of scale returns after its own call site
+    let mut ret_addresses = report
+        .blocks
+        .iter()
+        .filter(|block| block.function == "scale")
+        .filter_map(|block| match block.exit_jump {
+            Some(ExitJump::Ret(ret_address)) => Some(ret_address),
+            _ => None,
+        })
+        .collect::<Vec<u64>>();
+    ret_addresses.sort();
+    assert_eq!(ret_addresses, [thrice + 5, thrice + 10, thrice + 15]);
+
+    // each call (1) runs imul, test and jle (3), the add (1) and the ret (1) of scale, then the
+    // ret of thrice (1): 3 * 6 + 1
+    assert_eq!(report.wcet, 19);
+    // the add is skipped by each call
+    assert_eq!(report.bcet, 16);
+    assert_eq!(
+        report.critical_path,
+        [
+            thrice,
+            0x10,
+            0x17,
+            0x1a,
+            thrice + 5,
+            0x10,
+            0x17,
+            0x1a,
+            thrice + 10,
+            0x10,
+            0x17,
+            0x1a,
+            thrice + 15
+        ]
+    );
+}
+
+#[test]
+fn recursive_bounds() {
+    for arch in ARCHS {
+        let bytes = fixture(&format!("{arch}.o"));
+        let countdown = function_address(&bytes, "countdown");
+
+        // a single recursion without a bound
+        let report = analyze(arch, "recursion");
+        assert_eq!(report.recursive_functions, [countdown]);
+        let [(function, ret_address, max_recursions)] = report.recursive_bounds[..] else {
+            panic!("Recursive function of {arch} not detected");
+        };
+        assert_eq!((function, max_recursions), (countdown, 1));
+        // the recursive call returns to the ret at the end of the function
+        assert!(
+            ret_address > countdown,
+            "return address of countdown on {arch}"
+        );
+
+        let mut loop_bounds = LoopBounds::default();
+        loop_bounds.recursive.insert(countdown, 3);
+        let report = analyze_with_bounds(arch, "recursion", loop_bounds).expect("Analysis failed");
+        assert_eq!(report.recursive_bounds, [(countdown, ret_address, 3)]);
+    }
+}
+
+#[test]
+fn non_terminating_loop() {
+    // the loop of the task has no exit: the setup plus a single iteration through the multiply
+    check(
+        ARCHS,
+        "task",
+        [
+            Golden {
+                wcet: 6,
+                bcet: 1,
+                nodes: 4,
+                edges: 5,
+                critical_path: &[0x85, 0x8a],
+            },
+            Golden {
+                wcet: 5,
+                bcet: 1,
+                nodes: 4,
+                edges: 5,
+                critical_path: &[0xc8, 0xcc],
+            },
+        ],
+    );
+
+    // the bound of a loop that never terminates is ignored, and it is not an unbounded cycle
+    let mut loop_bounds = LoopBounds::default();
+    loop_bounds.named_cycles.insert("task#1".to_string(), 10);
+    for (arch, (header, iteration)) in ARCHS.iter().zip([(0x8a, 5), (0xcc, 4)]) {
+        let config = Config {
+            loop_bounds: loop_bounds.clone(),
+            ..Config::default()
+        };
+        let report = analyze_with_config(arch, "task", config).expect("Analysis failed");
+        assert_eq!(
+            report.non_terminating_loops,
+            [header],
+            "flag of task on {arch}"
+        );
+        assert_eq!(
+            report.loop_latencies.get(&header),
+            Some(&(iteration, 1, iteration)),
+            "iteration of task on {arch}"
+        );
+
+        let config = Config {
+            strict_bounds: true,
+            ..Config::default()
+        };
+        let report = analyze_with_config(arch, "task", config).expect("Analysis failed");
+        assert_eq!(report.wcet, iteration + 1, "strict WCET of task on {arch}");
+    }
+}
+
+#[test]
+fn mutual_recursion_depth() {
+    // call f, ret; f: test edi, edi, je 0x100f, call g, ret; g: dec edi, call f, ret
+    let code = [
+        0xe8, 0x01, 0x00, 0x00, 0x00, 0xc3, 0x85, 0xff, 0x74, 0x05, 0xe8, 0x01, 0x00, 0x00, 0x00,
+        0xc3, 0xff, 0xcf, 0xe8, 0xef, 0xff, 0xff, 0xff, 0xc3,
+    ];
+    let arch_mode = ArchMode::from_names("x86", Some("64")).expect("Invalid architecture");
+    let config = Config {
+        graphs_dir: None,
+        ..Config::default()
+    };
+
+    let report = analyze_raw(&code, &arch_mode, 0x1000, false, &config).expect("Analysis failed");
+    assert_eq!(report.wcet, 17);
+
+    // the copy of f nests the call of g
+    let config = Config {
+        max_depth: 0,
+        ..config
+    };
+    match analyze_raw(&code, &arch_mode, 0x1000, false, &config) {
+        Err(WcetError::AnalysisTooDeep(address)) => assert_eq!(address, 0x1006),
+        result => panic!("Too deep duplication accepted: {result:?}"),
+    }
+}
+
+#[test]
+fn long_duplicated_function() {
+    // call f, call f, ret; f: a chain of 8 blocks of jmp to the next instruction, ret
+    let mut code = vec![
+        0xe8, 0x06, 0x00, 0x00, 0x00, 0xe8, 0x01, 0x00, 0x00, 0x00, 0xc3,
+    ];
+    code.extend([0xeb, 0x00].repeat(8));
+    code.push(0xc3);
+    let arch_mode = ArchMode::from_names("x86", Some("64")).expect("Invalid architecture");
+
+    // only the nested calls count towards the depth, not the blocks along the paths of f
+    let config = Config {
+        graphs_dir: None,
+        max_depth: 1,
+        ..Config::default()
+    };
+    let report = analyze_raw(&code, &arch_mode, 0x1000, false, &config).expect("Analysis failed");
+    assert_eq!(report.wcet, 21);
+    assert_eq!(report.critical_path.len(), 21);
+}
+
+#[test]
+fn fictious_leaders_of_nested_calls() {
+    // call f, call f, ret; f: call g, call g, ret; g: nop, ret, loaded high in the address space
+    let code = [
+        0xe8, 0x06, 0x00, 0x00, 0x00, 0xe8, 0x01, 0x00, 0x00, 0x00, 0xc3, 0xe8, 0x06, 0x00, 0x00,
+        0x00, 0xe8, 0x01, 0x00, 0x00, 0x00, 0xc3, 0x90, 0xc3,
+    ];
+    let base = 0xffff_0000_0000_1000;
+    let arch_mode = ArchMode::from_names("x86", Some("64")).expect("Invalid architecture");
+    let mut cs = Capstone::new_raw(arch_mode.arch, arch_mode.mode, NO_EXTRA_MODE, None)
+        .expect("Invalid capstone");
+    cs.set_detail(true).expect("Unable to enable the details");
+    let instructions = cs.disasm_all(&code, base).expect("Unable to disassemble");
+    let config = Config {
+        graphs_dir: None,
+        ..Config::default()
+    };
+
+    let control_flow = build_control_flow(&cs, &arch_mode, &[instructions], &config)
+        .expect("Unable to build the blocks");
+    let real_leaders = control_flow
+        .blocks
+        .keys()
+        .filter(|leader| !control_flow.fictious_map.contains_key(leader))
+        .copied()
+        .collect::<HashSet<u64>>();
+
+    // the second call of f has a copy of its two blocks, and each second call of g a copy of g
+    let mut copies = control_flow
+        .fictious_map
+        .values()
+        .map(|real_address| real_address - base)
+        .collect::<Vec<u64>>();
+    copies.sort();
+    assert_eq!(copies, [0xb, 0x10, 0x16, 0x16]);
+    for (fictious_address, real_address) in control_flow.fictious_map.iter() {
+        assert!(
+            !real_leaders.contains(fictious_address),
+            "fictious leader 0x{fictious_address:x} of 0x{real_address:x} is a real leader"
+        );
+        assert!(control_flow.blocks.contains_key(fictious_address));
+    }
+}
+
+#[test]
+fn thumb_it_block_of_four_instructions() {
+    // cmp r0, #0, itttt eq, 3 x addeq r0, r0, r1, bxeq lr, 2 x adds r0, r0, r1, bx lr
+    let code = [
+        0x00, 0x28, 0x01, 0xbf, 0x40, 0x18, 0x40, 0x18, 0x40, 0x18, 0x70, 0x47, 0x40, 0x18, 0x40,
+        0x18, 0x70, 0x47,
+    ];
+    let arch_mode = ArchMode::from_names("arm", Some("thumb")).expect("Invalid architecture");
+    let config = Config {
+        graphs_dir: None,
+        ..Config::default()
+    };
+
+    // the return in the last slot of the IT block is conditional (capstone gives no condition
+    // to the returns), so the code following it is in the worst case instead of ending it
+    let report = analyze_raw(&code, &arch_mode, 0, false, &config).expect("Analysis failed");
+    assert_eq!(report.wcet, 9);
+}
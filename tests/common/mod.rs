@@ -0,0 +1,11 @@
+use timing_analysis_tool::symbols::function_symbols;
+
+/// Returns the address of the function `name` in the symbol table of the object file `bytes`.
+pub fn function_address(bytes: &[u8], name: &str) -> u64 {
+    let obj_file = object::File::parse(bytes).expect("Invalid fixture");
+    function_symbols(&obj_file)
+        .into_iter()
+        .find(|(_, function)| function == name)
+        .map(|(address, _)| address)
+        .expect("Function not found in the fixture")
+}
@@ -0,0 +1,99 @@
+// straight line code, a conditional diamond, a loop, a function call, two nested loops, a call never returning and a recursive function
+	.text
+
+	.type	straight,@function
+straight:
+	mov	w1, w0
+	add	w0, w1, #1
+	mul	w0, w0, w0
+	sub	w0, w0, #2
+	ret
+
+	.type	diamond,@function
+diamond:
+	cmp	w0, #0
+	b.le	.Lelse
+	mov	w1, #1
+	add	w0, w0, w1
+	b	.Lend
+.Lelse:
+	mov	w0, #2
+.Lend:
+	ret
+
+	.type	count_loop,@function
+count_loop:
+	mov	w0, #0
+	mov	w1, #5
+.Lloop:
+	add	w0, w0, w1
+	subs	w1, w1, #1
+	b.ne	.Lloop
+	ret
+
+	.type	leaf,@function
+leaf:
+	add	w0, w0, #1
+	ret
+
+	.type	caller,@function
+caller:
+	stp	x29, x30, [sp, #-16]!
+	mov	w0, #3
+	bl	leaf
+	add	w0, w0, #1
+	ldp	x29, x30, [sp], #16
+	ret
+
+	.type	nested_loop,@function
+nested_loop:
+	mov	w0, #0
+	mov	w1, #3
+.Louter:
+	mov	w2, #4
+.Linner:
+	add	w0, w0, w2
+	subs	w2, w2, #1
+	b.ne	.Linner
+	subs	w1, w1, #1
+	b.ne	.Louter
+	ret
+
+	.type	abort,@function
+abort:
+	ret
+
+	.type	checked,@function
+checked:
+	cmp	w0, #0
+	b.ne	.Lchecked
+	bl	abort
+	mov	w0, #1
+	mul	w0, w0, w0
+	mul	w0, w0, w0
+.Lchecked:
+	ret
+
+	.type	countdown,@function
+countdown:
+	cbz	w0, .Lcountdown
+	sub	w0, w0, #1
+	bl	countdown
+.Lcountdown:
+	ret
+
+	.type	recursion,@function
+recursion:
+	mov	w0, #3
+	bl	countdown
+	ret
+
+	.type	task,@function
+task:
+	mov	w0, #0
+.Ltask:
+	add	w0, w0, #1
+	tbz	w0, #0, .Ltask_even
+	mul	w0, w0, w0
+.Ltask_even:
+	b	.Ltask
@@ -0,0 +1,47 @@
+@ straight line code, a conditional diamond, a loop and a function call, in ARM mode
+	.text
+	.arm
+
+	.type	straight,%function
+straight:
+	mov	r1, r0
+	add	r0, r1, #1
+	mul	r0, r0, r0
+	sub	r0, r0, #2
+	bx	lr
+
+	.type	diamond,%function
+diamond:
+	cmp	r0, #0
+	ble	.Lelse
+	mov	r1, #1
+	add	r0, r0, r1
+	b	.Lend
+.Lelse:
+	mov	r0, #2
+.Lend:
+	bx	lr
+
+	.type	count_loop,%function
+count_loop:
+	mov	r0, #0
+	mov	r1, #5
+.Lloop:
+	add	r0, r0, r1
+	subs	r1, r1, #1
+	bne	.Lloop
+	bx	lr
+
+	.type	leaf,%function
+leaf:
+	add	r0, r0, #1
+	bx	lr
+
+	.type	caller,%function
+caller:
+	push	{r11, lr}
+	mov	r0, #3
+	.inst	0xebfffffa	@ bl leaf, encoded since the assembler leaves a relocation
+	add	r0, r0, #1
+	pop	{r11, lr}
+	bx	lr
@@ -0,0 +1,26 @@
+# a function called from two sites, whose second call is summarized with summarize_calls, and
+# from three sites of another function
+	.text
+
+	.type	twice,@function
+twice:
+	movl	$3, %edi
+	callq	scale
+	callq	scale
+	retq
+
+	.type	scale,@function
+scale:
+	imull	%edi, %eax
+	testl	%eax, %eax
+	jle	.Lend
+	addl	$1, %eax
+.Lend:
+	retq
+
+	.type	thrice,@function
+thrice:
+	callq	scale
+	callq	scale
+	callq	scale
+	retq
@@ -0,0 +1,60 @@
+# a conditional branch whose delay slot is executed on both paths
+	.text
+	.set	noreorder
+
+	.type	delay_slot,@function
+delay_slot:
+	beq	$a0, $zero, .Lzero
+	addiu	$v0, $zero, 1
+	addiu	$v0, $v0, 2
+	addiu	$v0, $v0, 3
+.Lzero:
+	jr	$ra
+	nop
+
+# straight line code, a conditional diamond, a loop and a function call
+	.type	straight,@function
+straight:
+	move	$v1, $a0
+	addiu	$v0, $v1, 1
+	mul	$v0, $v0, $v0
+	jr	$ra
+	addiu	$v0, $v0, -2
+
+	.type	diamond,@function
+diamond:
+	blez	$a0, .Lelse
+	nop
+	addiu	$v1, $zero, 1
+	b	.Lend
+	addu	$v0, $a0, $v1
+.Lelse:
+	addiu	$v0, $zero, 2
+.Lend:
+	jr	$ra
+	nop
+
+	.type	count_loop,@function
+count_loop:
+	move	$v0, $zero
+	addiu	$v1, $zero, 5
+.Lloop:
+	addu	$v0, $v0, $v1
+	bnez	$v1, .Lloop
+	addiu	$v1, $v1, -1
+	jr	$ra
+	nop
+
+	.type	leaf,@function
+leaf:
+	jr	$ra
+	addiu	$v0, $a0, 1
+
+	.type	caller,@function
+caller:
+	move	$t0, $ra
+	bal	leaf
+	addiu	$a0, $zero, 3
+	move	$ra, $t0
+	jr	$ra
+	addiu	$v0, $v0, 1
@@ -0,0 +1,19 @@
+# position-independent executable, with a call and a loop
+	.text
+
+	.globl	_start
+	.type	_start,@function
+_start:
+	movl	$3, %edi
+	callq	count
+	addl	$1, %eax
+	retq
+
+	.type	count,@function
+count:
+	xorl	%eax, %eax
+.Lloop:
+	addl	%edi, %eax
+	decl	%edi
+	jne	.Lloop
+	retq
@@ -0,0 +1,46 @@
+# straight line code, a conditional diamond, a loop and a function call
+	.text
+
+	.type	straight,@function
+straight:
+	mr	4, 3
+	addi	3, 4, 1
+	mullw	3, 3, 3
+	addi	3, 3, -2
+	blr
+
+	.type	diamond,@function
+diamond:
+	cmpwi	3, 0
+	ble	0, .Lelse
+	li	4, 1
+	add	3, 3, 4
+	b	.Lend
+.Lelse:
+	li	3, 2
+.Lend:
+	blr
+
+	.type	count_loop,@function
+count_loop:
+	li	3, 0
+	li	4, 5
+.Lloop:
+	add	3, 3, 4
+	addic.	4, 4, -1
+	bne	0, .Lloop
+	blr
+
+	.type	leaf,@function
+leaf:
+	addi	3, 3, 1
+	blr
+
+	.type	caller,@function
+caller:
+	mflr	0
+	li	3, 3
+	bl	leaf
+	addi	3, 3, 1
+	mtlr	0
+	blr
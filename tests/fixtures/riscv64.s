@@ -0,0 +1,46 @@
+# straight line code, a conditional diamond, a loop and a function call
+	.text
+	.option	norvc
+
+	.type	straight,@function
+straight:
+	mv	a1, a0
+	addi	a0, a1, 1
+	mul	a0, a0, a0
+	addi	a0, a0, -2
+	ret
+
+	.type	diamond,@function
+diamond:
+	blez	a0, .Lelse
+	li	a1, 1
+	add	a0, a0, a1
+	j	.Lend
+.Lelse:
+	li	a0, 2
+.Lend:
+	ret
+
+	.type	count_loop,@function
+count_loop:
+	li	a0, 0
+	li	a1, 5
+.Lloop:
+	add	a0, a0, a1
+	addi	a1, a1, -1
+	bnez	a1, .Lloop
+	ret
+
+	.type	leaf,@function
+leaf:
+	addi	a0, a0, 1
+	ret
+
+	.type	caller,@function
+caller:
+	mv	t0, ra
+	li	a0, 3
+	jal	leaf
+	addi	a0, a0, 1
+	mv	ra, t0
+	ret
@@ -0,0 +1,39 @@
+# executable with code in .init, .fini and .plt besides .text, and read-only data in a section
+# named like text
+	.section .init,"ax",@progbits
+	.globl	_init
+	.type	_init,@function
+_init:
+	callq	helper
+	retq
+
+	.section .fini,"ax",@progbits
+	.globl	_fini
+	.type	_fini,@function
+_fini:
+	retq
+
+	# stub of an external function, jumping to the address in its table
+	.section .plt,"ax",@progbits
+external:
+	jmpq	*table(%rip)
+
+	.text
+	.globl	_start
+	.type	_start,@function
+_start:
+	callq	external
+	callq	helper
+	retq
+
+	.type	helper,@function
+helper:
+	addl	$1, %eax
+	retq
+
+	.section .const_text,"a",@progbits
+	.byte	0xc3, 0xc3
+
+	.data
+table:
+	.quad	0
@@ -0,0 +1,66 @@
+# a conditional branch and an unconditional one in a function with its own register window,
+# restored in the delay slot of the return
+	.text
+
+	.type	window,@function
+window:
+	save	%sp, -176, %sp
+	cmp	%i0, 0
+	be	%icc, .Lzero
+	nop
+	add	%i0, 1, %i0
+	ba	.Lzero
+	add	%i0, 2, %i0
+	add	%i0, 3, %i0
+.Lzero:
+	ret
+	restore
+
+# straight line code, a conditional diamond, a loop and a function call
+	.type	straight,@function
+straight:
+	mov	%o0, %o1
+	add	%o1, 1, %o0
+	smul	%o0, %o0, %o0
+	retl
+	sub	%o0, 2, %o0
+
+	.type	diamond,@function
+diamond:
+	cmp	%o0, 0
+	ble	%icc, .Lelse
+	nop
+	mov	1, %o1
+	ba	.Lend
+	add	%o0, %o1, %o0
+.Lelse:
+	mov	2, %o0
+.Lend:
+	retl
+	nop
+
+	.type	count_loop,@function
+count_loop:
+	mov	0, %o0
+	mov	5, %o1
+.Lloop:
+	add	%o0, %o1, %o0
+	subcc	%o1, 1, %o1
+	bne	%icc, .Lloop
+	nop
+	retl
+	nop
+
+	.type	leaf,@function
+leaf:
+	retl
+	add	%o0, 1, %o0
+
+	.type	caller,@function
+caller:
+	save	%sp, -176, %sp
+	call	leaf
+	mov	3, %o0
+	add	%o0, 1, %i0
+	ret
+	restore
@@ -0,0 +1,46 @@
+# straight line code, a conditional diamond, a loop and a function call
+	.text
+
+	.type	straight,@function
+straight:
+	lr	%r3, %r2
+	la	%r2, 1(%r3)
+	msr	%r2, %r2
+	ahi	%r2, -2
+	br	%r14
+
+	.type	diamond,@function
+diamond:
+	chi	%r2, 0
+	jle	.Lelse
+	lhi	%r3, 1
+	ar	%r2, %r3
+	j	.Lend
+.Lelse:
+	lhi	%r2, 2
+.Lend:
+	br	%r14
+
+	.type	count_loop,@function
+count_loop:
+	lhi	%r2, 0
+	lhi	%r3, 5
+.Lloop:
+	ar	%r2, %r3
+	ahi	%r3, -1
+	jne	.Lloop
+	br	%r14
+
+	.type	leaf,@function
+leaf:
+	ahi	%r2, 1
+	br	%r14
+
+	.type	caller,@function
+caller:
+	lgr	%r0, %r14
+	lhi	%r2, 3
+	brasl	%r14, leaf
+	ahi	%r2, 1
+	lgr	%r14, %r0
+	br	%r14
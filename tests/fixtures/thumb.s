@@ -0,0 +1,48 @@
+@ straight line code, a conditional diamond, a loop and a function call, in Thumb mode
+	.text
+	.thumb
+	.syntax	unified
+
+	.type	straight,%function
+straight:
+	mov	r1, r0
+	adds	r0, r1, #1
+	muls	r0, r0, r0
+	subs	r0, r0, #2
+	bx	lr
+
+	.type	diamond,%function
+diamond:
+	cmp	r0, #0
+	ble	.Lelse
+	movs	r1, #1
+	adds	r0, r0, r1
+	b	.Lend
+.Lelse:
+	movs	r0, #2
+.Lend:
+	bx	lr
+
+	.type	count_loop,%function
+count_loop:
+	movs	r0, #0
+	movs	r1, #5
+.Lloop:
+	adds	r0, r0, r1
+	subs	r1, r1, #1
+	bne	.Lloop
+	bx	lr
+
+	.type	leaf,%function
+leaf:
+	adds	r0, r0, #1
+	bx	lr
+
+	.type	caller,%function
+caller:
+	push	{r7, lr}
+	movs	r0, #3
+	bl	leaf
+	adds	r0, r0, #1
+	pop	{r7, lr}
+	bx	lr
@@ -0,0 +1,99 @@
+# straight line code, a conditional diamond, a loop, a function call, two nested loops, a call never returning and a recursive function
+	.text
+
+	.type	straight,@function
+straight:
+	movl	%edi, %eax
+	addl	$1, %eax
+	imull	%eax, %eax
+	subl	$2, %eax
+	retq
+
+	.type	diamond,@function
+diamond:
+	cmpl	$0, %edi
+	jle	.Lelse
+	movl	$1, %eax
+	addl	%edi, %eax
+	jmp	.Lend
+.Lelse:
+	movl	$2, %eax
+.Lend:
+	retq
+
+	.type	count_loop,@function
+count_loop:
+	xorl	%eax, %eax
+	movl	$5, %ecx
+.Lloop:
+	addl	%ecx, %eax
+	decl	%ecx
+	jne	.Lloop
+	retq
+
+	.type	leaf,@function
+leaf:
+	leal	1(%rdi), %eax
+	retq
+
+	.type	caller,@function
+caller:
+	movl	$3, %edi
+	callq	leaf
+	addl	$1, %eax
+	retq
+
+	.type	nested_loop,@function
+nested_loop:
+	xorl	%eax, %eax
+	movl	$3, %ecx
+.Louter:
+	movl	$4, %edx
+.Linner:
+	addl	%edx, %eax
+	decl	%edx
+	jne	.Linner
+	decl	%ecx
+	jne	.Louter
+	retq
+
+	.type	abort,@function
+abort:
+	retq
+
+	.type	checked,@function
+checked:
+	cmpl	$0, %edi
+	jne	.Lchecked
+	callq	abort
+	movl	$1, %eax
+	imull	%eax, %eax
+	imull	%eax, %eax
+.Lchecked:
+	retq
+
+	.type	countdown,@function
+countdown:
+	testl	%edi, %edi
+	je	.Lcountdown
+	decl	%edi
+	callq	countdown
+.Lcountdown:
+	retq
+
+	.type	recursion,@function
+recursion:
+	movl	$3, %edi
+	callq	countdown
+	retq
+
+	.type	task,@function
+task:
+	movl	$0, %eax
+.Ltask:
+	addl	$1, %eax
+	testl	$1, %eax
+	je	.Ltask_even
+	imull	%eax, %eax
+.Ltask_even:
+	jmp	.Ltask
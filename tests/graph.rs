@@ -0,0 +1,331 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use timing_analysis_tool::instruction::Instruction;
+use timing_analysis_tool::latency::LatencyTable;
+use timing_analysis_tool::{
+    analyze_graph, build_graph, merge_straight_line_blocks, Block, Config, ExitJump, MappedGraph,
+};
+
+fn block(leader: u64, latency: u32) -> Block {
+    Block::new(Instruction {
+        address: leader,
+        mnemonic: "nop".to_string(),
+        operands: Vec::new(),
+        size: 4,
+        latency,
+    })
+}
+
+fn edge_weight(graph: &MappedGraph, source: u64, target: u64) -> f64 {
+    graph
+        .get_edges()
+        .into_iter()
+        .find(|(edge_source, edge_target, _)| {
+            edge_source.leader == source && edge_target.leader == target
+        })
+        .map(|(_, _, weight)| weight)
+        .expect("Edge not found")
+}
+
+#[test]
+fn blocks_are_identified_by_their_leader() {
+    let mut longer = block(0x10, 1);
+    longer.add_instruction(Instruction {
+        address: 0x14,
+        mnemonic: "nop".to_string(),
+        operands: Vec::new(),
+        size: 4,
+        latency: 1,
+    });
+
+    assert!(block(0x10, 1) == longer);
+    assert!(block(0x10, 1) != block(0x20, 1));
+    assert_eq!(HashSet::from([block(0x10, 1), longer]).len(), 1);
+}
+
+#[test]
+fn duplicate_edge_keeps_the_highest_weight() {
+    for weights in [[2.0, 7.0], [7.0, 2.0]] {
+        let mut graph = MappedGraph::new();
+        for weight in weights {
+            graph.add_edge(block(0x0, 1), block(0x10, 2), weight);
+        }
+
+        assert_eq!(graph.get_edges().len(), 1);
+        assert_eq!(edge_weight(&graph, 0x0, 0x10), 7.0);
+    }
+}
+
+#[test]
+fn cycle_exit_is_the_last_exit_of_the_iteration() {
+    // the cycle 0x10 -> 0x30 -> 0x20 -> 0x10 exits from 0x30 and then from 0x20, which is
+    // reached from 0x30 and is chosen although its address is lower
+    let graph = MappedGraph::from_spec(
+        "
+        0x10 -> 0x30
+        0x30 -> 0x20
+        0x30 -> 0x40
+        0x20 -> 0x10
+        0x20 -> 0x50
+        0x0 -> 0x10
+        0x40 10
+        0x50 100
+        ",
+    )
+    .expect("Invalid spec");
+    let blocks = graph
+        .get_nodes()
+        .into_iter()
+        .map(|block| (block.leader, block))
+        .collect::<BTreeMap<u64, Block>>();
+    let config = Config {
+        graphs_dir: None,
+        ..Config::default()
+    };
+
+    let report = analyze_graph(
+        &graph,
+        &blocks,
+        &HashMap::new(),
+        &mut HashMap::new(),
+        &BTreeMap::new(),
+        &config,
+    )
+    .expect("Analysis failed");
+
+    assert_eq!(report.wcet, 107);
+}
+
+#[test]
+fn longest_path_between_two_blocks() {
+    let graph = MappedGraph::from_spec(
+        "
+        0x0 -> 0x10
+        0x0 -> 0x20
+        0x10 -> 0x30
+        0x20 -> 0x30
+        0x30 -> 0x40
+        0x10 5
+        0x20 2
+        0x40 7
+        ",
+    )
+    .expect("Invalid spec");
+    let block = |leader: u64| {
+        graph
+            .get_nodes()
+            .into_iter()
+            .find(|block| block.leader == leader)
+            .expect("Block not found")
+    };
+
+    let (latency, path) = graph
+        .longest_path_between(&block(0x0), &block(0x30))
+        .expect("Cyclic graph")
+        .expect("Unreachable block");
+    assert_eq!(latency, 6.0);
+    assert_eq!(
+        path.iter().map(|block| block.leader).collect::<Vec<u64>>(),
+        [0x0, 0x10, 0x30]
+    );
+
+    assert!(graph
+        .longest_path_between(&block(0x10), &block(0x20))
+        .expect("Cyclic graph")
+        .is_none());
+}
+
+#[test]
+fn longest_path_without_exit_blocks() {
+    // every block jumps back into the cycle, the path ends at the farthest block
+    let graph = MappedGraph::from_spec(
+        "
+        0x0 -> 0x10 2
+        0x0 -> 0x20 5
+        0x10 -> 0x20 0
+        0x20 -> 0x10 0
+        ",
+    )
+    .expect("Invalid spec");
+    let entry = graph
+        .get_nodes()
+        .into_iter()
+        .find(|block| block.leader == 0x0)
+        .expect("Block not found");
+
+    assert_eq!(graph.longest_path(&entry).expect("Cyclic graph"), 5.0);
+}
+
+#[test]
+fn exit_blocks() {
+    // 0x10 returns to 0x20, 0x30 tail calls a function outside the graph
+    let mut ret = block(0x10, 5);
+    ret.set_exit_jump(ExitJump::Ret(0x20));
+    let mut tail_call = block(0x30, 3);
+    tail_call.set_exit_jump(ExitJump::UnconditionalAbsolute(0x1000));
+
+    let mut graph = MappedGraph::new();
+    graph.add_edge(block(0x0, 1), ret.clone(), 5.0);
+    graph.add_edge(ret.clone(), block(0x20, 7), 7.0);
+    graph.add_edge(block(0x0, 1), tail_call.clone(), 3.0);
+
+    let mut exit_blocks = graph
+        .exit_blocks()
+        .iter()
+        .map(|block| block.leader)
+        .collect::<Vec<u64>>();
+    exit_blocks.sort();
+    assert_eq!(exit_blocks, [0x10, 0x20, 0x30]);
+    assert!(!graph.is_exit_block(&block(0x0, 1)));
+
+    // the longest path goes on after the return, to the farthest exit block
+    assert_eq!(graph.longest_path(&block(0x0, 1)), Ok(12.0));
+    assert_eq!(
+        graph
+            .longest_path_trace(&block(0x0, 1))
+            .expect("Cyclic graph")
+            .iter()
+            .map(|block| block.leader)
+            .collect::<Vec<u64>>(),
+        [0x0, 0x10, 0x20]
+    );
+}
+
+#[test]
+fn longest_path_to_exits() {
+    // 0x30 and 0x40 are the exits reachable from 0x10, 0x50 is not reachable
+    let mut graph = MappedGraph::from_spec(
+        "
+        0x0 -> 0x10 1
+        0x10 -> 0x20 2
+        0x20 -> 0x30 3
+        0x10 -> 0x40 4
+        0x0 -> 0x50 9
+        ",
+    )
+    .expect("Invalid spec");
+    let condensed_graph = graph.condense_cycles();
+    let node = |leader: u64| {
+        condensed_graph
+            .scc_of(leader)
+            .expect("Block not found")
+            .to_vec()
+    };
+
+    assert_eq!(condensed_graph.longest_path_to_exits(&node(0x10)), Ok(5.0));
+    assert_eq!(condensed_graph.longest_path_to_exits(&node(0x30)), Ok(0.0));
+}
+
+#[test]
+fn graphml_export() {
+    let mut graph = MappedGraph::from_spec(
+        "
+        0x0 -> 0x10 3
+        0x10 -> 0x20
+        0x20 -> 0x10
+        0x10 2
+        ",
+    )
+    .expect("Invalid spec");
+
+    let graphml = graph.to_graphml();
+    assert!(graphml.starts_with("<?xml"));
+    assert_eq!(graphml.matches("<node ").count(), 3);
+    assert_eq!(graphml.matches("<edge ").count(), 3);
+    assert!(graphml
+        .contains("<edge source=\"0x0\" target=\"0x10\">\n      <data key=\"weight\">3</data>"));
+    assert!(graphml.contains("<data key=\"exit_jump\">UnconditionalAbsolute</data>"));
+
+    // the cycle 0x10 -> 0x20 -> 0x10 is a single node with the latency of its blocks
+    let condensed_graphml = graph.condense_cycles().to_graphml();
+    assert_eq!(condensed_graphml.matches("<node ").count(), 2);
+    assert!(condensed_graphml.contains(
+        "<data key=\"latency\">3</data>\n      <data key=\"exit_jump\">Cycle</data>\n      <data key=\"blocks\">0x10 0x20</data>"
+    ));
+}
+
+#[test]
+fn scc_membership() {
+    // the cycle 0x10 <-> 0x20 is entered from both its blocks (irreducible)
+    let mut graph = MappedGraph::from_spec(
+        "
+        0x0 -> 0x10
+        0x0 -> 0x20
+        0x10 -> 0x20
+        0x20 -> 0x10
+        0x20 -> 0x30
+        ",
+    )
+    .expect("Invalid spec");
+    let condensed_graph = graph.condense_cycles();
+
+    let leaders = |blocks: &[Block]| blocks.iter().map(|block| block.leader).collect::<Vec<_>>();
+    assert_eq!(condensed_graph.scc_of(0x0).map(leaders), Some(vec![0x0]));
+    assert_eq!(
+        condensed_graph.scc_of(0x20).map(leaders),
+        Some(vec![0x10, 0x20])
+    );
+    assert!(condensed_graph.scc_of(0x40).is_none());
+    assert_eq!(
+        condensed_graph.scc_membership(),
+        BTreeMap::from([(0x0, 0x0), (0x10, 0x10), (0x20, 0x10), (0x30, 0x30)])
+    );
+}
+
+#[test]
+fn straight_line_blocks_merged() {
+    // 0x0 falls through to the loop 0x10 -> 0x20 -> 0x10, which exits to 0x30 -> 0x40
+    let exit_jumps = [
+        (0x0, ExitJump::Next(0x10)),
+        (0x10, ExitJump::Next(0x20)),
+        (
+            0x20,
+            ExitJump::ConditionalAbsolute {
+                taken: 0x10,
+                not_taken: 0x30,
+            },
+        ),
+        (0x30, ExitJump::Next(0x40)),
+        (0x40, ExitJump::Ret(0)),
+    ];
+    let mut blocks = exit_jumps
+        .into_iter()
+        .map(|(leader, exit_jump)| {
+            let mut block = block(leader, (leader / 0x10 + 1) as u32);
+            block.set_exit_jump(exit_jump);
+            (leader, block)
+        })
+        .collect::<BTreeMap<u64, Block>>();
+
+    let wcet = |blocks: &BTreeMap<u64, Block>| {
+        let graph = build_graph(blocks, &LatencyTable::default()).expect("Invalid costs");
+        let config = Config {
+            graphs_dir: None,
+            ..Config::default()
+        };
+        analyze_graph(
+            &graph,
+            blocks,
+            &HashMap::new(),
+            &mut HashMap::new(),
+            &BTreeMap::new(),
+            &config,
+        )
+        .expect("Analysis failed")
+        .wcet
+    };
+    let wcet_before = wcet(&blocks);
+
+    // 0x10 has two predecessors and 0x30 follows a conditional jump
+    assert_eq!(merge_straight_line_blocks(&mut blocks, &HashSet::new()), 2);
+    assert_eq!(
+        blocks.keys().copied().collect::<Vec<_>>(),
+        [0x0, 0x10, 0x30]
+    );
+    assert_eq!(blocks[&0x10].get_latency(), 2 + 3);
+    assert!(matches!(
+        blocks[&0x10].exit_jump,
+        Some(ExitJump::ConditionalAbsolute { taken: 0x10, .. })
+    ));
+    assert_eq!(wcet(&blocks), wcet_before);
+}
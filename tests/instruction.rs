@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use capstone::Arch;
+
+use timing_analysis_tool::config::CacheConfig;
+use timing_analysis_tool::instruction::{split_operands, Instruction};
+use timing_analysis_tool::latency::{
+    CpuModel, LatencyLayer, LatencyTable, MnemonicLatency, OperandKind, OperandRule,
+};
+use timing_analysis_tool::{Block, Syntax};
+
+#[test]
+fn operands_split_outside_brackets() {
+    let cases: [(&str, &[&str]); 7] = [
+        ("", &[]),
+        ("rax", &["rax"]),
+        ("rax, rbx, 8", &["rax", "rbx", "8"]),
+        (
+            "eax, dword ptr [rbx + rcx*4 + 8], 3",
+            &["eax", "dword ptr [rbx + rcx*4 + 8]", "3"],
+        ),
+        (
+            "$8, 0x10(%rax,%rbx,4), %ecx",
+            &["$8", "0x10(%rax,%rbx,4)", "%ecx"],
+        ),
+        ("r0, r1, r2, lsl #2", &["r0", "r1", "r2", "lsl #2"]),
+        ("sp!, {r4, r5, lr}", &["sp!", "{r4, r5, lr}"]),
+    ];
+
+    for (op_str, expected) in cases {
+        assert_eq!(split_operands(op_str), expected, "operands of {op_str:?}");
+    }
+}
+
+#[test]
+fn rule_matches_the_third_operand() {
+    // imul with an immediate multiplier is slower than the two-operand form
+    let latency = MnemonicLatency::Rules {
+        base: 3,
+        rules: vec![OperandRule {
+            operands: vec![OperandKind::Reg, OperandKind::Reg, OperandKind::Imm],
+            latency: 4,
+        }],
+    };
+
+    assert_eq!(latency.resolve(&split_operands("rax, rbx, 8")), 4);
+    assert_eq!(latency.resolve(&split_operands("rax, rbx")), 3);
+    assert_eq!(
+        latency.resolve(&split_operands("rax, qword ptr [rbx], 8")),
+        3
+    );
+}
+
+fn instruction(address: u64, mnemonic: &str, op_str: &str) -> Instruction {
+    Instruction {
+        address,
+        mnemonic: mnemonic.to_string(),
+        operands: split_operands(op_str),
+        size: 2,
+        latency: 1,
+    }
+}
+
+#[test]
+fn read_after_write_hazards() {
+    let listing = [
+        ("mov", "rax, 1"),
+        ("mov", "ecx, dword ptr [rax + 4]"), // base register written by the previous one
+        ("cmp", "ecx, 0"),                   // reads ecx
+        ("add", "ecx, 1"),                   // the comparison writes no register
+        ("mov", "dword ptr [rbx], ecx"),     // reads ecx
+        ("mov", "edx, ecx"),                 // the store writes no register
+        ("lw", "$v0, 0($a0)"),
+        ("addiu", "$v0, $v0, 1"), // reads $v0
+    ];
+    let mut block = Block::new(instruction(0x0, listing[0].0, listing[0].1));
+    for (index, (mnemonic, op_str)) in listing.iter().enumerate().skip(1) {
+        block.add_instruction(instruction(index as u64, mnemonic, op_str));
+    }
+
+    assert_eq!(block.add_hazard_penalties(2), 4);
+    assert_eq!(
+        block
+            .instructions
+            .iter()
+            .map(|instruction| instruction.latency)
+            .collect::<Vec<u32>>(),
+        [1, 3, 3, 1, 3, 1, 1, 3]
+    );
+}
+
+#[test]
+fn cache_lines_touched() {
+    // lines of 16 bytes: the third instruction starts the second one and the last instruction
+    // spans the second and the third one
+    let mut block = Block::new(instruction(0xc, "mov", "rax, 1"));
+    for address in [0xe, 0x10, 0x1e] {
+        block.add_instruction(instruction(address, "add", "rax, 1"));
+    }
+    block.instructions[3].size = 4;
+    let cache = CacheConfig {
+        line_size: 16,
+        miss_penalty: 10,
+    };
+
+    assert_eq!(block.byte_range(), 0xc..0x22);
+    assert_eq!(block.add_cache_miss_penalties(&cache), 3);
+    assert_eq!(
+        block
+            .instructions
+            .iter()
+            .map(|instruction| instruction.latency)
+            .collect::<Vec<u32>>(),
+        [11, 1, 11, 11]
+    );
+}
+
+#[test]
+fn invalid_environment_latency_ignored() {
+    // an invalid value falls back to the latency without the variable
+    std::env::set_var("X86_UD2", "notanumber");
+    let latency_table = LatencyTable::default();
+    assert_eq!(
+        latency_table.get_latency(Arch::X86, Syntax::Intel, "ud2", &[], &[]),
+        latency_table.default
+    );
+
+    std::env::set_var("X86_UD2", "7");
+    assert_eq!(
+        latency_table.get_latency(Arch::X86, Syntax::Intel, "ud2", &[], &[]),
+        7
+    );
+    std::env::remove_var("X86_UD2");
+}
+
+#[test]
+fn size_suffix_only_in_att_syntax() {
+    // the last letter of an Intel mnemonic is not the size of its operands
+    let mut latency_table = LatencyTable::default();
+    latency_table.archs.insert(
+        "X86".to_string(),
+        HashMap::from([("SH".to_string(), MnemonicLatency::Fixed(9))]),
+    );
+    assert_eq!(
+        latency_table.get_latency(Arch::X86, Syntax::Att, "shl", &[], &[]),
+        9
+    );
+    assert_eq!(
+        latency_table.get_latency(Arch::X86, Syntax::Intel, "shl", &[], &[]),
+        latency_table.default
+    );
+}
+
+#[test]
+fn latency_file_layered_on_cpu_model() {
+    // the file omits the penalties, which are kept from the table it is layered on
+    let path = std::env::temp_dir().join(format!("latencies-{}.toml", std::process::id()));
+    std::fs::write(&path, "default = 2\n\n[X86]\nimul = 4\n").unwrap();
+    let layer = LatencyLayer::from_file(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    let mut latency_table = LatencyTable {
+        branch_penalty: 3,
+        call_latency: 5,
+        ..LatencyTable::for_cpu(CpuModel::Skylake)
+    };
+    latency_table.merge(layer.expect("Invalid latency file"));
+    assert_eq!(latency_table.default, 2);
+    assert_eq!(latency_table.branch_penalty, 3);
+    assert_eq!(latency_table.call_latency, 5);
+    assert_eq!(
+        latency_table.get_latency(Arch::X86, Syntax::Intel, "imul", &[], &[]),
+        4
+    );
+}
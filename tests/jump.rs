@@ -0,0 +1,332 @@
+use capstone::{Arch, Capstone, ExtraMode, Mode, NO_EXTRA_MODE};
+
+use timing_analysis_tool::indirect::IndirectTargets;
+use timing_analysis_tool::jump::{get_exit_jump, it_block_length};
+use timing_analysis_tool::{ExitJump, Syntax};
+
+const ADDRESS: u64 = 0x1000;
+
+/// Returns the exit jump of the single instruction encoded by `code` at `ADDRESS`.
+fn exit_jump(arch: Arch, mode: Mode, code: &[u8]) -> Option<ExitJump> {
+    exit_jump_in_syntax(arch, mode, Syntax::Intel, code)
+}
+
+/// Returns the exit jump of the single instruction encoded by `code` at `ADDRESS`, disassembled
+/// in `syntax`, with the compressed instructions of RISC-V as in the analysis.
+fn exit_jump_in_syntax(arch: Arch, mode: Mode, syntax: Syntax, code: &[u8]) -> Option<ExitJump> {
+    let extra_mode = (arch == Arch::RISCV).then_some(ExtraMode::RiscVC);
+    let mut cs =
+        Capstone::new_raw(arch, mode, extra_mode.into_iter(), None).expect("Invalid capstone");
+    cs.set_detail(true).expect("Unable to enable the details");
+    if syntax == Syntax::Att {
+        cs.set_syntax(capstone::Syntax::Att)
+            .expect("Unable to set the syntax");
+    }
+
+    let insns = cs
+        .disasm_count(code, ADDRESS, 1)
+        .expect("Unable to disassemble");
+    let insn = insns.first().expect("No instruction");
+    let insn_detail = cs.insn_detail(insn).expect("No details");
+    let next_address = ADDRESS + insn.bytes().len() as u64;
+
+    get_exit_jump(
+        insn,
+        next_address,
+        &insn_detail,
+        arch,
+        syntax,
+        &IndirectTargets::default(),
+    )
+    .expect("Unable to recognize the jump")
+}
+
+fn conditional(taken: u64, not_taken: u64) -> Option<ExitJump> {
+    Some(ExitJump::ConditionalRelative { taken, not_taken })
+}
+
+/// Checks the exit jump of each instruction, given as (encoding, mnemonic, expected exit jump).
+fn check(arch: Arch, mode: Mode, cases: &[(&[u8], &str, Option<ExitJump>)]) {
+    for (code, mnemonic, expected) in cases {
+        assert_eq!(
+            &exit_jump(arch, mode, code),
+            expected,
+            "exit jump of {mnemonic}"
+        );
+    }
+}
+
+#[test]
+fn arm_conditional_branches() {
+    check(
+        Arch::ARM,
+        Mode::Arm,
+        &[
+            (
+                &[0x02, 0x00, 0x00, 0x0a],
+                "beq",
+                conditional(0x1010, 0x1004),
+            ),
+            (
+                &[0x02, 0x00, 0x00, 0x1a],
+                "bne",
+                conditional(0x1010, 0x1004),
+            ),
+            (&[0xfc, 0xff, 0xff, 0xaa], "bge", conditional(0xff8, 0x1004)),
+            (
+                &[0x02, 0x00, 0x00, 0x3a],
+                "bcc",
+                conditional(0x1010, 0x1004),
+            ),
+            (
+                &[0x02, 0x00, 0x00, 0xea],
+                "b",
+                Some(ExitJump::UnconditionalRelative(0x1010)),
+            ),
+        ],
+    );
+}
+
+#[test]
+fn arm_conditional_call() {
+    // the conditional calls are considered always taken
+    check(
+        Arch::ARM,
+        Mode::Arm,
+        &[(
+            &[0x06, 0x00, 0x00, 0x0b],
+            "bleq",
+            Some(ExitJump::Call(0x1020, 0x1004)),
+        )],
+    );
+}
+
+#[test]
+fn thumb_conditional_branches() {
+    check(
+        Arch::ARM,
+        Mode::Thumb,
+        &[
+            (&[0x06, 0xd0], "beq", conditional(0x1010, 0x1002)),
+            (&[0x30, 0xb1], "cbz", conditional(0x1010, 0x1002)),
+            (
+                &[0x00, 0xf0, 0x06, 0xb8],
+                "b.w",
+                Some(ExitJump::UnconditionalRelative(0x1010)),
+            ),
+        ],
+    );
+}
+
+#[test]
+fn link_register_returns() {
+    check(
+        Arch::ARM,
+        Mode::Arm,
+        &[(&[0x1e, 0xff, 0x2f, 0xe1], "bx lr", Some(ExitJump::Ret(0)))],
+    );
+    check(
+        Arch::ARM,
+        Mode::Thumb,
+        &[(&[0x80, 0xbd], "pop {r7, pc}", Some(ExitJump::Ret(0)))],
+    );
+}
+
+#[test]
+fn ppc_calls_and_returns() {
+    check(
+        Arch::PPC,
+        Mode::Mode64,
+        &[
+            (
+                &[0x11, 0x00, 0x00, 0x48],
+                "bl",
+                Some(ExitJump::Call(0x1010, 0x1004)),
+            ),
+            (&[0x20, 0x00, 0x80, 0x4e], "blr", Some(ExitJump::Ret(0))),
+        ],
+    );
+}
+
+#[test]
+fn mips_calls_and_returns() {
+    check(
+        Arch::MIPS,
+        Mode::Mips32,
+        &[
+            (
+                &[0x03, 0x00, 0x11, 0x04],
+                "bal",
+                Some(ExitJump::Call(0x1010, 0x1004)),
+            ),
+            (&[0x08, 0x00, 0xe0, 0x03], "jr $ra", Some(ExitJump::Ret(0))),
+        ],
+    );
+}
+
+#[test]
+fn systemz_calls_and_returns() {
+    check(
+        Arch::SYSZ,
+        Mode::Default,
+        &[
+            (
+                &[0xc0, 0xe5, 0x00, 0x00, 0x00, 0x08],
+                "brasl %r14",
+                Some(ExitJump::Call(0x1010, 0x1006)),
+            ),
+            (&[0x07, 0xfe], "br %r14", Some(ExitJump::Ret(0))),
+        ],
+    );
+}
+
+#[test]
+fn thumb_it_blocks() {
+    let mut cs =
+        Capstone::new_raw(Arch::ARM, Mode::Thumb, NO_EXTRA_MODE, None).expect("Invalid capstone");
+    cs.set_detail(true).expect("Unable to enable the details");
+
+    for (code, mnemonic, length) in [
+        ([0x08, 0xbf], "it eq", 1),
+        ([0x0c, 0xbf], "ite eq", 2),
+        ([0x01, 0xbf], "itttt eq", 4),
+        ([0x0b, 0xbf], "itete eq", 4),
+        ([0x00, 0xbf], "nop", 0),
+    ] {
+        let insns = cs
+            .disasm_count(&code, ADDRESS, 1)
+            .expect("Unable to disassemble");
+        let insn = insns.first().expect("No instruction");
+        assert_eq!(
+            it_block_length(insn, Arch::ARM),
+            length,
+            "instructions of {mnemonic}"
+        );
+    }
+}
+
+#[test]
+fn arm64_conditional_branches() {
+    check(
+        Arch::ARM64,
+        Mode::Arm,
+        &[
+            (
+                &[0x80, 0x00, 0x00, 0x54],
+                "b.eq",
+                conditional(0x1010, 0x1004),
+            ),
+            (
+                &[0x81, 0x00, 0x00, 0x54],
+                "b.ne",
+                conditional(0x1010, 0x1004),
+            ),
+            (
+                &[0x80, 0x00, 0x00, 0xb4],
+                "cbz",
+                conditional(0x1010, 0x1004),
+            ),
+            (
+                &[0x04, 0x00, 0x00, 0x14],
+                "b",
+                Some(ExitJump::UnconditionalRelative(0x1010)),
+            ),
+        ],
+    );
+}
+
+#[test]
+fn riscv_compressed_branches() {
+    check(
+        Arch::RISCV,
+        Mode::RiscV32,
+        &[
+            (&[0x01, 0xca], "c.beqz a0, 16", conditional(0x1010, 0x1002)),
+            (&[0x01, 0xea], "c.bnez a0, 16", conditional(0x1010, 0x1002)),
+            (
+                &[0x01, 0xa8],
+                "c.j 16",
+                Some(ExitJump::UnconditionalRelative(0x1010)),
+            ),
+            (
+                &[0x01, 0x28],
+                "c.jal 16",
+                Some(ExitJump::Call(0x1010, 0x1002)),
+            ),
+            (&[0x02, 0x85], "c.jr a0", Some(ExitJump::Indirect)),
+            (&[0x82, 0x80], "c.jr ra", Some(ExitJump::Ret(0))),
+        ],
+    );
+}
+
+#[test]
+fn x86_direct_and_indirect_calls() {
+    // only the immediate operand is a target, the displacement of a memory operand is not
+    check(
+        Arch::X86,
+        Mode::Mode64,
+        &[
+            (
+                &[0xe8, 0x0b, 0x00, 0x00, 0x00],
+                "call 0x1010",
+                Some(ExitJump::Call(0x1010, 0x1005)),
+            ),
+            (&[0xff, 0xd0], "call rax", Some(ExitJump::Indirect)),
+            (
+                &[0xff, 0x15, 0x10, 0x00, 0x00, 0x00],
+                "call qword ptr [rip + 0x10]",
+                Some(ExitJump::Indirect),
+            ),
+            (
+                &[0xff, 0x90, 0x00, 0x10, 0x00, 0x00],
+                "call qword ptr [rax + 0x1000]",
+                Some(ExitJump::Indirect),
+            ),
+        ],
+    );
+    check(
+        Arch::X86,
+        Mode::Mode32,
+        &[(
+            &[0xff, 0x15, 0x00, 0x20, 0x00, 0x00],
+            "call dword ptr [0x2000]",
+            Some(ExitJump::Indirect),
+        )],
+    );
+}
+
+#[test]
+fn x86_jumps_in_both_syntaxes() {
+    // `jmpq *%rax` and `callq *0x10(%rip)` in AT&T are the same jumps as in Intel
+    let cases: &[(&[u8], Option<ExitJump>)] = &[
+        (
+            &[0xe9, 0x0b, 0x00, 0x00, 0x00],
+            Some(ExitJump::UnconditionalRelative(0x1010)),
+        ),
+        (&[0xeb, 0x0e], Some(ExitJump::UnconditionalRelative(0x1010))),
+        (&[0xff, 0xe0], Some(ExitJump::Indirect)),
+        (
+            &[0xff, 0x25, 0x10, 0x00, 0x00, 0x00],
+            Some(ExitJump::Indirect),
+        ),
+        (
+            &[0xe8, 0x0b, 0x00, 0x00, 0x00],
+            Some(ExitJump::Call(0x1010, 0x1005)),
+        ),
+        (
+            &[0xff, 0x15, 0x10, 0x00, 0x00, 0x00],
+            Some(ExitJump::Indirect),
+        ),
+        (&[0x74, 0x0e], conditional(0x1010, 0x1002)),
+        (&[0xc3], Some(ExitJump::Ret(0))),
+    ];
+    for (code, expected) in cases {
+        for syntax in [Syntax::Intel, Syntax::Att] {
+            assert_eq!(
+                &exit_jump_in_syntax(Arch::X86, Mode::Mode64, syntax, code),
+                expected,
+                "exit jump of {code:02x?} in {syntax:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use timing_analysis_tool::bounds::LoopBounds;
+use timing_analysis_tool::latency::{LatencyTable, MnemonicLatency};
+use timing_analysis_tool::{analyze_bytes, Config, EntryMode};
+
+use crate::common::function_address;
+
+mod common;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+/// Latency table of the measurements: every instruction takes 1 clock cycle, except the
+/// multiplications (3), and the taken conditional branches and the calls pay a penalty. The
+/// returns don't, since the return of the analyzed function has no edge to charge it to.
+fn latency_table() -> LatencyTable {
+    LatencyTable {
+        branch_penalty: 1,
+        call_latency: 2,
+        archs: HashMap::from([(
+            "X86".to_string(),
+            HashMap::from([("IMUL".to_string(), MnemonicLatency::Fixed(3))]),
+        )]),
+        ..LatencyTable::default()
+    }
+}
+
+/// Analyzes the ELF `file` of the fixtures from `function` (the entry point of the program if
+/// None) with `latency_table` and the loop bounds of *bounds.toml*, and returns the WCET with
+/// the measured costs it doesn't bound: the WCET must be an upper bound of the cost of every
+/// execution, so each of them is a soundness bug.
+fn under_approximations(
+    file: &str,
+    function: Option<&str>,
+    latency_table: LatencyTable,
+    measured: &[u64],
+) -> (u64, Vec<u64>) {
+    let bytes = std::fs::read(Path::new(FIXTURES).join(file)).expect("Unable to read the fixture");
+    let entry = function.map(|function| function_address(&bytes, function));
+
+    let config = Config {
+        latency_table,
+        loop_bounds: LoopBounds::from_file(&Path::new(FIXTURES).join("bounds.toml"))
+            .expect("Invalid loop bounds file"),
+        graphs_dir: None,
+        entry,
+        entry_mode: EntryMode::Program,
+        ..Config::default()
+    };
+    let report = analyze_bytes(&bytes, &config).expect("Analysis failed");
+
+    let under_approximations = measured
+        .iter()
+        .filter(|cost| **cost > report.wcet)
+        .copied()
+        .collect();
+    (report.wcet, under_approximations)
+}
+
+/// Checks that the WCET of `function` bounds the measured costs of its executions.
+fn assert_sound(file: &str, function: Option<&str>, measured: &[u64]) {
+    let (wcet, under_approximations) =
+        under_approximations(file, function, latency_table(), measured);
+    assert!(
+        under_approximations.is_empty(),
+        "The WCET of {} in {file} ({wcet} clock cycles) is lower than the measured costs {under_approximations:?}",
+        function.unwrap_or("the program")
+    );
+}
+
+// the costs below are computed by hand from the instructions executed by each input
+
+#[test]
+fn straight_line() {
+    // mov, add, imul (3), sub, ret
+    assert_sound("x86_64.o", Some("straight"), &[7]);
+}
+
+#[test]
+fn both_paths_of_a_branch() {
+    // cmp, jle, mov, add, jmp, ret if the argument is positive, otherwise cmp, jle (1 + 1),
+    // mov, ret
+    assert_sound("x86_64.o", Some("diamond"), &[6, 5]);
+}
+
+#[test]
+fn loop_iterations() {
+    // xor, mov, then add, dec, jne (1 + 1 if taken) for each of the 5 iterations, ret
+    assert_sound("x86_64.o", Some("count_loop"), &[2 + 5 * 3 + 4 + 1]);
+
+    // 3 iterations of the outer loop, each one with 4 of the inner loop
+    let inner = 4 * 3 + 3; // add, dec, jne, with the jne taken 3 times
+    let outer = 3 * (1 + inner + 2) + 2; // mov, inner loop, dec, jne, with the jne taken twice
+    assert_sound("x86_64.o", Some("nested_loop"), &[2 + outer + 1]);
+}
+
+#[test]
+fn call_and_loop_of_an_executable() {
+    // mov, call (1 + 2), then xor, 3 iterations of add, dec, jne (taken twice) and ret of
+    // count, add and ret of _start
+    assert_sound("pie.elf", None, &[3 + (1 + 3 * 3 + 2 + 1) + 2]);
+}
+
+#[test]
+fn under_approximation_is_flagged() {
+    let (wcet, under_approximations) =
+        under_approximations("x86_64.o", Some("straight"), latency_table(), &[7, 8, 100]);
+    assert_eq!(wcet, 7);
+    assert_eq!(under_approximations, [8, 100]);
+}